@@ -1,15 +1,122 @@
-use crate::python::{PythonEnvironment, Package};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::fuzzy;
+use crate::package_index;
+use crate::config::{Template, MAX_SIDEBAR_PERCENT, MIN_SIDEBAR_PERCENT};
+use crate::history::HistoryEntry;
+use crate::jupyter::Kernel;
+use crate::requirements::RequirementsDiff;
+use crate::usage::Usage;
+use crate::python::{CreationBackend, InstallOptions, PythonEnvironment, Package, PackageManager, PackageMetadata, PipInfo, active_conda_environment, environment_created, normalize_package_name, pyenv_global};
+
+const MAX_LOG_LINES: usize = 1000;
+/// How many of the most recent history entries the history view loads
+pub const MAX_HISTORY_ENTRIES: usize = 500;
+
+/// Package filter prefix that matches on license instead of name
+pub const LICENSE_FILTER_PREFIX: &str = "license:";
+
+/// Normal-mode keys that change environments, disabled in read-only mode
+pub const READ_ONLY_KEYS: [char; 11] = ['n', 'd', 'i', 'I', 'r', 'f', 'V', 'U', '!', 'C', 'M'];
+
+/// Environment types in the order the grouped environment list shows them
+pub const ENV_GROUPS: [&str; 8] = ["venv", "conda", "mamba", "pyenv", "pipx", "hatch", "system", "unknown"];
+
+/// The `ENV_GROUPS` entry an environment type is shown under.
+pub fn env_group(env_type: &str) -> &'static str {
+    ENV_GROUPS.iter().find(|&&group| group == env_type).copied().unwrap_or("unknown")
+}
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum AppState {
     Normal,
-    PackageView,
     CreateEnvironment,
+    PickTemplate,
+    ConfirmOverwrite,
+    PickVersion,
+    DiskUsage,
+    DotenvView,
+    KernelView,
+    SysPathView,
+    CompareRequirements,
+    RequirementsDiffView,
+    ConfirmRemoveKernel,
     DeleteEnvironment,
     InstallPackage,
+    InstallPreview,
     UninstallPackage,
+    ReinstallPackage,
     SearchEnvironment,
+    FilterPackages,
+    PipCommand,
+    LogView,
+    HistoryView,
+    PackageDetails,
+    PackageFiles,
+    CleanupBroken,
+    ConfirmBreakSystem,
+    ConfirmEnsurepip,
+    ConfirmUpgrade,
+    ConfirmUpgradeAll,
     HelpMenu,
+    ConfirmQuit,
+    ConfirmPyenvGlobal,
+    ConfirmCondaExport,
+}
+
+/// Order of the environment list.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvSort {
+    /// The order environments were detected in
+    Detected,
+    /// Alphabetically by name, ignoring case
+    Name,
+    /// Oldest first, to find stale environments
+    Age,
+    /// Most recently opened first
+    Recent,
+    /// Most often opened first
+    Frequent,
+}
+
+impl EnvSort {
+    pub fn next(self) -> Self {
+        match self {
+            EnvSort::Detected => EnvSort::Name,
+            EnvSort::Name => EnvSort::Age,
+            EnvSort::Age => EnvSort::Recent,
+            EnvSort::Recent => EnvSort::Frequent,
+            EnvSort::Frequent => EnvSort::Detected,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            EnvSort::Detected => "detected",
+            EnvSort::Name => "name",
+            EnvSort::Age => "age",
+            EnvSort::Recent => "recent use",
+            EnvSort::Frequent => "frequent use",
+        }
+    }
+}
+
+/// Package view state of a tab that isn't currently shown. The shown tab's
+/// state lives directly on `App` and is swapped in and out on tab switches.
+#[derive(Debug, Default)]
+pub struct EnvTab {
+    /// Environment the tab was showing
+    pub env_path: Option<PathBuf>,
+    packages: Vec<Package>,
+    selected_package: Option<usize>,
+    package_filter: String,
+    outdated: Option<HashMap<String, String>>,
+    leaves: Option<HashSet<String>>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -26,6 +133,8 @@ pub enum Focus {
 
 pub struct App {
     pub state: AppState,
+    /// Views to return to on Esc, most recent last
+    pub state_stack: Vec<AppState>,
     pub dialog_state: DialogState,
     pub environments: Vec<PythonEnvironment>,
     pub selected_environment: Option<usize>,
@@ -33,15 +142,174 @@ pub struct App {
     pub selected_package: Option<usize>,
     pub focus: Focus,
     pub input_text: String,
+    /// Byte offset of the cursor in `input_text`, always on a char boundary
+    pub input_cursor: usize,
     pub status_message: Option<String>,
     pub status_message_timer: u8,
     pub show_global_packages: bool,
+    /// Whether package summaries are shown next to each package
+    pub show_summaries: bool,
+    /// Whether the package details panel is shown below the package list
+    pub show_details: bool,
+    /// Whether long lines in the details panel wrap instead of being cut off
+    pub wrap_details: bool,
+    /// Lines the details panel is scrolled down by; reset when the selection changes
+    pub details_scroll: u16,
+    /// Whether the package list cuts versions to `major.minor.patch`
+    pub short_versions: bool,
+    pub count_buffer: String,
+    /// Letters typed for type-ahead find, cleared after a short idle
+    pub type_ahead: String,
+    /// Ticks since the last type-ahead letter
+    pub type_ahead_idle: u8,
+    /// Environments the last `s` search matched, best first, and which one
+    /// is selected; `n`/`N` step through them until the search is cleared
+    pub search_matches: Vec<usize>,
+    pub search_match: usize,
+    pub search_query: String,
+    /// Whether environment search tells `A` from `a`, for paths where case matters
+    pub search_case_sensitive: bool,
+    pub sidebar_percent: u16,
+    pub required_by: Vec<String>,
+    pub package_filter: String,
+    pub install_preview: Vec<Package>,
+    /// Latest versions from the last outdated check; `None` until a check has run
+    pub outdated: Option<HashMap<String, String>>,
+    /// Normalized names of packages nothing depends on; `Some` while the
+    /// leaf view is on, which marks them and dims everything else
+    pub leaves: Option<HashSet<String>>,
+    /// Install options from the config
+    pub install_options: InstallOptions,
+    /// Per-install index URL typed into the install dialog
+    pub index_override: String,
+    pub editing_index: bool,
+    /// Install with `--upgrade`, after confirming the package is already installed
+    pub install_upgrade: bool,
+    /// PyPI package names for completion; `None` unless enabled and loaded
+    pub package_index: Option<Vec<String>>,
+    /// While Tab cycles through suggestions: the name typed before cycling
+    /// and the position of the suggestion shown
+    pub completion_cycle: Option<(String, usize)>,
+    /// Versions of the selected package offered in the version picker, newest first
+    pub available_versions: Vec<String>,
+    /// Whether `available_versions` came from pip's cache because the index
+    /// couldn't be reached
+    pub versions_offline: bool,
+    pub selected_version: usize,
+    /// Install with `--pre`, toggled per install in the install dialog
+    pub install_pre: bool,
+    /// Whether outdated checks count pre-releases as the latest version
+    pub outdated_prereleases: bool,
+    /// Interpreter for new environments from the config
+    pub default_python: Option<String>,
+    /// Tool that creates new environments, from the config
+    pub creation_backend: CreationBackend,
+    /// Whether everything that changes environments is disabled, from
+    /// `--read-only` or the config
+    pub read_only: bool,
+    /// What's being created in the background, shown next to the spinner,
+    /// e.g. "Creating environment 'foo'" or "Creating 3 of 5: dataenv"
+    pub creating: Option<String>,
+    /// Any other background work the user is waiting on, shown next to the
+    /// spinner, e.g. "Fetching versions of 'requests'"
+    pub working: Option<String>,
+    /// Advanced every tick while something shows a spinner
+    pub spinner_frame: usize,
+    /// Per-environment interpreter typed into the create dialog
+    pub python_override: String,
+    pub editing_python: bool,
+    /// Existing directory the user agreed to replace with the environment
+    /// being created
+    pub confirmed_overwrite: Option<PathBuf>,
+    /// Templates from the config, offered after the create dialog
+    pub templates: Vec<Template>,
+    /// Position in the template picker; 0 is "no template"
+    pub selected_template: usize,
+    /// Installed package counts by environment path, filled in by a background pass
+    pub package_counts: HashMap<PathBuf, usize>,
+    /// Licenses by environment path and normalized package name, filled in
+    /// on demand by a background pass
+    pub licenses: HashMap<PathBuf, HashMap<String, String>>,
+    /// The pip that handles operations, by environment path; `None` if the
+    /// environment has no usable pip
+    pub pip_info: HashMap<PathBuf, Option<PipInfo>>,
+    /// Environments known to have no pip, e.g. created with `--without-pip`
+    pub pipless: HashSet<PathBuf>,
+    /// Environment a background install is running in, and the normalized
+    /// names of the packages pip says it's working on there
+    pub installing: Option<(PathBuf, Vec<String>)>,
+    /// Output of commands run on the user's behalf, shown in the log view
+    pub log: Vec<String>,
+    pub log_scroll: u16,
+    /// Recent history entries, newest first, loaded when the history view opens
+    pub history: Vec<HistoryEntry>,
+    pub history_scroll: u16,
+    /// Sizes of virtual environments as `(name, path, bytes)`, largest first
+    pub disk_usage: Vec<(String, PathBuf, u64)>,
+    /// Environments the disk usage scan has yet to measure
+    pub disk_usage_pending: usize,
+    pub disk_usage_scroll: u16,
+    /// The `.env` file shown in the dotenv popup and its variables, secrets masked
+    pub dotenv_path: Option<PathBuf>,
+    pub dotenv_vars: Vec<(String, String)>,
+    pub dotenv_scroll: u16,
+    /// Registered Jupyter kernels, once the kernel view has loaded them
+    pub kernels: Option<Vec<Kernel>>,
+    pub selected_kernel: usize,
+    /// `sys.path` of the selected environment, for the sys.path popup
+    pub sys_path: Vec<String>,
+    pub sys_path_scroll: u16,
+    /// The requirements file last compared against and the result
+    pub requirements_diff: Option<(PathBuf, RequirementsDiff)>,
+    pub requirements_diff_scroll: u16,
+    /// Metadata shown in the package details popup
+    pub package_metadata: Option<PackageMetadata>,
+    pub metadata_scroll: u16,
+    pub files_scroll: u16,
+    /// A package listing is running in the background
+    pub loading_packages: bool,
+    /// Whether moving past either end of a list wraps to the other end
+    pub wrap_navigation: bool,
+    /// Problems hit while detecting environments, also written to the log
+    pub detection_warnings: Vec<String>,
+    /// Path of the conda environment active in the launching shell
+    pub active_conda: Option<PathBuf>,
+    /// pyenv's global version ("system" or a version name); `None` without pyenv
+    pub pyenv_global: Option<String>,
+    pub env_sort: EnvSort,
+    /// Pinned environment paths, in the order they're shown at the top of the list
+    pub pinned: Vec<PathBuf>,
+    /// Per-environment usage, for the recent and frequent sort orders
+    pub usage: Usage,
+    /// Whether system and pyenv interpreters are left out of the environment list
+    pub hide_system_environments: bool,
+    /// Whether the environment list is grouped by type under section headers
+    pub group_environments: bool,
+    /// Whether the status bar shows how to activate the selected environment
+    pub show_activation_command: bool,
+    /// Whether `q` asks for confirmation before quitting
+    pub confirm_quit: bool,
+    /// Whether to count explicit installs and dependencies per environment
+    pub show_dependency_counts: bool,
+    /// `(total, explicit)` package counts by environment path; `None` if pip
+    /// couldn't list them
+    pub dependency_counts: HashMap<PathBuf, Option<(usize, usize)>>,
+    /// Open tabs; the entry at `active_tab` is a placeholder for the live state
+    pub tabs: Vec<EnvTab>,
+    pub active_tab: usize,
+    /// Environment paths in detection order, so `EnvSort::Detected` can be restored
+    detection_order: Vec<PathBuf>,
+    /// Broken virtual environments offered for deletion by the cleanup action
+    pub cleanup_candidates: Vec<PathBuf>,
+    /// Broken environments LazyEnv doesn't own (system, pyenv), only reported
+    pub cleanup_reported: Vec<String>,
 }
 
 impl App {
     pub fn new() -> Self {
         Self {
             state: AppState::Normal,
+            state_stack: Vec::new(),
             dialog_state: DialogState::None,
             environments: Vec::new(),
             selected_environment: None,
@@ -49,23 +317,498 @@ impl App {
             selected_package: None,
             focus: Focus::Environments,
             input_text: String::new(),
+            input_cursor: 0,
             status_message: None,
             status_message_timer: 0,
             show_global_packages: false,
+            show_summaries: false,
+            show_details: true,
+            wrap_details: true,
+            details_scroll: 0,
+            short_versions: false,
+            count_buffer: String::new(),
+            type_ahead: String::new(),
+            type_ahead_idle: 0,
+            search_matches: Vec::new(),
+            search_match: 0,
+            search_query: String::new(),
+            search_case_sensitive: false,
+            sidebar_percent: 30,
+            required_by: Vec::new(),
+            package_filter: String::new(),
+            install_preview: Vec::new(),
+            outdated: None,
+            leaves: None,
+            install_options: InstallOptions::default(),
+            index_override: String::new(),
+            editing_index: false,
+            install_upgrade: false,
+            package_index: None,
+            completion_cycle: None,
+            available_versions: Vec::new(),
+            versions_offline: false,
+            selected_version: 0,
+            install_pre: false,
+            outdated_prereleases: false,
+            default_python: None,
+            creation_backend: CreationBackend::Venv,
+            read_only: false,
+            creating: None,
+            working: None,
+            spinner_frame: 0,
+            python_override: String::new(),
+            editing_python: false,
+            confirmed_overwrite: None,
+            templates: Vec::new(),
+            selected_template: 0,
+            package_counts: HashMap::new(),
+            licenses: HashMap::new(),
+            pip_info: HashMap::new(),
+            pipless: HashSet::new(),
+            installing: None,
+            log: Vec::new(),
+            log_scroll: 0,
+            history: Vec::new(),
+            history_scroll: 0,
+            disk_usage: Vec::new(),
+            disk_usage_pending: 0,
+            disk_usage_scroll: 0,
+            dotenv_path: None,
+            dotenv_vars: Vec::new(),
+            dotenv_scroll: 0,
+            kernels: None,
+            selected_kernel: 0,
+            sys_path: Vec::new(),
+            sys_path_scroll: 0,
+            requirements_diff: None,
+            requirements_diff_scroll: 0,
+            package_metadata: None,
+            metadata_scroll: 0,
+            files_scroll: 0,
+            loading_packages: false,
+            wrap_navigation: true,
+            detection_warnings: Vec::new(),
+            active_conda: None,
+            pyenv_global: None,
+            env_sort: EnvSort::Detected,
+            pinned: Vec::new(),
+            usage: Usage::default(),
+            hide_system_environments: false,
+            group_environments: false,
+            show_activation_command: false,
+            confirm_quit: false,
+            show_dependency_counts: false,
+            dependency_counts: HashMap::new(),
+            tabs: vec![EnvTab::default()],
+            active_tab: 0,
+            detection_order: Vec::new(),
+            cleanup_candidates: Vec::new(),
+            cleanup_reported: Vec::new(),
+        }
+    }
+
+    /// The selected environment, if the selection still points into the list.
+    pub fn current_environment(&self) -> Option<&PythonEnvironment> {
+        self.selected_environment.and_then(|idx| self.environments.get(idx))
+    }
+
+    /// Replace the environment list with a fresh detection result, keeping the
+    /// selection in range.
+    pub fn set_environments(&mut self, environments: Vec<PythonEnvironment>) {
+        self.detection_order = environments.iter().map(|env| env.path.clone()).collect();
+        self.environments = environments;
+        self.active_conda = active_conda_environment(&self.environments)
+            .map(|idx| self.environments[idx].path.clone());
+        self.pyenv_global = pyenv_global();
+        self.sort_environments();
+        self.clamp_environment_selection();
+    }
+
+    /// The pyenv version `env` is, e.g. `3.12.4`; `None` for other environments.
+    pub fn pyenv_version(env: &PythonEnvironment) -> Option<String> {
+        (env.env_type == "pyenv").then(|| env.path.file_name().unwrap_or_default().to_string_lossy().into_owned())
+    }
+    
+    /// Whether `env` is the pyenv version set as `pyenv global`.
+    pub fn is_pyenv_global(&self, env: &PythonEnvironment) -> bool {
+        Self::pyenv_version(env).is_some_and(|version| self.pyenv_global.as_deref() == Some(version.as_str()))
+    }
+
+    /// Add a newly created environment and select it.
+    pub fn add_environment(&mut self, environment: PythonEnvironment) {
+        self.detection_order.push(environment.path.clone());
+        self.environments.push(environment);
+        self.selected_environment = Some(self.environments.len() - 1);
+        self.resort_environments();
+    }
+
+    /// Fill in the version of a pending environment once a re-probe answers.
+    pub fn set_environment_version(&mut self, path: &Path, version: String) {
+        if let Some(env) = self.environments.iter_mut().find(|env| env.path == path) {
+            env.python_version = version;
+        }
+    }
+
+    /// Remove the environment at `idx`, keeping the selection in range.
+    pub fn remove_environment(&mut self, idx: usize) {
+        if idx < self.environments.len() {
+            self.environments.remove(idx);
+        }
+        self.search_matches.clear();
+        self.clamp_environment_selection();
+    }
+
+    /// Remove the environment at `path` from the list, if it's there.
+    pub fn remove_environment_path(&mut self, path: &Path) {
+        if let Some(idx) = self.environments.iter().position(|env| env.path == path) {
+            self.remove_environment(idx);
+        }
+    }
+
+    /// Switch to the next sort order, keeping the same environment selected.
+    pub fn cycle_env_sort(&mut self) {
+        self.env_sort = self.env_sort.next();
+        self.resort_environments();
+    }
+
+    pub fn is_pinned(&self, path: &Path) -> bool {
+        self.pinned.iter().any(|pinned| pinned == path)
+    }
+
+    /// Pin the selected environment to the top of the list, or unpin it.
+    pub fn toggle_pin(&mut self) {
+        let Some(path) = self.current_environment().map(|env| env.path.clone()) else {
+            return;
+        };
+        
+        if self.is_pinned(&path) {
+            self.pinned.retain(|pinned| *pinned != path);
+        } else {
+            self.pinned.push(path);
+        }
+        self.resort_environments();
+    }
+
+    /// Move the selected pinned environment one place up within the pinned group.
+    /// Returns `false` if the selected environment isn't pinned.
+    pub fn move_environment_up(&mut self) -> bool {
+        self.move_pinned(|pos| pos.checked_sub(1))
+    }
+
+    /// Move the selected pinned environment one place down within the pinned group.
+    /// Returns `false` if the selected environment isn't pinned.
+    pub fn move_environment_down(&mut self) -> bool {
+        let len = self.pinned.len();
+        self.move_pinned(|pos| Some(pos + 1).filter(|&next| next < len))
+    }
+
+    fn move_pinned(&mut self, target: impl Fn(usize) -> Option<usize>) -> bool {
+        let Some(pos) = self.current_environment()
+            .and_then(|env| self.pinned.iter().position(|pinned| *pinned == env.path)) else {
+            return false;
+        };
+        
+        // At either end of the pinned group the environment just stays put
+        if let Some(target) = target(pos) {
+            self.pinned.swap(pos, target);
+            self.resort_environments();
+        }
+        true
+    }
+
+    /// Re-sort the environment list, keeping the same environment selected.
+    fn resort_environments(&mut self) {
+        let selected = self.current_environment().map(|env| env.path.clone());
+        self.sort_environments();
+        if let Some(path) = selected {
+            self.selected_environment = self.environments.iter().position(|env| env.path == path);
+        }
+    }
+
+    /// Sort by the current `EnvSort`, then float pinned environments to the top
+    /// in their pinned order. The sorts are stable, so everything else keeps
+    /// the sort order below the pinned group.
+    fn sort_environments(&mut self) {
+        // Matches are indices, which reordering invalidates
+        self.search_matches.clear();
+        match self.env_sort {
+            EnvSort::Detected => {
+                let order = &self.detection_order;
+                self.environments.sort_by_key(|env| {
+                    order.iter().position(|path| *path == env.path).unwrap_or(usize::MAX)
+                });
+            },
+            EnvSort::Name => {
+                self.environments.sort_by_cached_key(|env| env.name.to_lowercase());
+            },
+            EnvSort::Age => {
+                // Environments without a timestamp go last
+                self.environments.sort_by_cached_key(|env| {
+                    let created = environment_created(&env.path);
+                    (created.is_none(), created)
+                });
+            },
+            EnvSort::Recent => {
+                let usage = &self.usage;
+                self.environments.sort_by_key(|env| Reverse(usage.get(&env.path).last_used));
+            },
+            EnvSort::Frequent => {
+                let usage = &self.usage;
+                self.environments.sort_by_key(|env| Reverse(usage.get(&env.path).count));
+            },
+        }
+        
+        let pinned = &self.pinned;
+        self.environments.sort_by_key(|env| {
+            pinned.iter().position(|path| *path == env.path).unwrap_or(usize::MAX)
+        });
+    }
+
+    /// Keep the selection on a shown environment, moving to the nearest one
+    /// below (or the last one) if it's out of range or hidden.
+    fn clamp_environment_selection(&mut self) {
+        let visible = self.visible_environments();
+        self.selected_environment = match self.selected_environment {
+            _ if visible.is_empty() => None,
+            Some(idx) if visible.contains(&idx) => Some(idx),
+            Some(idx) => visible.iter().copied().find(|&i| i > idx).or(visible.last().copied()),
+            None => Some(visible[0]),
+        };
+        if self.selected_environment.is_none() {
+            self.packages.clear();
+            self.selected_package = None;
+        }
+    }
+
+    /// pipx owns its environments; installing into them directly with pip is discouraged.
+    pub fn selected_is_pipx(&self) -> bool {
+        self.current_environment()
+            .is_some_and(|env| env.env_type == "pipx")
+    }
+
+    /// Install options for the next install, with the dialog's index URL taking precedence.
+    pub fn effective_install_options(&self) -> InstallOptions {
+        let mut options = self.install_options.clone();
+        let index_override = self.index_override.trim();
+        if !index_override.is_empty() {
+            options.index_url = Some(index_override.to_string());
+        }
+        options.upgrade = self.install_upgrade;
+        options.pre = self.install_pre;
+        options
+    }
+
+    /// Completions for the package name typed in the install dialog. Empty
+    /// without an index or once the input is more than a bare name.
+    pub fn install_suggestions(&self) -> Vec<&str> {
+        let Some(names) = &self.package_index else {
+            return Vec::new();
+        };
+        let prefix = match &self.completion_cycle {
+            Some((prefix, _)) => prefix.as_str(),
+            None => self.input_text.trim(),
+        };
+        if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) {
+            return Vec::new();
+        }
+        package_index::completions(names, prefix)
+    }
+
+    /// Tab in the install dialog: complete the name to what all suggestions
+    /// share, then cycle through them. `false` if there was nothing to
+    /// complete, including when the input is already an exact name.
+    pub fn complete_package_name(&mut self) -> bool {
+        let suggestions: Vec<String> = self.install_suggestions().into_iter().map(str::to_string).collect();
+        if suggestions.is_empty() {
+            return false;
+        }
+        
+        let next = match &self.completion_cycle {
+            Some((_, shown)) => (shown + 1) % suggestions.len(),
+            None => {
+                let typed = self.input_text.trim().to_string();
+                if suggestions.iter().any(|name| name.eq_ignore_ascii_case(&typed)) {
+                    return false;
+                }
+                let common = package_index::common_prefix(&suggestions.iter().map(String::as_str).collect::<Vec<_>>());
+                if common.len() > typed.len() {
+                    self.input_text = common;
+                    self.input_end();
+                    return true;
+                }
+                self.completion_cycle = Some((typed, 0));
+                0
+            },
+        };
+        
+        if let Some((_, shown)) = &mut self.completion_cycle {
+            *shown = next;
+        }
+        self.input_text = suggestions[next].clone();
+        self.input_end();
+        true
+    }
+
+    /// The installed package matching `requirement`, compared per PEP 503.
+    /// Requirements with a version specifier, extras or a URL never match,
+    /// since pip has real work to do for those.
+    pub fn installed_package(&self, requirement: &str) -> Option<&Package> {
+        let requirement = requirement.trim();
+        if requirement.is_empty()
+            || !requirement.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) {
+            return None;
+        }
+        
+        let wanted = normalize_package_name(requirement);
+        self.packages.iter().find(|pkg| normalize_package_name(&pkg.name) == wanted)
+    }
+
+    /// Installed packages the last outdated check found newer releases of,
+    /// with the latest version, in list order.
+    pub fn outdated_packages(&self) -> Vec<(&Package, &str)> {
+        let Some(outdated) = &self.outdated else {
+            return Vec::new();
+        };
+        self.packages
+            .iter()
+            .filter_map(|pkg| outdated.get(&normalize_package_name(&pkg.name)).map(|latest| (pkg, latest.as_str())))
+            .collect()
+    }
+
+    /// The outdated packages pip may upgrade. Conda-managed ones are left out:
+    /// `pip install --upgrade` over them corrupts the environment.
+    pub fn upgradable_packages(&self) -> Vec<(&Package, &str)> {
+        let mut outdated = self.outdated_packages();
+        outdated.retain(|(pkg, _)| pkg.manager != PackageManager::Conda);
+        outdated
+    }
+
+    /// Record a measured environment, keeping the list largest first.
+    pub fn add_disk_usage(&mut self, name: String, path: PathBuf, size: u64) {
+        let idx = self.disk_usage.partition_point(|&(_, _, other)| other >= size);
+        self.disk_usage.insert(idx, (name, path, size));
+        self.disk_usage_pending = self.disk_usage_pending.saturating_sub(1);
+    }
+
+    /// License of an installed package in the selected environment, if the
+    /// license pass has fetched it.
+    pub fn package_license(&self, package_name: &str) -> Option<&str> {
+        let env = self.current_environment()?;
+        self.licenses
+            .get(&env.path)?
+            .get(&normalize_package_name(package_name))
+            .map(String::as_str)
+    }
+
+    /// Append lines to the log, dropping the oldest once it grows past `MAX_LOG_LINES`.
+    pub fn push_log(&mut self, text: &str) {
+        self.log.extend(text.lines().map(str::to_string));
+        if self.log.len() > MAX_LOG_LINES {
+            self.log.drain(..self.log.len() - MAX_LOG_LINES);
+        }
+    }
+
+    /// Tell the user an action was skipped because of read-only mode.
+    pub fn refuse_read_only(&mut self) {
+        self.status_message = Some("Read-only mode: changes are disabled".to_string());
+        self.status_message_timer = 0;
+    }
+
+    /// Switch to `state`, remembering the current view so Esc can return to it.
+    pub fn open(&mut self, state: AppState) {
+        if state != self.state {
+            self.state_stack.push(self.state);
+            self.state = state;
+        }
+    }
+
+    /// Return to the view that was open before the current one, or the main
+    /// view if there's none.
+    pub fn go_back(&mut self) {
+        self.state = self.state_stack.pop().unwrap_or(AppState::Normal);
+    }
+
+    /// Return to the main view and forget the views opened on the way, e.g.
+    /// once a dialog's action has run.
+    pub fn reset_state(&mut self) {
+        self.state = AppState::Normal;
+        self.state_stack.clear();
+    }
+
+    /// Open the log view scrolled to the most recent output.
+    pub fn show_log(&mut self) {
+        self.log_scroll = self.log.len().saturating_sub(1) as u16;
+        self.open(AppState::LogView);
+    }
+
+    /// Record the warnings from the latest detection pass and copy them into the log.
+    pub fn set_detection_warnings(&mut self, warnings: Vec<String>) {
+        for warning in &warnings {
+            self.push_log(&format!("warning: {}", warning));
+        }
+        self.detection_warnings = warnings;
+    }
+
+    pub fn clear_input(&mut self) {
+        self.input_text.clear();
+        self.input_cursor = 0;
+    }
+
+    pub fn input_insert(&mut self, c: char) {
+        self.input_text.insert(self.input_cursor, c);
+        self.input_cursor += c.len_utf8();
+    }
+
+    /// Delete the character before the cursor
+    pub fn input_backspace(&mut self) {
+        if let Some(c) = self.input_text[..self.input_cursor].chars().next_back() {
+            self.input_cursor -= c.len_utf8();
+            self.input_text.remove(self.input_cursor);
+        }
+    }
+
+    /// Delete the character under the cursor
+    pub fn input_delete(&mut self) {
+        if self.input_cursor < self.input_text.len() {
+            self.input_text.remove(self.input_cursor);
+        }
+    }
+
+    pub fn input_left(&mut self) {
+        if let Some(c) = self.input_text[..self.input_cursor].chars().next_back() {
+            self.input_cursor -= c.len_utf8();
         }
     }
 
+    pub fn input_right(&mut self) {
+        if let Some(c) = self.input_text[self.input_cursor..].chars().next() {
+            self.input_cursor += c.len_utf8();
+        }
+    }
+
+    pub fn input_home(&mut self) {
+        self.input_cursor = 0;
+    }
+
+    pub fn input_end(&mut self) {
+        self.input_cursor = self.input_text.len();
+    }
+
     pub fn next_environment(&mut self) {
         if self.focus != Focus::Environments {
             return;
         }
         
-        let len = self.environments.len();
+        let visible = self.visible_environments();
+        let len = visible.len();
         if len > 0 {
-            self.selected_environment = match self.selected_environment {
-                Some(i) => Some((i + 1) % len),
-                None => Some(0),
+            let position = self.selected_environment.and_then(|idx| visible.iter().position(|&i| i == idx));
+            let position = match position {
+                Some(i) if self.wrap_navigation => (i + 1) % len,
+                Some(i) => (i + 1).min(len - 1),
+                None => 0,
             };
+            self.selected_environment = Some(visible[position]);
         }
     }
 
@@ -74,12 +817,224 @@ impl App {
             return;
         }
         
-        let len = self.environments.len();
+        let visible = self.visible_environments();
+        let len = visible.len();
         if len > 0 {
-            self.selected_environment = match self.selected_environment {
-                Some(i) => Some((i + len - 1) % len),
-                None => Some(len - 1),
+            let position = self.selected_environment.and_then(|idx| visible.iter().position(|&i| i == idx));
+            let position = match position {
+                Some(i) if self.wrap_navigation => (i + len - 1) % len,
+                Some(i) => i.saturating_sub(1),
+                None => len - 1,
             };
+            self.selected_environment = Some(visible[position]);
+        }
+    }
+
+    /// Indices into `environments` that are shown, in display order. When
+    /// grouped, each type's environments follow each other in `ENV_GROUPS`
+    /// order, keeping the chosen sort within a group.
+    pub fn visible_environments(&self) -> Vec<usize> {
+        let mut visible: Vec<usize> = self.environments
+            .iter()
+            .enumerate()
+            .filter(|(_, env)| !self.hide_system_environments || !matches!(&env.env_type[..], "system" | "pyenv"))
+            .map(|(idx, _)| idx)
+            .collect();
+        if self.group_environments {
+            visible.sort_by_key(|&idx| {
+                let group = env_group(&self.environments[idx].env_type);
+                ENV_GROUPS.iter().position(|&known| known == group)
+            });
+        }
+        visible
+    }
+
+    /// Group the environment list by type, or flatten it again.
+    pub fn toggle_environment_grouping(&mut self) {
+        self.group_environments = !self.group_environments;
+    }
+
+    /// Show or hide system and pyenv interpreters without rescanning.
+    pub fn toggle_system_environments(&mut self) {
+        self.hide_system_environments = !self.hide_system_environments;
+        self.clamp_environment_selection();
+    }
+
+    /// Indices into `packages` that match the current filter, in display order.
+    /// Matching is fuzzy on normalized names, so `flask_lo` and `flgn` both find
+    /// `Flask-Login`; the best matches come first.
+    pub fn visible_packages(&self) -> Vec<usize> {
+        // "license:gpl" lists packages whose fetched license mentions GPL
+        if let Some(license) = self.package_filter.trim().strip_prefix(LICENSE_FILTER_PREFIX) {
+            let license = license.trim().to_lowercase();
+            return (0..self.packages.len())
+                .filter(|&idx| self.package_license(&self.packages[idx].name)
+                    .is_some_and(|found| found.to_lowercase().contains(&license)))
+                .collect();
+        }
+        
+        let filter = normalize_package_name(&self.package_filter);
+        if filter.is_empty() {
+            return (0..self.packages.len()).collect();
+        }
+        
+        let mut scored: Vec<(i64, usize)> = self.packages
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, pkg)| fuzzy::score(&filter, &normalize_package_name(&pkg.name)).map(|score| (score, idx)))
+            .collect();
+        // Stable, so equal scores keep list order
+        scored.sort_by_key(|&(score, _)| Reverse(score));
+        scored.into_iter().map(|(_, idx)| idx).collect()
+    }
+
+    /// Select the next search match, or the previous one going `backward`,
+    /// wrapping around. Returns the environment selected, if there's a search.
+    pub fn step_search_match(&mut self, backward: bool) -> Option<usize> {
+        let count = self.search_matches.len();
+        if count == 0 {
+            return None;
+        }
+        self.search_match = if backward {
+            (self.search_match + count - 1) % count
+        } else {
+            (self.search_match + 1) % count
+        };
+        let idx = self.search_matches[self.search_match];
+        self.selected_environment = Some(idx);
+        Some(idx)
+    }
+
+    /// Shown environments whose name or path fuzzily matches `query`, best
+    /// first. Case only counts with `search_case_sensitive` on.
+    pub fn search_environments(&self, query: &str) -> Vec<usize> {
+        let mut scored: Vec<(i64, usize)> = self.visible_environments()
+            .into_iter()
+            .filter_map(|idx| {
+                let env = &self.environments[idx];
+                let by_name = fuzzy::score_with_case(query, &env.name, self.search_case_sensitive);
+                // Long paths contain almost any subsequence, so they only count as substrings
+                let by_path = fuzzy::score_with_case(query, &env.path.to_string_lossy(), self.search_case_sensitive)
+                    .filter(|&score| score >= fuzzy::SUBSTRING_BONUS);
+                by_name.max(by_path).map(|score| (score, idx))
+            })
+            .collect();
+        scored.sort_by_key(|&(score, _)| Reverse(score));
+        scored.into_iter().map(|(_, idx)| idx).collect()
+    }
+
+    /// Open a new tab on the selected environment and switch to it. Its
+    /// packages start empty until they're loaded.
+    pub fn open_tab(&mut self) {
+        let current = self.stash_tab();
+        self.tabs[self.active_tab] = current;
+        self.tabs.push(EnvTab {
+            env_path: self.current_environment().map(|env| env.path.clone()),
+            ..EnvTab::default()
+        });
+        self.active_tab = self.tabs.len() - 1;
+    }
+
+    /// Switch to the tab at `idx`, restoring its packages and environment.
+    pub fn switch_tab(&mut self, idx: usize) {
+        if idx == self.active_tab || idx >= self.tabs.len() {
+            return;
+        }
+        
+        let current = self.stash_tab();
+        self.tabs[self.active_tab] = current;
+        let tab = std::mem::take(&mut self.tabs[idx]);
+        self.active_tab = idx;
+        self.restore_tab(tab);
+    }
+
+    pub fn next_tab(&mut self) {
+        self.switch_tab((self.active_tab + 1) % self.tabs.len());
+    }
+
+    pub fn previous_tab(&mut self) {
+        self.switch_tab((self.active_tab + self.tabs.len() - 1) % self.tabs.len());
+    }
+
+    /// Close the current tab and go back to the one before it. The last tab can't be closed.
+    pub fn close_tab(&mut self) -> bool {
+        if self.tabs.len() <= 1 {
+            return false;
+        }
+        
+        self.tabs.remove(self.active_tab);
+        self.active_tab = self.active_tab.saturating_sub(1);
+        let tab = std::mem::take(&mut self.tabs[self.active_tab]);
+        self.restore_tab(tab);
+        true
+    }
+
+    /// Name shown in the tab bar for the tab at `idx`.
+    pub fn tab_title(&self, idx: usize) -> String {
+        let path = if idx == self.active_tab {
+            self.current_environment().map(|env| &env.path)
+        } else {
+            self.tabs[idx].env_path.as_ref()
+        };
+        path.and_then(|path| self.environments.iter().find(|env| env.path == *path))
+            .map_or_else(|| "(none)".to_string(), |env| env.name.clone())
+    }
+
+    fn stash_tab(&mut self) -> EnvTab {
+        EnvTab {
+            env_path: self.current_environment().map(|env| env.path.clone()),
+            packages: std::mem::take(&mut self.packages),
+            selected_package: self.selected_package.take(),
+            package_filter: std::mem::take(&mut self.package_filter),
+            outdated: self.outdated.take(),
+            leaves: self.leaves.take(),
+        }
+    }
+
+    fn restore_tab(&mut self, tab: EnvTab) {
+        if let Some(path) = &tab.env_path
+            && let Some(idx) = self.environments.iter().position(|env| env.path == *path) {
+            self.selected_environment = Some(idx);
+        }
+        self.packages = tab.packages;
+        self.selected_package = tab.selected_package;
+        self.package_filter = tab.package_filter;
+        self.outdated = tab.outdated;
+        self.leaves = tab.leaves;
+        self.loading_packages = false;
+    }
+
+    /// Select the package called `name` (compared normalized). Returns `false`
+    /// and leaves the selection alone if there's no such package.
+    pub fn select_package(&mut self, name: &str) -> bool {
+        let wanted = normalize_package_name(name);
+        match self.packages.iter().position(|pkg| normalize_package_name(&pkg.name) == wanted) {
+            Some(idx) => {
+                self.selected_package = Some(idx);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Replace the package list and select the first visible package. What
+    /// was worked out from the old list (outdated and leaf packages, the
+    /// dependents of the selected one, a pending type-ahead) is dropped.
+    pub fn set_packages(&mut self, packages: Vec<Package>) {
+        self.packages = packages;
+        self.outdated = None;
+        self.leaves = None;
+        self.required_by.clear();
+        self.type_ahead.clear();
+        self.selected_package = self.visible_packages().first().copied();
+    }
+
+    /// Keep the package selection on a visible package so actions never
+    /// target something hidden by the filter.
+    pub fn clamp_package_selection(&mut self) {
+        let visible = self.visible_packages();
+        if !self.selected_package.is_some_and(|idx| visible.contains(&idx)) {
+            self.selected_package = visible.first().copied();
         }
     }
 
@@ -88,11 +1043,14 @@ impl App {
             return;
         }
         
-        let len = self.packages.len();
+        let visible = self.visible_packages();
+        let len = visible.len();
         if len > 0 {
-            self.selected_package = match self.selected_package {
-                Some(i) => Some((i + 1) % len),
-                None => Some(0),
+            let position = self.selected_package.and_then(|idx| visible.iter().position(|&i| i == idx));
+            self.selected_package = match position {
+                Some(i) if self.wrap_navigation => Some(visible[(i + 1) % len]),
+                Some(i) => Some(visible[(i + 1).min(len - 1)]),
+                None => Some(visible[0]),
             };
         }
     }
@@ -102,24 +1060,93 @@ impl App {
             return;
         }
         
-        let len = self.packages.len();
+        let visible = self.visible_packages();
+        let len = visible.len();
         if len > 0 {
-            self.selected_package = match self.selected_package {
-                Some(i) => Some((i + len - 1) % len),
-                None => Some(len - 1),
+            let position = self.selected_package.and_then(|idx| visible.iter().position(|&i| i == idx));
+            self.selected_package = match position {
+                Some(i) if self.wrap_navigation => Some(visible[(i + len - 1) % len]),
+                Some(i) => Some(visible[i.saturating_sub(1)]),
+                None => Some(visible[len - 1]),
             };
         }
     }
 
+    /// Jump to a 1-based position in the focused list, or to the end when no position is given.
+    pub fn jump_to(&mut self, position: Option<usize>) {
+        let len = match self.focus {
+            Focus::Environments => self.visible_environments().len(),
+            Focus::Packages => self.visible_packages().len(),
+        };
+        if len == 0 {
+            return;
+        }
+        
+        let idx = position.map_or(len - 1, |n| n.clamp(1, len) - 1);
+        match self.focus {
+            Focus::Environments => self.selected_environment = Some(self.visible_environments()[idx]),
+            Focus::Packages => self.selected_package = Some(self.visible_packages()[idx]),
+        }
+    }
+
+    /// Type-ahead find: add `c` to the buffer and select the first item in the
+    /// focused list, from the selection on, whose name starts with it. Typing
+    /// the same letter again cycles through the items starting with it.
+    pub fn type_ahead(&mut self, c: char) {
+        let c = c.to_ascii_lowercase();
+        self.type_ahead_idle = 0;
+        let cycling = !self.type_ahead.is_empty() && self.type_ahead.chars().all(|typed| typed == c);
+        if !cycling {
+            self.type_ahead.push(c);
+        }
+        
+        // Prefixed names like "conda: base" also match on the part after the prefix
+        let names: Vec<(usize, String)> = match self.focus {
+            Focus::Environments => self.visible_environments()
+                .into_iter()
+                .map(|idx| (idx, self.environments[idx].name.to_lowercase()))
+                .collect(),
+            Focus::Packages => self.visible_packages()
+                .into_iter()
+                .map(|idx| (idx, self.packages[idx].name.to_lowercase()))
+                .collect(),
+        };
+        let selected = match self.focus {
+            Focus::Environments => self.selected_environment,
+            Focus::Packages => self.selected_package,
+        };
+        let current = names.iter().position(|&(idx, _)| Some(idx) == selected).unwrap_or(0);
+        let start = if cycling { current + 1 } else { current };
+        
+        let prefix = &self.type_ahead;
+        let found = names[start.min(names.len())..]
+            .iter()
+            .chain(&names[..start.min(names.len())])
+            .find(|(_, name)| name.starts_with(prefix.as_str())
+                || name.split_once(": ").is_some_and(|(_, short)| short.starts_with(prefix.as_str())))
+            .map(|&(idx, _)| idx);
+        
+        if let Some(idx) = found {
+            match self.focus {
+                Focus::Environments => self.selected_environment = Some(idx),
+                Focus::Packages => self.selected_package = Some(idx),
+            }
+        }
+    }
+
+    pub fn grow_sidebar(&mut self) {
+        self.sidebar_percent = (self.sidebar_percent + 5).min(MAX_SIDEBAR_PERCENT);
+    }
+
+    pub fn shrink_sidebar(&mut self) {
+        self.sidebar_percent = self.sidebar_percent.saturating_sub(5).max(MIN_SIDEBAR_PERCENT);
+    }
+
     pub fn toggle_focus(&mut self) {
         match self.focus {
             Focus::Environments => {
                 self.focus = Focus::Packages;
-                if self.packages.is_empty() {
-                    self.selected_package = None;
-                } else if self.selected_package.is_none() {
-                    self.selected_package = Some(0);
-                }
+                self.clamp_package_selection();
             },
             Focus::Packages => {
                 self.focus = Focus::Environments;
@@ -0,0 +1,84 @@
+//! Command-line arguments. With none, LazyEnv starts the interactive TUI.
+
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: lazyenv [OPTIONS]
+       lazyenv [OPTIONS] install <environment> <package>
+
+Commands:
+  install           Install a package into the named environment and exit
+
+Options:
+  --list            Print detected environments and exit
+  --config <path>   Use this config file instead of the default
+  --read-only       Browse only; disable everything that changes environments
+  --version         Print the version and exit
+  --help            Print this help and exit
+
+Without options, LazyEnv starts the interactive interface.";
+
+#[derive(Debug, PartialEq)]
+pub enum CliCommand {
+    Tui,
+    Help,
+    Version,
+    List,
+    Install { environment: String, package: String },
+}
+
+#[derive(Debug)]
+pub struct Cli {
+    pub command: CliCommand,
+    /// Config file given with `--config`
+    pub config: Option<PathBuf>,
+    /// Whether `--read-only` was given
+    pub read_only: bool,
+}
+
+/// Parse the arguments after the program name.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Cli, String> {
+    let mut cli = Cli {
+        command: CliCommand::Tui,
+        config: None,
+        read_only: false,
+    };
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let command = match arg.as_str() {
+            "-h" | "--help" => CliCommand::Help,
+            "-V" | "--version" => CliCommand::Version,
+            "--list" => CliCommand::List,
+            "install" => {
+                let (Some(environment), Some(package)) = (args.next(), args.next()) else {
+                    return Err("install needs an environment and a package".to_string());
+                };
+                CliCommand::Install { environment, package }
+            },
+            "--read-only" => {
+                cli.read_only = true;
+                continue;
+            },
+            "--config" => {
+                let path = args.next().ok_or("--config needs a path")?;
+                cli.config = Some(PathBuf::from(path));
+                continue;
+            },
+            _ => {
+                if let Some(path) = arg.strip_prefix("--config=") {
+                    cli.config = Some(PathBuf::from(path));
+                    continue;
+                }
+                return Err(format!("unknown argument '{}'", arg));
+            }
+        };
+
+        // --help and --version win over anything else on the line
+        if cli.command == CliCommand::Tui || matches!(command, CliCommand::Help | CliCommand::Version) {
+            cli.command = command;
+        }
+    }
+
+    Ok(cli)
+}
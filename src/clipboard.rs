@@ -0,0 +1,85 @@
+//! Copying text to and reading it from the system clipboard through the
+//! platform's clipboard tools (`pbcopy`/`pbpaste`, `clip`/PowerShell,
+//! `wl-copy`/`wl-paste`, `xclip` or `xsel`).
+
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Clipboard commands to try, in order, for the current platform.
+fn candidates() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        vec![("pbcopy", &[])]
+    } else if cfg!(windows) {
+        vec![("clip", &[])]
+    } else {
+        let mut commands: Vec<(&'static str, &'static [&'static str])> = Vec::new();
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            commands.push(("wl-copy", &[]));
+        }
+        commands.push(("xclip", &["-selection", "clipboard"]));
+        commands.push(("xsel", &["--clipboard", "--input"]));
+        commands
+    }
+}
+
+/// Copy `text` to the clipboard using the first clipboard tool that works.
+pub fn copy(text: &str) -> io::Result<()> {
+    for (program, args) in candidates() {
+        // The tool may keep running to serve the selection, so don't capture its output
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+
+        let Ok(mut child) = child else {
+            continue;
+        };
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+
+        if child.wait()?.success() {
+            return Ok(());
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::NotFound, "No working clipboard tool found"))
+}
+
+/// Commands that print the clipboard contents, in order, for the current platform.
+fn paste_candidates() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        vec![("pbpaste", &[])]
+    } else if cfg!(windows) {
+        vec![("powershell", &["-NoProfile", "-Command", "Get-Clipboard"])]
+    } else {
+        let mut commands: Vec<(&'static str, &'static [&'static str])> = Vec::new();
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            commands.push(("wl-paste", &["--no-newline"]));
+        }
+        commands.push(("xclip", &["-selection", "clipboard", "-o"]));
+        commands.push(("xsel", &["--clipboard", "--output"]));
+        commands
+    }
+}
+
+/// Read the clipboard's text using the first clipboard tool that works.
+pub fn paste() -> io::Result<String> {
+    for (program, args) in paste_candidates() {
+        let output = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output();
+
+        if let Ok(output) = output
+            && output.status.success() {
+            return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::NotFound, "No working clipboard tool found"))
+}
@@ -0,0 +1,167 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::EnvSort;
+use crate::paths;
+use crate::python::{CreationBackend, InstallOptions};
+
+pub const MIN_SIDEBAR_PERCENT: u16 = 15;
+pub const MAX_SIDEBAR_PERCENT: u16 = 70;
+
+/// Extra pip settings applied to installs, reinstalls and uninstalls (never to
+/// detection or listing). They're given on the command line, so they beat
+/// `pip.conf` and `PIP_*` variables, and per-operation options such as the
+/// install dialog's index come after them, so those beat these.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PipSettings {
+    /// Hosts to trust even without valid HTTPS (`--trusted-host`)
+    pub trusted_hosts: Vec<String>,
+    /// Socket timeout in seconds (`--timeout`)
+    pub timeout: Option<u64>,
+    /// Connection attempts before giving up (`--retries`)
+    pub retries: Option<u32>,
+    /// Any other flags, after the ones above. Installs get them as-is;
+    /// uninstalls and version lookups only get the ones pip accepts there.
+    pub extra_args: Vec<String>,
+}
+
+impl PipSettings {
+    /// Network flags every pip command that talks to an index understands.
+    pub fn flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        for host in &self.trusted_hosts {
+            flags.push("--trusted-host".to_string());
+            flags.push(host.clone());
+        }
+        if let Some(timeout) = self.timeout {
+            flags.push("--timeout".to_string());
+            flags.push(timeout.to_string());
+        }
+        if let Some(retries) = self.retries {
+            flags.push("--retries".to_string());
+            flags.push(retries.to_string());
+        }
+        flags
+    }
+}
+
+/// Packages to install into a new environment right after it's created.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Template {
+    pub name: String,
+    pub packages: Vec<String>,
+}
+
+/// User preferences persisted between sessions as JSON.
+/// Missing fields fall back to their defaults so older files keep loading.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub sidebar_percent: u16,
+    /// Package index used instead of PyPI for installs
+    pub index_url: Option<String>,
+    /// Extra package indexes searched in addition to the primary one
+    pub extra_index_urls: Vec<String>,
+    /// Whether list navigation wraps from the last item to the first and back
+    pub wrap_navigation: bool,
+    /// Ignore a repeated Up/Down/PgUp/PgDn that arrives sooner than this many
+    /// milliseconds after the last one; 0 handles every key
+    pub navigation_debounce_ms: u64,
+    /// Milliseconds between UI ticks while something is running, e.g. a
+    /// background check or a status message waiting to clear
+    pub tick_rate_ms: u64,
+    /// Interpreter for new environments: a path, a command like `python3.11`, or a version like `3.11`
+    pub default_python: Option<String>,
+    /// Tool that creates new environments: `venv` or `virtualenv`
+    pub creation_backend: CreationBackend,
+    /// Pinned environment paths, top of the list first
+    pub pinned_environments: Vec<PathBuf>,
+    /// Whether system and pyenv interpreters are hidden from the environment list
+    pub hide_system_environments: bool,
+    /// Whether the environment list is grouped by type under headers
+    pub group_environments: bool,
+    /// Whether the status bar shows the command that activates the selected
+    /// environment instead of its path
+    pub show_activation_command: bool,
+    /// Whether `q` asks before quitting; Ctrl+C always quits at once
+    pub confirm_quit: bool,
+    /// Whether outdated checks report pre-releases (`pip list --outdated --pre`)
+    pub outdated_prereleases: bool,
+    /// Whether the details panel breaks the selected environment's package
+    /// count down into explicit installs and dependencies
+    pub dependency_counts: bool,
+    /// Whether to download PyPI's list of package names to complete names in
+    /// the install dialog
+    pub package_completion: bool,
+    /// Order of the environment list at startup; `S` cycles it from there
+    pub env_sort: EnvSort,
+    /// Whether to start in read-only mode, as with `--read-only`
+    pub read_only: bool,
+    /// Package sets offered when creating an environment
+    pub templates: Vec<Template>,
+    pub pip: PipSettings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            sidebar_percent: 30,
+            index_url: None,
+            extra_index_urls: Vec::new(),
+            wrap_navigation: true,
+            navigation_debounce_ms: 0,
+            tick_rate_ms: 100,
+            default_python: None,
+            creation_backend: CreationBackend::Venv,
+            pinned_environments: Vec::new(),
+            hide_system_environments: false,
+            group_environments: false,
+            show_activation_command: false,
+            confirm_quit: false,
+            outdated_prereleases: false,
+            dependency_counts: false,
+            package_completion: false,
+            env_sort: EnvSort::Detected,
+            read_only: false,
+            templates: Vec::new(),
+            pip: PipSettings::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn default_path() -> io::Result<PathBuf> {
+        Ok(paths::config_dir()?.join("config.json"))
+    }
+
+    /// Load the config file, falling back to defaults if it is missing or unreadable.
+    pub fn load(path: &Path) -> Self {
+        let mut config = fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Config>(&content).ok())
+            .unwrap_or_default();
+
+        config.sidebar_percent = config.sidebar_percent.clamp(MIN_SIDEBAR_PERCENT, MAX_SIDEBAR_PERCENT);
+        config
+    }
+
+    /// Install options from the index and pip settings.
+    pub fn install_options(&self) -> InstallOptions {
+        InstallOptions {
+            index_url: self.index_url.clone(),
+            extra_index_urls: self.extra_index_urls.clone(),
+            pip_flags: self.pip.flags(),
+            extra_args: self.pip.extra_args.clone(),
+            ..InstallOptions::default()
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(path, content)
+    }
+}
@@ -0,0 +1,66 @@
+//! Small fuzzy matcher for searching lists: the query's characters must appear
+//! in order in the candidate, and matches that are contiguous or start at word
+//! boundaries score higher. Plain substring matches always outrank scattered ones.
+
+/// Bonus that puts any substring match above every scattered match; scores at
+/// or above it are substring matches
+pub const SUBSTRING_BONUS: i64 = 1000;
+
+/// Score how well `query` matches `candidate`, ignoring case. `None` if the
+/// query's characters don't all appear in order. Higher is better.
+pub fn score(query: &str, candidate: &str) -> Option<i64> {
+    score_with_case(query, candidate, false)
+}
+
+/// Like `score`, but with `case_sensitive` set an `A` only matches an `A`.
+pub fn score_with_case(query: &str, candidate: &str, case_sensitive: bool) -> Option<i64> {
+    let chars = |text: &str| -> Vec<char> {
+        if case_sensitive {
+            text.chars().collect()
+        } else {
+            text.chars().flat_map(char::to_lowercase).collect()
+        }
+    };
+    let query = chars(query);
+    let candidate = chars(candidate);
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    if let Some(start) = candidate.windows(query.len()).position(|window| window == query.as_slice()) {
+        // Earlier and word-aligned substrings rank first
+        let aligned = start == 0 || is_separator(candidate[start - 1]);
+        return Some(SUBSTRING_BONUS + if aligned { 200 } else { 0 } + 100 - (start as i64).min(100));
+    }
+
+    let mut score = 0;
+    let mut query_idx = 0;
+    let mut previous_match: Option<usize> = None;
+    for (idx, &c) in candidate.iter().enumerate() {
+        if query_idx == query.len() {
+            break;
+        }
+        if c != query[query_idx] {
+            continue;
+        }
+
+        score += 1;
+        if idx == 0 || is_separator(candidate[idx - 1]) {
+            score += 8;
+        }
+        match previous_match {
+            Some(previous) if previous + 1 == idx => score += 5,
+            // Penalize gaps, but not so much that a long name can't match
+            Some(previous) => score -= ((idx - previous - 1) as i64).min(3),
+            None => {}
+        }
+        previous_match = Some(idx);
+        query_idx += 1;
+    }
+
+    (query_idx == query.len()).then_some(score)
+}
+
+fn is_separator(c: char) -> bool {
+    matches!(c, '-' | '_' | '.' | '/' | '\\' | ' ' | ':')
+}
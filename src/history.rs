@@ -0,0 +1,105 @@
+//! A record of every change made through LazyEnv, appended as one JSON object
+//! per line to `history.jsonl` in the data directory.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::paths;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Create,
+    Delete,
+    Install,
+    Uninstall,
+    Reinstall,
+    Repair,
+}
+
+impl Operation {
+    pub fn label(self) -> &'static str {
+        match self {
+            Operation::Create => "create",
+            Operation::Delete => "delete",
+            Operation::Install => "install",
+            Operation::Uninstall => "uninstall",
+            Operation::Reinstall => "reinstall",
+            Operation::Repair => "repair",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub operation: Operation,
+    /// Path of the target environment, or its name if it was never created
+    pub environment: String,
+    pub package: Option<String>,
+    /// Version installed, or the one removed for uninstalls
+    pub version: Option<String>,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+impl HistoryEntry {
+    /// An entry timestamped now; it succeeded if there's no `error`.
+    pub fn new(
+        operation: Operation,
+        environment: impl Into<String>,
+        package: Option<&str>,
+        version: Option<&str>,
+        error: Option<String>,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+
+        Self {
+            timestamp,
+            operation,
+            environment: environment.into(),
+            package: package.map(str::to_string),
+            version: version.map(str::to_string),
+            success: error.is_none(),
+            error,
+        }
+    }
+}
+
+pub fn path() -> io::Result<PathBuf> {
+    Ok(paths::data_dir()?.join("history.jsonl"))
+}
+
+/// Append `entry` to the history file.
+pub fn append(entry: &HistoryEntry) -> io::Result<()> {
+    let line = serde_json::to_string(entry)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path()?)?;
+    writeln!(file, "{}", line)
+}
+
+/// The `limit` most recent entries, newest first. Lines that don't parse are
+/// skipped, and a missing file is an empty history.
+pub fn load_recent(limit: usize) -> io::Result<Vec<HistoryEntry>> {
+    let content = match fs::read_to_string(path()?) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    Ok(content
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .take(limit)
+        .collect())
+}
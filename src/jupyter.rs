@@ -0,0 +1,148 @@
+//! Jupyter kernels registered for the user, read from `jupyter kernelspec`,
+//! and registering environments as kernels through their own ipykernel.
+//! Everything here needs a `jupyter` command on PATH except registering,
+//! which only needs ipykernel inside the environment.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::Deserialize;
+
+use crate::process::{operation_timeout, output_with_timeout, probe_timeout};
+use crate::python::{environment_bin_dir, environment_python, PythonEnvironment};
+
+#[derive(Debug, Clone)]
+pub struct Kernel {
+    /// Name used to register and remove it, e.g. `myenv`
+    pub name: String,
+    /// Name shown in notebook kernel pickers, e.g. `Python (myenv)`
+    pub display_name: String,
+    /// Interpreter the kernel launches, if its command starts with an absolute path
+    pub python: Option<PathBuf>,
+}
+
+impl Kernel {
+    /// Whether the kernel launches `env`'s interpreter. Paths are compared as
+    /// written: resolving a venv's `python` symlink would land on its base
+    /// interpreter and match the wrong environment. Any interpreter in the
+    /// environment's bin directory counts (`python3`, `python3.12`), but not
+    /// one further down, so a conda base doesn't claim the kernels of its
+    /// `envs/*`.
+    pub fn launches(&self, env: &PythonEnvironment) -> bool {
+        self.python.as_ref().is_some_and(|python| if env.path.is_file() {
+            *python == env.path
+        } else {
+            let dir = python.parent();
+            // Conda on Windows keeps python.exe at the top of the environment
+            dir == Some(environment_bin_dir(&env.path).as_path()) || (cfg!(windows) && dir == Some(env.path.as_path()))
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct KernelspecList {
+    kernelspecs: BTreeMap<String, KernelspecEntry>,
+}
+
+#[derive(Deserialize)]
+struct KernelspecEntry {
+    spec: Kernelspec,
+}
+
+#[derive(Deserialize)]
+struct Kernelspec {
+    #[serde(default)]
+    argv: Vec<String>,
+    #[serde(default)]
+    display_name: String,
+}
+
+/// Kernels from `jupyter kernelspec list --json`, sorted by name. Fails with
+/// `NotFound` when Jupyter isn't installed.
+pub fn list_kernels() -> io::Result<Vec<Kernel>> {
+    let output = output_with_timeout(
+        Command::new("jupyter").args(["kernelspec", "list", "--json"]),
+        probe_timeout(),
+    )
+    .map_err(not_installed)?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "Failed to list Jupyter kernels: {}",
+            String::from_utf8_lossy(&output.stderr).trim(),
+        )));
+    }
+
+    let list: KernelspecList = serde_json::from_slice(&output.stdout)?;
+    Ok(list.kernelspecs
+        .into_iter()
+        .map(|(name, entry)| Kernel {
+            python: entry.spec.argv.first().map(PathBuf::from).filter(|path| path.is_absolute()),
+            display_name: entry.spec.display_name,
+            name,
+        })
+        .collect())
+}
+
+/// The first kernel that launches `env`'s interpreter.
+pub fn kernel_for<'a>(kernels: &'a [Kernel], env: &PythonEnvironment) -> Option<&'a Kernel> {
+    kernels.iter().find(|kernel| kernel.launches(env))
+}
+
+/// A kernel name for `env_name`; Jupyter only allows letters, digits, `-`,
+/// `_` and `.`.
+pub fn kernel_name(env_name: &str) -> String {
+    env_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c.to_ascii_lowercase() } else { '-' })
+        .collect()
+}
+
+/// Register the environment as a kernel for the current user with its own
+/// `python -m ipykernel install`.
+pub fn register_kernel(env_path: &Path, name: &str, display_name: &str) -> io::Result<()> {
+    let output = output_with_timeout(
+        Command::new(environment_python(env_path))
+            .args(["-m", "ipykernel", "install", "--user", "--name", name, "--display-name", display_name]),
+        operation_timeout(),
+    )?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("No module named ipykernel") {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "ipykernel isn't installed in this environment; install it first",
+        ))
+    } else {
+        Err(io::Error::other(format!("Failed to register kernel: {}", stderr.trim())))
+    }
+}
+
+/// Unregister the kernel `name` with `jupyter kernelspec remove`.
+pub fn remove_kernel(name: &str) -> io::Result<()> {
+    let output = output_with_timeout(
+        Command::new("jupyter").args(["kernelspec", "remove", "-f", name]),
+        operation_timeout(),
+    )
+    .map_err(not_installed)?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "Failed to remove kernel: {}",
+            String::from_utf8_lossy(&output.stderr).trim(),
+        )))
+    }
+}
+
+fn not_installed(error: io::Error) -> io::Error {
+    if error.kind() == io::ErrorKind::NotFound {
+        io::Error::new(io::ErrorKind::NotFound, "Jupyter isn't installed (no jupyter command on PATH)")
+    } else {
+        error
+    }
+}
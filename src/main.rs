@@ -1,8 +1,26 @@
 mod app;
+mod cli;
+mod clipboard;
+mod config;
+mod dotenv;
+mod fuzzy;
+mod history;
+mod jupyter;
+mod package_index;
+mod paths;
 mod ui;
 mod python;
+mod requirements;
+mod process;
+mod usage;
+mod version;
 
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::io;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
 use std::time::Duration;
 
 use crossterm::{
@@ -12,11 +30,63 @@ use crossterm::{
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
 
-use crate::app::{App, AppState, DialogState, Focus};
+use crate::app::{App, AppState, DialogState, Focus, LICENSE_FILTER_PREFIX, MAX_HISTORY_ENTRIES, READ_ONLY_KEYS};
+use crate::cli::CliCommand;
+use crate::config::{Config, Template};
+use crate::history::{HistoryEntry, Operation};
+use crate::usage::Usage;
+use crate::process::{cancel_running, last_command, make_cancellable, running_commands, split_pip_args};
 use crate::ui::ui;
-use crate::python::{list_environments, list_packages, create_environment, delete_environment, install_package, uninstall_package};
+use crate::python::{parse_requirement, requirement_hashes, sys_path, CreationBackend, virtualenv_tool, PENDING_VERSION, InstallOptions, Package, PythonEnvironment, count_packages, environment_dir, list_environments, project_environment, list_packages, create_environment, delete_environment, delete_environment_for_overwrite, install_package, install_package_with_progress, pip_phase_packages, uninstall_package, reinstall_package, package_dependents, preview_install, list_outdated_packages, list_leaf_packages, package_metadata, available_versions, cached_versions, package_licenses, normalize_package_name, pip_info, PipInfo, repair_venv, probe_version, directory_size, activation_vars, run_pip_command, freeze_requirements, find_broken_venvs, resolve_interpreter, PythonError, dependency_counts, set_pyenv_global, has_pip, bootstrap_pip, conda_remove_package, list_pip_packages, PackageManager, is_conda_environment, conda_env_export};
+
+/// Bounds for the configured `tick_rate_ms`
+const MIN_TICK_RATE_MS: u64 = 20;
+const MAX_TICK_RATE_MS: u64 = 1000;
+
+/// How long to wait for input when nothing is running and no timer is pending
+const IDLE_POLL_TIMEOUT: Duration = Duration::from_secs(2);
 
 fn main() -> Result<(), io::Error> {
+    let cli = match cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("lazyenv: {}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    
+    match cli.command {
+        CliCommand::Help => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        },
+        CliCommand::Version => {
+            println!("lazyenv {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        },
+        CliCommand::List => return print_environments(),
+        CliCommand::Install { .. } | CliCommand::Tui => {}
+    }
+    
+    let config_path = cli.config.or_else(|| Config::default_path().ok());
+    let mut config = config_path.as_deref().map(Config::load).unwrap_or_default();
+    
+    let read_only = cli.read_only || config.read_only;
+    
+    if let CliCommand::Install { environment, package } = &cli.command {
+        if read_only {
+            eprintln!("lazyenv: install isn't available in read-only mode");
+            std::process::exit(1);
+        }
+        if let Err(e) = install_from_cli(&config, environment, package) {
+            eprintln!("lazyenv: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    
+    install_panic_hook();
+    
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -26,410 +96,2881 @@ fn main() -> Result<(), io::Error> {
 
     // Create app state
     let mut app = App::new();
+    app.sidebar_percent = config.sidebar_percent;
+    app.wrap_navigation = config.wrap_navigation;
+    app.default_python = config.default_python.clone();
+    app.pinned = config.pinned_environments.clone();
+    app.templates = config.templates.clone();
+    app.hide_system_environments = config.hide_system_environments;
+    app.group_environments = config.group_environments;
+    app.show_activation_command = config.show_activation_command;
+    app.confirm_quit = config.confirm_quit;
+    app.show_dependency_counts = config.dependency_counts;
+    app.env_sort = config.env_sort;
+    app.outdated_prereleases = config.outdated_prereleases;
+    app.usage = Usage::load();
+    app.install_options = config.install_options();
+    app.creation_backend = config.creation_backend;
+    app.read_only = read_only;
     
     // Load initial data
     match list_environments() {
-        Ok(envs) => {
-            app.environments = envs;
-            if !app.environments.is_empty() {
-                app.selected_environment = Some(0);
-                // Don't load packages initially to avoid errors
+        Ok((envs, warnings)) => {
+            app.set_environments(envs);
+            app.set_detection_warnings(warnings);
+            if app.creation_backend == CreationBackend::Virtualenv && virtualenv_tool().is_none() {
+                app.push_log("warning: creation_backend is virtualenv, but virtualenv isn't on PATH; new environments will use python -m venv");
             }
+            // Prefer the environment belonging to the current project
+            if let Some(idx) = project_environment(&app.environments) {
+                app.selected_environment = Some(idx);
+            }
+            // Don't load packages initially to avoid errors
         },
         Err(e) => {
-            eprintln!("Error loading environments: {}", e);
             // Continue with empty environments list
+            app.status_message = Some(format!("Error loading environments: {}", e));
         }
     }
 
+    let mut package_counts = Some(spawn_package_counts(&app.environments));
+    let mut version_probes = Some(spawn_version_probes(&app.environments));
+    let mut package_listing: Option<PackageListing> = None;
+    let mut outdated_check: Option<Receiver<OutdatedCheck>> = None;
+    let mut version_lookup: Option<Receiver<VersionLookup>> = None;
+    let mut install_preview: Option<Receiver<InstallPreview>> = None;
+    let mut leaf_listing: Option<LeafListing> = None;
+    let mut conda_export: Option<Receiver<CondaExport>> = None;
+    let mut bulk_install: Option<BulkInstall> = None;
+    let mut license_fetch: Option<Receiver<LicenseBatch>> = None;
+    let mut pip_probe: Option<Receiver<(PathBuf, Option<PipInfo>)>> = None;
+    let mut disk_usage_scan: Option<Receiver<(String, PathBuf, u64)>> = None;
+    let mut hash_export: Option<Receiver<HashProgress>> = None;
+    let mut env_creation: Option<Creation> = None;
+    let mut batch_creation: Option<BatchCreation> = None;
+    let mut dependency_probe: Option<DependencyProbe> = None;
+    let mut package_names = config.package_completion.then(spawn_package_index_load);
+
     // Main loop
-    let tick_rate = Duration::from_millis(100);
+    let tick_rate = Duration::from_millis(config.tick_rate_ms.clamp(MIN_TICK_RATE_MS, MAX_TICK_RATE_MS));
+    // Timers count ticks, so convert their durations for the configured rate
+    let ticks_in = |duration: Duration| (duration.as_millis() / tick_rate.as_millis()).clamp(1, u8::MAX as u128) as u8;
+    let status_message_ticks = ticks_in(Duration::from_secs(2));
+    let type_ahead_ticks = ticks_in(Duration::from_secs(1));
     let mut last_tick = std::time::Instant::now();
+    // Only redraw when something changed: an event, a background result or a timer
+    let mut dirty = true;
+    let navigation_debounce = Duration::from_millis(config.navigation_debounce_ms);
+    let mut last_navigation: Option<(KeyCode, std::time::Instant)> = None;
 
     loop {
-        terminal.draw(|f| ui(f, &mut app))?;
-
-        let timeout = tick_rate
-            .checked_sub(last_tick.elapsed())
-            .unwrap_or_else(|| Duration::from_secs(0));
-
-        if crossterm::event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                match app.state {
-                    AppState::Normal => match key.code {
-                        KeyCode::Char('q') => break,
-                        KeyCode::Down => {
-                            if app.focus == Focus::Environments {
-                                app.next_environment();
-                            } else if app.focus == Focus::Packages {
-                                app.next_package();
-                            }
-                        },
-                        KeyCode::Up => {
-                            if app.focus == Focus::Environments {
-                                app.previous_environment();
-                            } else if app.focus == Focus::Packages {
-                                app.previous_package();
-                            }
-                        },
-                        KeyCode::Tab => app.toggle_focus(),
-                        KeyCode::Enter => {
-                            if let Some(idx) = app.selected_environment {
-                                match list_packages(&app.environments[idx].path) {
-                                    Ok(pkgs) => {
-                                        app.packages = pkgs;
-                                        if !app.packages.is_empty() {
-                                            app.selected_package = Some(0);
-                                        }
-                                    },
-                                    Err(e) => {
-                                        app.status_message = Some(format!("Error listing packages: {}", e));
-                                    }
-                                }
-                            }
-                        },
-                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
-                        KeyCode::Char('n') => {
-                            app.state = AppState::CreateEnvironment;
-                            app.input_text.clear();
-                        },
-                        KeyCode::Char('d') => {
-                            if let Some(idx) = app.selected_environment {
-                                app.state = AppState::DeleteEnvironment;
-                                app.dialog_state = DialogState::Confirm;
-                            }
+        if let Some(rx) = &package_counts
+            && drain(rx, |(path, count, has_pip)| {
+                if has_pip == Some(false) {
+                    app.pipless.insert(path.clone());
+                }
+                app.package_counts.insert(path, count);
+                dirty = true;
+            }) {
+            package_counts = None;
+        }
+        if let Some(rx) = &version_probes
+            && drain(rx, |(path, version)| {
+                app.set_environment_version(&path, version);
+                dirty = true;
+            }) {
+            version_probes = None;
+        }
+        if let Some((path, result)) = package_listing.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            dirty = true;
+            package_listing = None;
+            app.loading_packages = false;
+            // The listing usually follows an operation that may have changed pip
+            app.pip_info.remove(&path);
+            app.dependency_counts.remove(&path);
+            // Ignore a listing for an environment the user has since moved away from
+            let still_selected = app.current_environment()
+                .is_some_and(|env| env.path == path);
+            if still_selected && !app.show_global_packages {
+                match result {
+                    Ok(pkgs) => {
+                        app.set_packages(pkgs);
+                    },
+                    Err(e) => {
+                        app.status_message = Some(format!("Error listing packages: {}", e));
+                    }
+                }
+            }
+        }
+        if let Some(check) = outdated_check.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            dirty = true;
+            outdated_check = None;
+            apply_outdated_check(&mut app, check);
+        }
+        if let Some(lookup) = version_lookup.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            dirty = true;
+            version_lookup = None;
+            open_version_picker(&mut app, lookup);
+        }
+        if let Some(preview) = install_preview.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            dirty = true;
+            install_preview = None;
+            show_install_preview(&mut app, preview);
+        }
+        if let Some(export) = conda_export.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            dirty = true;
+            conda_export = None;
+            finish_conda_export(&mut app, export);
+        }
+        if let Some((path, result)) = leaf_listing.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            dirty = true;
+            leaf_listing = None;
+            let still_selected = app.current_environment()
+                .is_some_and(|env| env.path == path);
+            if still_selected && !app.show_global_packages {
+                match result {
+                    Ok(leaves) => {
+                        app.status_message = Some(format!("{} of {} packages aren't required by anything else", leaves.len(), app.packages.len()));
+                        app.leaves = Some(leaves);
+                    },
+                    Err(e) => {
+                        app.status_message = Some(format!("Error listing leaf packages: {}", e));
+                    }
+                }
+            }
+        }
+        
+        if let Some(result) = package_names.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            dirty = true;
+            package_names = None;
+            match result {
+                Ok(names) => app.package_index = Some(names),
+                Err(e) => app.push_log(&format!("Package name completion is unavailable: {}", e)),
+            }
+        }
+        if let Some((path, info)) = pip_probe.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            dirty = true;
+            pip_probe = None;
+            app.pip_info.insert(path, info);
+        }
+        if pip_probe.is_none()
+            && package_listing.is_none()
+            && let Some(env) = app.current_environment()
+            && !app.pip_info.contains_key(&env.path) {
+            pip_probe = Some(spawn_pip_probe(env.path.clone()));
+        }
+        if let Some((path, counts)) = dependency_probe.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            dirty = true;
+            dependency_probe = None;
+            app.dependency_counts.insert(path, counts);
+        }
+        if app.show_dependency_counts
+            && dependency_probe.is_none()
+            && package_listing.is_none()
+            && !app.show_global_packages
+            && let Some(env) = app.current_environment()
+            && !app.dependency_counts.contains_key(&env.path) {
+            dependency_probe = Some(spawn_dependency_probe(env.path.clone()));
+        }
+        
+        if let Some(rx) = &disk_usage_scan
+            && drain(rx, |(name, path, size)| {
+                app.add_disk_usage(name, path, size);
+                dirty = true;
+            }) {
+            disk_usage_scan = None;
+            app.disk_usage_pending = 0;
+        }
+        
+        if let Some(rx) = &license_fetch
+            && drain(rx, |batch| {
+                apply_license_batch(&mut app, batch);
+                dirty = true;
+            }) {
+            license_fetch = None;
+        }
+        
+        if let Some(rx) = &hash_export
+            && drain(rx, |progress| {
+                apply_hash_progress(&mut app, progress);
+                dirty = true;
+            }) {
+            hash_export = None;
+        }
+        
+        if let Some(result) = env_creation.as_ref().and_then(|creation| creation.result.try_recv().ok())
+            && let Some(creation) = env_creation.take() {
+            dirty = true;
+            let created = finish_create(&mut app, &creation.name, result);
+            if created.is_some() {
+                package_listing = spawn_package_listing(&mut app);
+            }
+            if let Some(template) = creation.template
+                && let Some(env_path) = created {
+                if bulk_install.is_some() {
+                    app.status_message = Some(format!("Environment '{}' created; template '{}' not installed while other installs run", creation.name, template.name));
+                } else {
+                    let label = format!("Template '{}'", template.name);
+                    bulk_install = Some(BulkInstall::spawn(label, env_path, template.packages, app.install_options.clone(), false));
+                }
+            }
+        }
+        
+        if let Some(batch) = &mut batch_creation
+            && batch.poll(&mut app) {
+            batch_creation = None;
+            dirty = true;
+            package_listing = spawn_package_listing(&mut app);
+        }
+        
+        if let Some(bulk) = &mut bulk_install
+            && bulk.poll(&mut app) {
+            // Refresh the list if the user is still looking at that environment
+            if !app.show_global_packages
+                && app.current_environment().is_some_and(|env| env.path == bulk.env_path) {
+                if bulk.recheck_outdated {
+                    outdated_check = Some(spawn_outdated_check(bulk.env_path.clone(), app.outdated_prereleases, true));
+                } else {
+                    package_listing = spawn_package_listing(&mut app);
+                }
+            }
+            bulk_install = None;
+        }
+        
+        if dirty {
+            terminal.draw(|f| ui(f, &mut app))?;
+            dirty = false;
+        }
+
+        // With nothing running and no timer pending there's nothing to tick
+        // for, so sleep until a key arrives (or the idle timeout passes)
+        let busy = package_counts.is_some()
+            || version_probes.is_some()
+            || package_listing.is_some()
+            || outdated_check.is_some()
+            || version_lookup.is_some()
+            || install_preview.is_some()
+            || leaf_listing.is_some()
+            || conda_export.is_some()
+            || package_names.is_some()
+            || pip_probe.is_some()
+            || dependency_probe.is_some()
+            || disk_usage_scan.is_some()
+            || license_fetch.is_some()
+            || hash_export.is_some()
+            || env_creation.is_some()
+            || batch_creation.is_some()
+            || bulk_install.is_some()
+            || app.status_message.is_some()
+            || !app.type_ahead.is_empty();
+        let timeout = if busy {
+            tick_rate
+                .checked_sub(last_tick.elapsed())
+                .unwrap_or_else(|| Duration::from_secs(0))
+        } else {
+            IDLE_POLL_TIMEOUT
+        };
+
+        let event = if crossterm::event::poll(timeout)? {
+            dirty = true;
+            Some(event::read()?)
+        } else {
+            None
+        };
+        
+        if let Some(Event::Resize(..)) = event {
+            // Resize the buffers before the next draw so nothing is laid out against the old size
+            terminal.autoresize()?;
+        }
+        
+        // Terminals that flood events while a key is held would otherwise make
+        // the selection overshoot, so drop repeats inside the debounce window
+        let event = match event {
+            Some(Event::Key(key))
+                if !navigation_debounce.is_zero()
+                    && matches!(key.code, KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown) => {
+                let now = std::time::Instant::now();
+                let repeated = last_navigation
+                    .is_some_and(|(code, at)| code == key.code && now.duration_since(at) < navigation_debounce);
+                if repeated {
+                    None
+                } else {
+                    last_navigation = Some((key.code, now));
+                    Some(Event::Key(key))
+                }
+            },
+            event => event,
+        };
+        
+        if let Some(Event::Key(key)) = event {
+            // Any key other than a digit or a jump key abandons a pending count
+            if app.state == AppState::Normal
+                && !matches!(key.code, KeyCode::Char('0'..='9' | 'G') | KeyCode::Enter) {
+                app.count_buffer.clear();
+            }
+            // Only consecutive Tabs cycle through completions
+            if key.code != KeyCode::Tab {
+                app.completion_cycle = None;
+            }
+            let selected_package = app.selected_package;
+            
+            // Ctrl+X works from any view: kill the pip or venv run a background
+            // operation is stuck on and forget the operation
+            if key.code == KeyCode::Char('x') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                let running = bulk_install.is_some()
+                    || env_creation.is_some()
+                    || batch_creation.is_some()
+                    || hash_export.is_some()
+                    || license_fetch.is_some()
+                    || package_listing.is_some()
+                    || outdated_check.is_some()
+                    || version_lookup.is_some()
+                    || install_preview.is_some()
+                    || leaf_listing.is_some()
+                    || conda_export.is_some();
+                if running {
+                    cancel_running();
+                    // Dropping the receivers makes the threads stop at their next send
+                    bulk_install = None;
+                    env_creation = None;
+                    batch_creation = None;
+                    hash_export = None;
+                    license_fetch = None;
+                    package_listing = None;
+                    outdated_check = None;
+                    version_lookup = None;
+                    install_preview = None;
+                    leaf_listing = None;
+                    conda_export = None;
+                    app.creating = None;
+                    app.working = None;
+                    app.installing = None;
+                    app.loading_packages = false;
+                    app.reset_state();
+                    app.push_log("cancelled the running operation");
+                    app.status_message = Some("Operation cancelled".to_string());
+                } else {
+                    app.status_message = Some("Nothing is running in the background".to_string());
+                }
+                app.status_message_timer = 0;
+                continue;
+            }
+            // Ctrl+O shows what LazyEnv is running on the user's behalf, and logs it for bug reports
+            if key.code == KeyCode::Char('o') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                let running = running_commands();
+                app.status_message = Some(if running.is_empty() {
+                    match last_command() {
+                        Some(line) => {
+                            app.push_log(&format!("last command: {}", line));
+                            format!("Nothing running; last command: {} (l: log)", line)
                         },
-                        KeyCode::Char('i') => {
-                            if let Some(_) = app.selected_environment {
-                                app.state = AppState::InstallPackage;
-                                app.input_text.clear();
+                        None => "No commands have been run yet".to_string(),
+                    }
+                } else {
+                    for line in &running {
+                        app.push_log(&format!("running: {}", line));
+                    }
+                    format!("Running: {} (l: log)", running.join(" | "))
+                });
+                app.status_message_timer = 0;
+                continue;
+            }
+            
+            match app.state {
+                AppState::Normal => match key.code {
+                    KeyCode::Char('q') if app.confirm_quit => app.open(AppState::ConfirmQuit),
+                    KeyCode::Char('q') => break,
+                    KeyCode::Esc => {
+                        app.status_message = None;
+                        app.status_message_timer = 0;
+                        app.search_matches.clear();
+                    },
+                    KeyCode::Up | KeyCode::Down
+                        if key.modifiers.contains(KeyModifiers::SHIFT) && app.focus == Focus::Environments => {
+                        let moved = if key.code == KeyCode::Up {
+                            app.move_environment_up()
+                        } else {
+                            app.move_environment_down()
+                        };
+                        if !moved {
+                            app.status_message = Some("Only pinned environments can be reordered; press p to pin".to_string());
+                        }
+                    },
+                    KeyCode::Up | KeyCode::Down
+                        if key.modifiers.contains(KeyModifiers::SHIFT) && app.focus == Focus::Packages => {
+                        // Clamped to the content when the panel is drawn
+                        app.details_scroll = if key.code == KeyCode::Up {
+                            app.details_scroll.saturating_sub(1)
+                        } else {
+                            app.details_scroll.saturating_add(1)
+                        };
+                    },
+                    KeyCode::Down => {
+                        if app.focus == Focus::Environments {
+                            app.next_environment();
+                        } else if app.focus == Focus::Packages {
+                            app.next_package();
+                        }
+                    },
+                    KeyCode::Up => {
+                        if app.focus == Focus::Environments {
+                            app.previous_environment();
+                        } else if app.focus == Focus::Packages {
+                            app.previous_package();
+                        }
+                    },
+                    KeyCode::Tab if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.next_tab();
+                        package_listing = None;
+                    },
+                    KeyCode::Tab => app.toggle_focus(),
+                    KeyCode::Char(c @ '1'..='9') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        app.switch_tab(c as usize - '1' as usize);
+                        package_listing = None;
+                    },
+                    KeyCode::Char(c) if c.is_alphabetic() && key.modifiers.contains(KeyModifiers::ALT) => {
+                        app.type_ahead(c);
+                    },
+                    KeyCode::Char('n' | 'N') if !app.search_matches.is_empty() => {
+                        app.step_search_match(key.code == KeyCode::Char('N'));
+                        package_listing = spawn_package_listing(&mut app);
+                    },
+                    KeyCode::Char(c) if app.read_only && READ_ONLY_KEYS.contains(&c) => app.refuse_read_only(),
+                    KeyCode::Char(c) if c.is_ascii_digit() => {
+                        app.count_buffer.push(c);
+                    },
+                    KeyCode::Char('G') => {
+                        let position = app.count_buffer.parse().ok();
+                        app.count_buffer.clear();
+                        app.jump_to(position);
+                    },
+                    KeyCode::Enter if !app.count_buffer.is_empty() => {
+                        let position = app.count_buffer.parse().ok();
+                        app.count_buffer.clear();
+                        app.jump_to(position);
+                    },
+                    // With no package to show (e.g. nothing listed yet), Enter lists the environment's packages
+                    KeyCode::Enter if app.focus == Focus::Packages && app.selected_package.is_some() => show_package_details(&mut app),
+                    KeyCode::Enter => {
+                        if let Some(path) = app.current_environment().map(|env| env.path.clone()) {
+                            app.usage.record(&path);
+                        }
+                        package_listing = spawn_package_listing(&mut app);
+                    },
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+                    KeyCode::Char('n') => {
+                        app.open(AppState::CreateEnvironment);
+                        app.clear_input();
+                        app.python_override.clear();
+                        app.editing_python = false;
+                        app.selected_template = 0;
+                        app.confirmed_overwrite = None;
+                    },
+                    KeyCode::Char('d') if app.selected_environment.is_some() => {
+                        app.open(AppState::DeleteEnvironment);
+                        app.dialog_state = DialogState::Confirm;
+                    },
+                    KeyCode::Char('i' | 'I' | 'r' | 'f') if app.selected_is_pipx() => {
+                        app.status_message = Some("pipx environments are managed by pipx; use 'pipx inject' / 'pipx uninject'".to_string());
+                    },
+                    KeyCode::Char('i') if app.selected_environment.is_some() => {
+                        app.open(AppState::InstallPackage);
+                        app.clear_input();
+                        app.index_override.clear();
+                        app.install_upgrade = false;
+                        app.install_pre = false;
+                        app.editing_index = false;
+                    },
+                    KeyCode::Char('I') if app.selected_environment.is_some() => install_from_clipboard(&mut app),
+                    KeyCode::Char('r') => begin_uninstall(&mut app),
+                    KeyCode::Char('f') => begin_reinstall(&mut app),
+                    KeyCode::Char('s') => {
+                        app.open(AppState::SearchEnvironment);
+                        app.clear_input();
+                    },
+                    KeyCode::Char('o') if !app.show_global_packages => {
+                        if let Some(env) = app.current_environment() {
+                            outdated_check = Some(spawn_outdated_check(env.path.clone(), app.outdated_prereleases, false));
+                            app.status_message = Some("Checking for outdated packages…".to_string());
+                        }
+                    },
+                    KeyCode::Char('L') if !app.show_global_packages => {
+                        if app.leaves.take().is_none()
+                            && let Some(env) = app.current_environment() {
+                            leaf_listing = Some(spawn_leaf_listing(env.path.clone()));
+                            app.status_message = Some("Finding packages nothing else requires…".to_string());
+                        }
+                    },
+                    KeyCode::Char('K') if !app.show_global_packages => {
+                        if license_fetch.is_some() {
+                            app.status_message = Some("Licenses are already being fetched".to_string());
+                        } else if let Some(env) = app.current_environment() {
+                            // Only fetch what the cache doesn't have yet
+                            let cached = app.licenses.get(&env.path);
+                            let names: Vec<String> = app.packages
+                                .iter()
+                                .filter(|pkg| !cached.is_some_and(|licenses| licenses.contains_key(&normalize_package_name(&pkg.name))))
+                                .map(|pkg| pkg.name.clone())
+                                .collect();
+                            if names.is_empty() {
+                                app.status_message = Some(format!("Licenses are already fetched; filter with {}<name>", LICENSE_FILTER_PREFIX));
+                            } else {
+                                let total = names.len();
+                                license_fetch = Some(spawn_license_fetch(env.path.clone(), names));
+                                app.status_message = Some(format!("Fetching licenses 0/{}…", total));
                             }
-                        },
-                        KeyCode::Char('r') => {
-                            if let Some(_) = app.selected_environment {
-                                if let Some(pkg_idx) = app.selected_package {
-                                    if pkg_idx < app.packages.len() {
-                                        app.state = AppState::UninstallPackage;
-                                        app.dialog_state = DialogState::Confirm;
-                                    }
+                        }
+                    },
+                    KeyCode::Char('D') => {
+                        // A scan already running keeps going; just show its progress
+                        if disk_usage_scan.is_none() {
+                            let venvs: Vec<(String, PathBuf)> = app.environments
+                                .iter()
+                                .filter(|env| env.env_type == "venv")
+                                .map(|env| (env.name.clone(), env.path.clone()))
+                                .collect();
+                            app.disk_usage.clear();
+                            app.disk_usage_pending = venvs.len();
+                            disk_usage_scan = Some(spawn_disk_usage_scan(venvs));
+                        }
+                        app.disk_usage_scroll = 0;
+                        app.open(AppState::DiskUsage);
+                    },
+                    KeyCode::Char('E') => show_dotenv(&mut app),
+                    KeyCode::Char('P') => {
+                        if let Some(env) = app.current_environment() {
+                            match sys_path(&env.path) {
+                                Ok(entries) => {
+                                    app.sys_path = entries;
+                                    app.sys_path_scroll = 0;
+                                    app.open(AppState::SysPathView);
+                                },
+                                Err(e) => {
+                                    app.status_message = Some(format!("Error reading sys.path: {}", e));
                                 }
                             }
-                        },
-                        KeyCode::Char('s') => {
-                            app.state = AppState::SearchEnvironment;
-                            app.input_text.clear();
-                        },
-                        KeyCode::Char('g') => {
-                            app.show_global_packages = !app.show_global_packages;
-                            if app.show_global_packages {
-                                match python::list_global_packages() {
-                                    Ok(pkgs) => {
-                                        app.packages = pkgs;
-                                        if !app.packages.is_empty() {
-                                            app.selected_package = Some(0);
-                                        }
-                                    },
-                                    Err(e) => {
-                                        app.status_message = Some(format!("Error listing global packages: {}", e));
-                                    }
-                                }
-                            } else if let Some(idx) = app.selected_environment {
-                                match list_packages(&app.environments[idx].path) {
-                                    Ok(pkgs) => {
-                                        app.packages = pkgs;
-                                        if !app.packages.is_empty() {
-                                            app.selected_package = Some(0);
-                                        }
-                                    },
-                                    Err(e) => {
-                                        app.status_message = Some(format!("Error listing packages: {}", e));
-                                    }
+                        }
+                    },
+                    KeyCode::Char('J') => {
+                        app.selected_kernel = 0;
+                        if load_kernels(&mut app) {
+                            app.open(AppState::KernelView);
+                        }
+                    },
+                    KeyCode::Char('V') if !app.show_global_packages && !app.selected_is_pipx() => {
+                        if let Some(lookup) = spawn_version_lookup(&mut app) {
+                            version_lookup = Some(lookup);
+                        }
+                    },
+                    KeyCode::Char('U') if !app.show_global_packages => {
+                        if app.outdated.is_none() {
+                            app.status_message = Some("Press o to check for outdated packages first".to_string());
+                        } else if app.outdated_packages().is_empty() {
+                            app.status_message = Some("Everything is up to date".to_string());
+                        } else if app.upgradable_packages().is_empty() {
+                            app.status_message = Some("Only conda-managed packages are outdated; upgrade them with conda update".to_string());
+                        } else if bulk_install.is_some() {
+                            app.status_message = Some("Wait for the running installs to finish first".to_string());
+                        } else {
+                            app.open(AppState::ConfirmUpgradeAll);
+                        }
+                    },
+                    KeyCode::Char('!') if app.selected_environment.is_some() => {
+                        app.open(AppState::PipCommand);
+                        app.clear_input();
+                    },
+                    KeyCode::Char('=') if !app.show_global_packages && app.selected_environment.is_some() => {
+                        app.open(AppState::CompareRequirements);
+                        // The last file compared, or the usual name
+                        app.input_text = app.requirements_diff.as_ref().map_or_else(|| "requirements.txt".to_string(), |(path, _)| path.display().to_string());
+                        app.input_end();
+                    },
+                    KeyCode::Char('l') => app.show_log(),
+                    KeyCode::Char('y') if !app.show_global_packages => copy_freeze(&mut app),
+                    KeyCode::Char('F') if !app.show_global_packages => {
+                        if hash_export.is_some() {
+                            app.status_message = Some("Hashed requirements are already being generated".to_string());
+                        } else if let Some(env) = app.current_environment().cloned() {
+                            // Written next to where LazyEnv was started, like a project's requirements
+                            match std::env::current_dir() {
+                                Ok(dir) => {
+                                    let output = dir.join(format!("requirements-{}-hashed.txt", safe_file_name(&env.name)));
+                                    app.status_message = Some(format!("Generating hashed requirements for {}…", env.name));
+                                    hash_export = Some(spawn_hash_export(env, app.install_options.clone(), output));
+                                },
+                                Err(e) => {
+                                    app.status_message = Some(format!("Error finding the current directory: {}", e));
                                 }
                             }
+                        }
+                    },
+                    KeyCode::Char('Y') => copy_package_table(&mut app),
+                    KeyCode::Char('X') if !app.show_global_packages => match app.current_environment() {
+                        Some(env) if is_conda_environment(&env.path) => app.open(AppState::ConfirmCondaExport),
+                        Some(_) => {
+                            app.status_message = Some("environment.yml export is for conda environments only; y or F export pip requirements".to_string());
                         },
-                        KeyCode::Char('R') => {
-                            // Refresh environments
-                            match list_environments() {
-                                Ok(envs) => {
-                                    app.environments = envs;
-                                    if !app.environments.is_empty() {
-                                        app.selected_environment = Some(0);
-                                        app.status_message = Some("Environments refreshed".to_string());
-                                    }
+                        None => {},
+                    },
+                    KeyCode::Char('c') => copy_install_command(&mut app),
+                    KeyCode::Char('/') => {
+                        app.open(AppState::FilterPackages);
+                        app.focus = Focus::Packages;
+                        app.search_matches.clear();
+                    },
+                    KeyCode::Char('g') => {
+                        // Switching between views always starts unfiltered
+                        app.show_global_packages = !app.show_global_packages;
+                        app.package_filter.clear();
+                        if app.show_global_packages {
+                            // Drop any environment listing still in flight
+                            package_listing = None;
+                            app.loading_packages = false;
+                            match python::list_global_packages() {
+                                Ok(pkgs) => {
+                                    app.set_packages(pkgs);
                                 },
                                 Err(e) => {
-                                    app.status_message = Some(format!("Error refreshing environments: {}", e));
+                                    app.status_message = Some(format!("Error listing global packages: {}", e));
                                 }
                             }
-                        },
-                        KeyCode::Char('x') => {
-                            app.state = AppState::HelpMenu;
-                        },
-                        _ => {}
-                    },
-                    AppState::PackageView => match key.code {
-                        KeyCode::Char('q') => break,
-                        KeyCode::Down => app.next_package(),
-                        KeyCode::Up => app.previous_package(),
-                        KeyCode::Tab => app.toggle_focus(),
-                        KeyCode::Esc => app.state = AppState::Normal,
-                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
-                        KeyCode::Char('i') => {
-                            if let Some(_) = app.selected_environment {
-                                app.state = AppState::InstallPackage;
-                                app.input_text.clear();
+                        } else {
+                            package_listing = spawn_package_listing(&mut app);
+                            // With no environment selected there's nothing to list, so
+                            // don't leave the global packages showing under the wrong title
+                            if package_listing.is_none() {
+                                app.set_packages(Vec::new());
                             }
-                        },
-                        KeyCode::Char('r') => {
-                            if let Some(_) = app.selected_environment {
-                                if let Some(pkg_idx) = app.selected_package {
-                                    if pkg_idx < app.packages.len() {
-                                        app.state = AppState::UninstallPackage;
-                                        app.dialog_state = DialogState::Confirm;
-                                    }
-                                }
+                        }
+                    },
+                    KeyCode::Char('R') => {
+                        // Refresh environments
+                        match list_environments() {
+                            Ok((envs, warnings)) => {
+                                app.set_environments(envs);
+                                app.set_detection_warnings(warnings);
+                                app.package_counts.clear();
+                                app.pipless.clear();
+                                package_counts = Some(spawn_package_counts(&app.environments));
+                                version_probes = Some(spawn_version_probes(&app.environments));
+                                app.status_message = Some("Environments refreshed".to_string());
+                            },
+                            Err(e) => {
+                                app.status_message = Some(format!("Error refreshing environments: {}", e));
                             }
-                        },
-                        KeyCode::Char('x') => {
-                            app.state = AppState::HelpMenu;
-                        },
-                        _ => {}
+                        }
                     },
-                    AppState::HelpMenu => match key.code {
-                        KeyCode::Esc | KeyCode::Char('x') => {
-                            app.state = AppState::Normal;
-                        },
-                        _ => {}
+                    KeyCode::Char('x') => {
+                        app.open(AppState::HelpMenu);
                     },
-                    AppState::CreateEnvironment => match key.code {
-                        KeyCode::Esc => {
-                            app.state = AppState::Normal;
-                        },
-                        KeyCode::Enter => {
-                            if !app.input_text.is_empty() {
-                                match create_environment(&app.input_text) {
-                                    Ok(env) => {
-                                        app.environments.push(env);
-                                        app.selected_environment = Some(app.environments.len() - 1);
-                                        match list_packages(&app.environments[app.environments.len() - 1].path) {
-                                            Ok(pkgs) => {
-                                                app.packages = pkgs;
-                                                if !app.packages.is_empty() {
-                                                    app.selected_package = Some(0);
-                                                }
-                                            },
-                                            Err(e) => {
-                                                app.status_message = Some(format!("Error listing packages: {}", e));
-                                            }
-                                        }
-                                        app.state = AppState::Normal;
-                                        app.status_message = Some(format!("Environment '{}' created successfully", app.input_text));
-                                    },
-                                    Err(e) => {
-                                        app.status_message = Some(format!("Error creating environment: {}", e));
-                                    }
-                                }
-                            }
-                        },
-                        KeyCode::Char(c) => {
-                            app.input_text.push(c);
-                        },
-                        KeyCode::Backspace => {
-                            app.input_text.pop();
-                        },
-                        _ => {}
+                    KeyCode::Char('C') => begin_cleanup(&mut app),
+                    KeyCode::Char('p') => app.toggle_pin(),
+                    KeyCode::Char('t') if app.selected_environment.is_some() => {
+                        app.open_tab();
+                        package_listing = spawn_package_listing(&mut app);
+                    },
+                    KeyCode::Char('w') => {
+                        if app.close_tab() {
+                            package_listing = None;
+                        } else {
+                            app.status_message = Some("Can't close the last tab".to_string());
+                        }
+                    },
+                    KeyCode::Char(']') => {
+                        app.next_tab();
+                        package_listing = None;
+                    },
+                    KeyCode::Char('[') => {
+                        app.previous_tab();
+                        package_listing = None;
+                    },
+                    KeyCode::Char('S') => {
+                        app.cycle_env_sort();
+                        app.status_message = Some(format!("Sorting environments by {}", app.env_sort.label()));
                     },
-                    AppState::DeleteEnvironment => match key.code {
-                        KeyCode::Esc => {
-                            app.state = AppState::Normal;
-                            app.dialog_state = DialogState::None;
+                    KeyCode::Char('m') => app.show_summaries = !app.show_summaries,
+                    KeyCode::Char('z') => app.show_details = !app.show_details,
+                    KeyCode::Char('W') => {
+                        app.wrap_details = !app.wrap_details;
+                        app.status_message = Some(if app.wrap_details {
+                            "Wrapping long lines in the details panel".to_string()
+                        } else {
+                            "Cutting off long lines in the details panel".to_string()
+                        });
+                    },
+                    KeyCode::Char('a') => {
+                        if let Some(env) = app.current_environment().cloned() {
+                            app.status_message = Some(match open_shell(&mut terminal, &env) {
+                                Ok(_) => format!("Back from the {} shell", env.name),
+                                Err(e) => format!("Error opening a shell: {}", e),
+                            });
+                        }
+                    },
+                    KeyCode::Char('v') => app.short_versions = !app.short_versions,
+                    KeyCode::Char('H') => match history::load_recent(MAX_HISTORY_ENTRIES) {
+                        Ok(entries) => {
+                            app.history = entries;
+                            app.history_scroll = 0;
+                            app.open(AppState::HistoryView);
                         },
-                        KeyCode::Char('y') => {
-                            if let Some(idx) = app.selected_environment {
-                                let env_path = app.environments[idx].path.clone();
-                                let env_name = app.environments[idx].name.clone();
-                                match delete_environment(&env_path) {
-                                    Ok(_) => {
-                                        app.environments.remove(idx);
-                                        if app.environments.is_empty() {
-                                            app.selected_environment = None;
-                                            app.packages.clear();
-                                        } else {
-                                            app.selected_environment = Some(idx.min(app.environments.len() - 1));
-                                            match list_packages(&app.environments[app.selected_environment.unwrap()].path) {
-                                                Ok(pkgs) => {
-                                                    app.packages = pkgs;
-                                                    if !app.packages.is_empty() {
-                                                        app.selected_package = Some(0);
-                                                    }
-                                                },
-                                                Err(e) => {
-                                                    app.status_message = Some(format!("Error listing packages: {}", e));
-                                                }
-                                            }
-                                        }
-                                        app.status_message = Some(format!("Environment '{}' deleted successfully", env_name));
-                                    },
-                                    Err(e) => {
-                                        app.status_message = Some(format!("Error deleting environment: {}", e));
-                                    }
-                                }
+                        Err(e) => {
+                            app.status_message = Some(format!("Error reading history: {}", e));
+                        }
+                    },
+                    KeyCode::Char('h') => {
+                        app.toggle_system_environments();
+                        app.status_message = Some(if app.hide_system_environments {
+                            "Hiding system and pyenv interpreters".to_string()
+                        } else {
+                            "Showing all environments".to_string()
+                        });
+                    },
+                    KeyCode::Char('T') => {
+                        app.toggle_environment_grouping();
+                        app.status_message = Some(if app.group_environments {
+                            "Grouping environments by type".to_string()
+                        } else {
+                            "Showing environments as one list".to_string()
+                        });
+                    },
+                    KeyCode::Char('>') => app.grow_sidebar(),
+                    KeyCode::Char('<') => app.shrink_sidebar(),
+                    // Without pyenv there's nothing to switch, so the key does nothing
+                    KeyCode::Char('M') if app.pyenv_global.is_some() => {
+                        match app.current_environment() {
+                            Some(env) if app.is_pyenv_global(env) => {
+                                app.status_message = Some(format!("{} is already the pyenv global version", env.name));
+                            },
+                            Some(env) if App::pyenv_version(env).is_some() => app.open(AppState::ConfirmPyenvGlobal),
+                            _ => {
+                                app.status_message = Some("Select a pyenv version to make it the global default".to_string());
+                            },
+                        }
+                    },
+                    _ => {}
+                },
+                AppState::ConfirmQuit => match key.code {
+                    KeyCode::Char('y' | 'q') => break,
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+                    KeyCode::Esc | KeyCode::Char('n') => {
+                        app.go_back();
+                    },
+                    _ => {}
+                },
+                AppState::ConfirmPyenvGlobal => match key.code {
+                    KeyCode::Char('y') => {
+                        app.reset_state();
+                        if let Some(version) = app.current_environment().and_then(App::pyenv_version) {
+                            app.status_message = Some(match set_pyenv_global(&version) {
+                                Ok(()) => {
+                                    app.push_log(&format!("pyenv global {}", version));
+                                    app.pyenv_global = Some(version.clone());
+                                    format!("pyenv global is now {}", version)
+                                },
+                                Err(e) => format!("Error switching the pyenv global version: {}", e),
+                            });
+                        }
+                    },
+                    KeyCode::Esc | KeyCode::Char('n') => {
+                        app.go_back();
+                    },
+                    _ => {}
+                },
+                AppState::ConfirmCondaExport => match key.code {
+                    KeyCode::Char(c @ ('y' | 'h')) => {
+                        app.reset_state();
+                        conda_export = spawn_conda_export(&mut app, c == 'h');
+                    },
+                    KeyCode::Esc | KeyCode::Char('n') => {
+                        app.go_back();
+                    },
+                    _ => {}
+                },
+                AppState::HelpMenu => match key.code {
+                    KeyCode::Esc | KeyCode::Char('x') => {
+                        app.go_back();
+                    },
+                    _ => {}
+                },
+                AppState::CreateEnvironment => match key.code {
+                    KeyCode::Esc => {
+                        app.go_back();
+                    },
+                    KeyCode::Enter if !app.input_text.is_empty() => {
+                        if env_creation.is_some() || batch_creation.is_some() {
+                            app.status_message = Some("Wait for the environment being created to finish first".to_string());
+                        } else if app.input_text.contains(',') {
+                            batch_creation = BatchCreation::spawn(&mut app);
+                        } else {
+                            match environment_dir(&app.input_text) {
+                                Err(e) => app.status_message = Some(e.to_string()),
+                                Ok(venv_dir) if venv_dir.exists() && app.confirmed_overwrite.as_ref() != Some(&venv_dir) => {
+                                    app.open(AppState::ConfirmOverwrite);
+                                },
+                                Ok(_) => env_creation = continue_create(&mut app),
                             }
-                            app.state = AppState::Normal;
-                            app.dialog_state = DialogState::None;
-                        },
-                        KeyCode::Char('n') => {
-                            app.state = AppState::Normal;
-                            app.dialog_state = DialogState::None;
-                        },
-                        _ => {}
+                        }
                     },
-                    AppState::InstallPackage => match key.code {
-                        KeyCode::Esc => {
-                            app.state = AppState::Normal;
-                        },
-                        KeyCode::Enter => {
-                            if !app.input_text.is_empty() && app.selected_environment.is_some() {
-                                let idx = app.selected_environment.unwrap();
-                                let env_path = &app.environments[idx].path;
-                                match install_package(env_path, &app.input_text) {
-                                    Ok(_) => {
-                                        match list_packages(env_path) {
+                    KeyCode::Up | KeyCode::Down => {
+                        app.editing_python = !app.editing_python;
+                    },
+                    KeyCode::Char(c) if app.editing_python => {
+                        app.python_override.push(c);
+                    },
+                    KeyCode::Backspace if app.editing_python => {
+                        app.python_override.pop();
+                    },
+                    // The interpreter field is append-only; cursor keys only edit the name
+                    KeyCode::Delete | KeyCode::Left | KeyCode::Right | KeyCode::Home | KeyCode::End
+                        if app.editing_python => {},
+                    KeyCode::Char(c) => app.input_insert(c),
+                    KeyCode::Backspace => app.input_backspace(),
+                    KeyCode::Delete => app.input_delete(),
+                    KeyCode::Left => app.input_left(),
+                    KeyCode::Right => app.input_right(),
+                    KeyCode::Home => app.input_home(),
+                    KeyCode::End => app.input_end(),
+                    _ => {}
+                },
+                AppState::ConfirmOverwrite => match key.code {
+                    KeyCode::Char('y') => {
+                        app.confirmed_overwrite = environment_dir(&app.input_text).ok();
+                        app.go_back();
+                        env_creation = continue_create(&mut app);
+                    },
+                    // Back to the create dialog with the name preserved
+                    KeyCode::Esc | KeyCode::Char('n') => {
+                        app.go_back();
+                    },
+                    _ => {}
+                },
+                AppState::PickVersion => match key.code {
+                    KeyCode::Esc => app.go_back(),
+                    KeyCode::Up => app.selected_version = app.selected_version.saturating_sub(1),
+                    KeyCode::Down => {
+                        app.selected_version = (app.selected_version + 1).min(app.available_versions.len().saturating_sub(1));
+                    },
+                    KeyCode::Enter => {
+                        app.reset_state();
+                        install_version(&mut app);
+                    },
+                    _ => {}
+                },
+                AppState::PickTemplate => match key.code {
+                    // Back to the create dialog with the name preserved
+                    KeyCode::Esc => {
+                        app.go_back();
+                    },
+                    KeyCode::Up => app.selected_template = app.selected_template.saturating_sub(1),
+                    KeyCode::Down => app.selected_template = (app.selected_template + 1).min(app.templates.len()),
+                    KeyCode::Enter => {
+                        let template = app.selected_template
+                            .checked_sub(1)
+                            .and_then(|idx| app.templates.get(idx))
+                            .cloned();
+                        if template.is_some() && bulk_install.is_some() {
+                            app.status_message = Some("Wait for the running installs to finish first".to_string());
+                        } else {
+                            // Failures found up front leave the create dialog open; the
+                            // template is installed once the environment exists
+                            app.go_back();
+                            env_creation = run_create(&mut app, template);
+                        }
+                    },
+                    _ => {}
+                },
+                AppState::DeleteEnvironment => match key.code {
+                    KeyCode::Esc => {
+                        app.go_back();
+                        app.dialog_state = DialogState::None;
+                    },
+                    KeyCode::Char('y') => {
+                        if let Some(idx) = app.selected_environment
+                            && let Some(env) = app.current_environment() {
+                            let env_path = env.path.clone();
+                            let env_name = env.name.clone();
+                            let result = delete_environment(&env_path);
+                            record_history(&mut app, HistoryEntry::new(
+                                Operation::Delete, env_path.display().to_string(), None, None, result.as_ref().err().map(ToString::to_string),
+                            ));
+                            match result {
+                                Ok(_) => {
+                                    app.remove_environment(idx);
+                                    if let Some(env) = app.current_environment() {
+                                        match list_packages(&env.path) {
                                             Ok(pkgs) => {
-                                                app.packages = pkgs;
-                                                if !app.packages.is_empty() {
-                                                    app.selected_package = Some(0);
-                                                }
+                                                app.set_packages(pkgs);
                                             },
                                             Err(e) => {
                                                 app.status_message = Some(format!("Error listing packages: {}", e));
                                             }
                                         }
-                                        app.status_message = Some(format!("Package '{}' installed successfully", app.input_text));
-                                    },
-                                    Err(e) => {
-                                        app.status_message = Some(format!("Error installing package: {}", e));
                                     }
+                                    app.status_message = Some(format!("Environment '{}' deleted successfully", env_name));
+                                },
+                                Err(e) => {
+                                    app.status_message = Some(format!("Error deleting environment: {}", e));
                                 }
                             }
-                            app.state = AppState::Normal;
-                        },
-                        KeyCode::Char(c) => {
-                            app.input_text.push(c);
-                        },
-                        KeyCode::Backspace => {
-                            app.input_text.pop();
-                        },
-                        _ => {}
+                        }
+                        app.reset_state();
+                        app.dialog_state = DialogState::None;
                     },
-                    AppState::UninstallPackage => match key.code {
-                        KeyCode::Esc => {
-                            app.state = AppState::Normal;
-                            app.dialog_state = DialogState::None;
-                        },
-                        KeyCode::Char('y') => {
-                            if let Some(env_idx) = app.selected_environment {
-                                if let Some(pkg_idx) = app.selected_package {
-                                    if pkg_idx < app.packages.len() {
-                                        let env_path = &app.environments[env_idx].path;
-                                        let pkg_name = app.packages[pkg_idx].name.clone();
-                                        match uninstall_package(env_path, &pkg_name) {
-                                            Ok(_) => {
-                                                match list_packages(env_path) {
-                                                    Ok(pkgs) => {
-                                                        app.packages = pkgs;
-                                                        app.selected_package = Some(pkg_idx.min(app.packages.len().saturating_sub(1)));
-                                                    },
-                                                    Err(e) => {
-                                                        app.status_message = Some(format!("Error listing packages: {}", e));
-                                                    }
-                                                }
-                                                app.status_message = Some(format!("Package '{}' uninstalled successfully", pkg_name));
-                                            },
-                                            Err(e) => {
-                                                app.status_message = Some(format!("Error uninstalling package: {}", e));
-                                            }
+                    KeyCode::Char('n') => {
+                        app.go_back();
+                        app.dialog_state = DialogState::None;
+                    },
+                    _ => {}
+                },
+                AppState::InstallPackage => match key.code {
+                    KeyCode::Esc => {
+                        app.go_back();
+                    },
+                    KeyCode::Enter => {
+                        if !app.show_global_packages && app.installed_package(&app.input_text).is_some() {
+                            app.open(AppState::ConfirmUpgrade);
+                        } else {
+                            app.reset_state();
+                            run_install(&mut app, false);
+                        }
+                    },
+                    KeyCode::Tab => {
+                        if !app.complete_package_name()
+                            && !app.input_text.is_empty()
+                            && let Some(preview) = spawn_install_preview(&mut app) {
+                            install_preview = Some(preview);
+                        }
+                    },
+                    KeyCode::Up | KeyCode::Down => {
+                        app.editing_index = !app.editing_index;
+                    },
+                    KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.install_pre = !app.install_pre;
+                    },
+                    KeyCode::Char(c) if app.editing_index => {
+                        app.index_override.push(c);
+                    },
+                    KeyCode::Backspace if app.editing_index => {
+                        app.index_override.pop();
+                    },
+                    // The index field is append-only; cursor keys only edit the package name
+                    KeyCode::Delete | KeyCode::Left | KeyCode::Right | KeyCode::Home | KeyCode::End
+                        if app.editing_index => {},
+                    KeyCode::Char(c) => app.input_insert(c),
+                    KeyCode::Backspace => app.input_backspace(),
+                    KeyCode::Delete => app.input_delete(),
+                    KeyCode::Left => app.input_left(),
+                    KeyCode::Right => app.input_right(),
+                    KeyCode::Home => app.input_home(),
+                    KeyCode::End => app.input_end(),
+                    _ => {}
+                },
+                AppState::InstallPreview => match key.code {
+                    KeyCode::Char('y') => {
+                        app.reset_state();
+                        run_install(&mut app, false);
+                    },
+                    // Back to the install dialog with the name preserved
+                    KeyCode::Esc | KeyCode::Char('n') => {
+                        app.go_back();
+                    },
+                    _ => {}
+                },
+                AppState::UninstallPackage => match key.code {
+                    KeyCode::Esc => {
+                        app.go_back();
+                        app.dialog_state = DialogState::None;
+                    },
+                    KeyCode::Char('y') => {
+                        if let Some(env) = app.current_environment()
+                            && let Some(pkg_idx) = app.selected_package
+                            && pkg_idx < app.packages.len() {
+                            let env_path = env.path.clone();
+                            let pkg_name = app.packages[pkg_idx].name.clone();
+                            let pkg_version = app.packages[pkg_idx].version.clone();
+                            // pip can't remove what conda installed, and shouldn't try
+                            let result = match app.packages[pkg_idx].manager {
+                                PackageManager::Conda => conda_remove_package(&env_path, &pkg_name),
+                                PackageManager::Pip => uninstall_package(&env_path, &pkg_name, &app.install_options),
+                            };
+                            record_history(&mut app, HistoryEntry::new(
+                                Operation::Uninstall, env_path.display().to_string(), Some(&pkg_name), Some(&pkg_version),
+                                result.as_ref().err().map(ToString::to_string),
+                            ));
+                            match result {
+                                Ok(_) => {
+                                    match list_packages(&env_path) {
+                                        Ok(pkgs) => {
+                                            app.set_packages(pkgs);
+                                            // Keep the selection where the removed package was
+                                            app.selected_package = Some(pkg_idx.min(app.packages.len().saturating_sub(1)));
+                                            app.clamp_package_selection();
+                                        },
+                                        Err(e) => {
+                                            app.status_message = Some(format!("Error listing packages: {}", e));
                                         }
                                     }
+                                    app.status_message = Some(format!("Package '{}' uninstalled successfully", pkg_name));
+                                },
+                                Err(e) => {
+                                    app.status_message = Some(format!("Error uninstalling package: {}", e));
                                 }
                             }
-                            app.state = AppState::Normal;
-                            app.dialog_state = DialogState::None;
-                        },
-                        KeyCode::Char('n') => {
-                            app.state = AppState::Normal;
-                            app.dialog_state = DialogState::None;
-                        },
-                        _ => {}
+                        }
+                        app.reset_state();
+                        app.dialog_state = DialogState::None;
                     },
-                    AppState::SearchEnvironment => match key.code {
-                        KeyCode::Esc => {
-                            app.state = AppState::Normal;
-                        },
-                        KeyCode::Enter => {
-                            if !app.input_text.is_empty() {
-                                let search_term = app.input_text.to_lowercase();
-                                let filtered_envs = app.environments.iter().enumerate()
-                                    .filter(|(_, env)| env.name.to_lowercase().contains(&search_term) || 
-                                                      env.path.to_string_lossy().to_lowercase().contains(&search_term))
-                                    .map(|(idx, _)| idx)
-                                    .collect::<Vec<_>>();
-                                
-                                if !filtered_envs.is_empty() {
-                                    app.selected_environment = Some(filtered_envs[0]);
-                                    match list_packages(&app.environments[filtered_envs[0]].path) {
+                    KeyCode::Char('n') => {
+                        app.go_back();
+                        app.dialog_state = DialogState::None;
+                    },
+                    _ => {}
+                },
+                AppState::ReinstallPackage => match key.code {
+                    KeyCode::Esc => {
+                        app.go_back();
+                        app.dialog_state = DialogState::None;
+                    },
+                    KeyCode::Char('y') => {
+                        if let Some(env) = app.current_environment()
+                            && let Some(pkg_idx) = app.selected_package
+                            && pkg_idx < app.packages.len() {
+                            let env_path = env.path.clone();
+                            let pkg_name = app.packages[pkg_idx].name.clone();
+                            let pkg_version = app.packages[pkg_idx].version.clone();
+                            let result = reinstall_package(&env_path, &pkg_name, &app.install_options);
+                            record_history(&mut app, HistoryEntry::new(
+                                Operation::Reinstall, env_path.display().to_string(), Some(&pkg_name), Some(&pkg_version),
+                                result.as_ref().err().map(ToString::to_string),
+                            ));
+                            match result {
+                                Ok(_) => {
+                                    match list_packages(&env_path) {
                                         Ok(pkgs) => {
-                                            app.packages = pkgs;
-                                            if !app.packages.is_empty() {
-                                                app.selected_package = Some(0);
+                                            app.set_packages(pkgs);
+                                            if !app.select_package(&pkg_name) {
+                                                app.selected_package = Some(pkg_idx.min(app.packages.len().saturating_sub(1)));
                                             }
+                                            app.clamp_package_selection();
                                         },
                                         Err(e) => {
                                             app.status_message = Some(format!("Error listing packages: {}", e));
                                         }
                                     }
-                                    app.status_message = Some(format!("Found {} matching environments", filtered_envs.len()));
-                                } else {
-                                    app.status_message = Some("No matching environments found".to_string());
+                                    app.status_message = Some(format!("Package '{}' reinstalled successfully", pkg_name));
+                                },
+                                Err(e) => {
+                                    app.status_message = Some(format!("Error reinstalling package: {}", e));
                                 }
                             }
-                            app.state = AppState::Normal;
-                        },
-                        KeyCode::Char(c) => {
-                            app.input_text.push(c);
-                        },
-                        KeyCode::Backspace => {
-                            app.input_text.pop();
-                        },
-                        _ => {}
+                        }
+                        app.reset_state();
+                        app.dialog_state = DialogState::None;
                     },
-                }
-            }
-        }
-
-        if last_tick.elapsed() >= tick_rate {
-            last_tick = std::time::Instant::now();
-            
-            // Clear status message after a delay
-            if let Some(_) = &app.status_message {
-                app.status_message_timer += 1;
-                if app.status_message_timer > 20 { // ~2 seconds with 100ms tick rate
-                    app.status_message = None;
-                    app.status_message_timer = 0;
-                }
-            }
-        }
-    }
-
-    // Restore terminal
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
-
-    Ok(())
+                    KeyCode::Char('n') => {
+                        app.go_back();
+                        app.dialog_state = DialogState::None;
+                    },
+                    _ => {}
+                },
+                AppState::ConfirmUpgrade => match key.code {
+                    KeyCode::Char('y') => {
+                        app.install_upgrade = true;
+                        app.reset_state();
+                        run_install(&mut app, false);
+                    },
+                    // Back to the install dialog with the name preserved
+                    KeyCode::Esc | KeyCode::Char('n') => {
+                        app.go_back();
+                    },
+                    _ => {}
+                },
+                AppState::ConfirmUpgradeAll => match key.code {
+                    KeyCode::Char('y') => {
+                        app.reset_state();
+                        if let Some(env) = app.current_environment() {
+                            let packages = app.upgradable_packages().iter().map(|(pkg, _)| pkg.name.clone()).collect();
+                            let options = InstallOptions {
+                                upgrade: true,
+                                ..app.install_options.clone()
+                            };
+                            bulk_install = Some(BulkInstall::spawn("Upgrade".to_string(), env.path.clone(), packages, options, true));
+                        }
+                    },
+                    KeyCode::Esc | KeyCode::Char('n') => {
+                        app.go_back();
+                    },
+                    _ => {}
+                },
+                AppState::ConfirmBreakSystem => match key.code {
+                    KeyCode::Char('y') => {
+                        app.reset_state();
+                        run_install(&mut app, true);
+                    },
+                    KeyCode::Esc | KeyCode::Char('n') => {
+                        app.go_back();
+                        app.status_message = Some("Install cancelled; press n to create a virtual environment".to_string());
+                    },
+                    _ => {}
+                },
+                AppState::ConfirmEnsurepip => match key.code {
+                    KeyCode::Char('y') => {
+                        app.reset_state();
+                        if let Some(env_path) = app.current_environment().map(|env| env.path.clone()) {
+                            match bootstrap_pip(&env_path) {
+                                Ok(()) => {
+                                    app.pipless.remove(&env_path);
+                                    app.pip_info.remove(&env_path);
+                                    app.push_log(&format!("bootstrapped pip into {} with ensurepip", env_path.display()));
+                                    run_install(&mut app, false);
+                                },
+                                Err(e) => {
+                                    app.status_message = Some(format!("Error installing pip: {}", e));
+                                },
+                            }
+                        }
+                    },
+                    KeyCode::Esc | KeyCode::Char('n') => {
+                        app.go_back();
+                        app.status_message = Some("Install cancelled; this environment has no pip".to_string());
+                    },
+                    _ => {}
+                },
+                AppState::CleanupBroken => match key.code {
+                    KeyCode::Char('y') if !app.cleanup_candidates.is_empty() => {
+                        let mut deleted = 0;
+                        let mut errors = Vec::new();
+                        for path in std::mem::take(&mut app.cleanup_candidates) {
+                            let result = delete_environment(&path);
+                            record_history(&mut app, HistoryEntry::new(
+                                Operation::Delete, path.display().to_string(), None, None, result.as_ref().err().map(ToString::to_string),
+                            ));
+                            match result {
+                                Ok(_) => {
+                                    app.remove_environment_path(&path);
+                                    deleted += 1;
+                                },
+                                Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+                            }
+                        }
+                        app.status_message = Some(if errors.is_empty() {
+                            format!("Deleted {} broken environment(s)", deleted)
+                        } else {
+                            format!("Deleted {} broken environment(s); failed: {}", deleted, errors.join(", "))
+                        });
+                        app.reset_state();
+                    },
+                    KeyCode::Char('r') if !app.cleanup_candidates.is_empty() => {
+                        let mut repaired = 0;
+                        let mut errors = Vec::new();
+                        for path in std::mem::take(&mut app.cleanup_candidates) {
+                            let result = repair_venv(&path);
+                            record_history(&mut app, HistoryEntry::new(
+                                Operation::Repair, path.display().to_string(), None, None, result.as_ref().err().map(ToString::to_string),
+                            ));
+                            match result {
+                                Ok(env) => {
+                                    app.remove_environment_path(&path);
+                                    app.add_environment(env);
+                                    repaired += 1;
+                                },
+                                Err(e) => {
+                                    app.push_log(&format!("repair {}: {}", path.display(), e));
+                                    errors.push(path.display().to_string());
+                                }
+                            }
+                        }
+                        app.status_message = Some(if errors.is_empty() {
+                            format!("Repaired {} environment(s)", repaired)
+                        } else {
+                            format!("Repaired {} environment(s); still broken: {} (l: log)", repaired, errors.join(", "))
+                        });
+                        app.reset_state();
+                    },
+                    KeyCode::Esc | KeyCode::Char('n') => {
+                        app.go_back();
+                    },
+                    _ => {}
+                },
+                AppState::FilterPackages => match key.code {
+                    KeyCode::Esc => {
+                        app.package_filter.clear();
+                        app.clamp_package_selection();
+                        app.go_back();
+                    },
+                    KeyCode::Enter => {
+                        app.go_back();
+                    },
+                    KeyCode::Down => app.next_package(),
+                    KeyCode::Up => app.previous_package(),
+                    KeyCode::Char(c) => {
+                        app.package_filter.push(c);
+                        app.clamp_package_selection();
+                    },
+                    KeyCode::Backspace => {
+                        app.package_filter.pop();
+                        app.clamp_package_selection();
+                    },
+                    _ => {}
+                },
+                AppState::PipCommand => match key.code {
+                    KeyCode::Esc => {
+                        app.go_back();
+                    },
+                    KeyCode::Enter => run_pip_command_line(&mut app),
+                    KeyCode::Char(c) => app.input_insert(c),
+                    KeyCode::Backspace => app.input_backspace(),
+                    KeyCode::Delete => app.input_delete(),
+                    KeyCode::Left => app.input_left(),
+                    KeyCode::Right => app.input_right(),
+                    KeyCode::Home => app.input_home(),
+                    KeyCode::End => app.input_end(),
+                    _ => {}
+                },
+                AppState::CompareRequirements => match key.code {
+                    KeyCode::Esc => {
+                        app.go_back();
+                    },
+                    KeyCode::Enter if !app.input_text.trim().is_empty() => compare_requirements(&mut app),
+                    KeyCode::Char(c) => app.input_insert(c),
+                    KeyCode::Backspace => app.input_backspace(),
+                    KeyCode::Delete => app.input_delete(),
+                    KeyCode::Left => app.input_left(),
+                    KeyCode::Right => app.input_right(),
+                    KeyCode::Home => app.input_home(),
+                    KeyCode::End => app.input_end(),
+                    _ => {}
+                },
+                AppState::RequirementsDiffView => match key.code {
+                    KeyCode::Esc | KeyCode::Char('=') => {
+                        app.go_back();
+                    },
+                    KeyCode::Up => app.requirements_diff_scroll = app.requirements_diff_scroll.saturating_sub(1),
+                    KeyCode::Down => app.requirements_diff_scroll = app.requirements_diff_scroll.saturating_add(1),
+                    KeyCode::PageUp => app.requirements_diff_scroll = app.requirements_diff_scroll.saturating_sub(10),
+                    KeyCode::PageDown => app.requirements_diff_scroll = app.requirements_diff_scroll.saturating_add(10),
+                    KeyCode::Home => app.requirements_diff_scroll = 0,
+                    _ => {}
+                },
+                AppState::PackageDetails => match key.code {
+                    KeyCode::Esc | KeyCode::Enter => {
+                        app.go_back();
+                    },
+                    KeyCode::Char('f') => {
+                        app.files_scroll = 0;
+                        app.open(AppState::PackageFiles);
+                    },
+                    KeyCode::Up => app.metadata_scroll = app.metadata_scroll.saturating_sub(1),
+                    KeyCode::Down => app.metadata_scroll = app.metadata_scroll.saturating_add(1),
+                    KeyCode::PageUp => app.metadata_scroll = app.metadata_scroll.saturating_sub(10),
+                    KeyCode::PageDown => app.metadata_scroll = app.metadata_scroll.saturating_add(10),
+                    KeyCode::Home => app.metadata_scroll = 0,
+                    _ => {}
+                },
+                AppState::PackageFiles => match key.code {
+                    KeyCode::Esc | KeyCode::Char('f') => {
+                        app.go_back();
+                    },
+                    KeyCode::Up => app.files_scroll = app.files_scroll.saturating_sub(1),
+                    KeyCode::Down => app.files_scroll = app.files_scroll.saturating_add(1),
+                    KeyCode::PageUp => app.files_scroll = app.files_scroll.saturating_sub(10),
+                    KeyCode::PageDown => app.files_scroll = app.files_scroll.saturating_add(10),
+                    KeyCode::Home => app.files_scroll = 0,
+                    _ => {}
+                },
+                AppState::DiskUsage => match key.code {
+                    KeyCode::Esc | KeyCode::Char('D') => {
+                        app.go_back();
+                    },
+                    KeyCode::Up => app.disk_usage_scroll = app.disk_usage_scroll.saturating_sub(1),
+                    KeyCode::Down => app.disk_usage_scroll = app.disk_usage_scroll.saturating_add(1).min(app.disk_usage.len().saturating_sub(1) as u16),
+                    KeyCode::PageUp => app.disk_usage_scroll = app.disk_usage_scroll.saturating_sub(10),
+                    KeyCode::PageDown => app.disk_usage_scroll = app.disk_usage_scroll.saturating_add(10).min(app.disk_usage.len().saturating_sub(1) as u16),
+                    KeyCode::Home => app.disk_usage_scroll = 0,
+                    KeyCode::End => app.disk_usage_scroll = app.disk_usage.len().saturating_sub(1) as u16,
+                    _ => {}
+                },
+                AppState::DotenvView => match key.code {
+                    KeyCode::Esc | KeyCode::Char('E') => {
+                        app.go_back();
+                    },
+                    KeyCode::Up => app.dotenv_scroll = app.dotenv_scroll.saturating_sub(1),
+                    KeyCode::Down => app.dotenv_scroll = app.dotenv_scroll.saturating_add(1).min(app.dotenv_vars.len().saturating_sub(1) as u16),
+                    KeyCode::PageUp => app.dotenv_scroll = app.dotenv_scroll.saturating_sub(10),
+                    KeyCode::PageDown => app.dotenv_scroll = app.dotenv_scroll.saturating_add(10).min(app.dotenv_vars.len().saturating_sub(1) as u16),
+                    KeyCode::Home => app.dotenv_scroll = 0,
+                    KeyCode::End => app.dotenv_scroll = app.dotenv_vars.len().saturating_sub(1) as u16,
+                    _ => {}
+                },
+                AppState::SysPathView => match key.code {
+                    KeyCode::Esc | KeyCode::Char('P') => {
+                        app.go_back();
+                    },
+                    KeyCode::Up => app.sys_path_scroll = app.sys_path_scroll.saturating_sub(1),
+                    KeyCode::Down => app.sys_path_scroll = app.sys_path_scroll.saturating_add(1).min(app.sys_path.len().saturating_sub(1) as u16),
+                    KeyCode::PageUp => app.sys_path_scroll = app.sys_path_scroll.saturating_sub(10),
+                    KeyCode::PageDown => app.sys_path_scroll = app.sys_path_scroll.saturating_add(10).min(app.sys_path.len().saturating_sub(1) as u16),
+                    KeyCode::Home => app.sys_path_scroll = 0,
+                    KeyCode::End => app.sys_path_scroll = app.sys_path.len().saturating_sub(1) as u16,
+                    _ => {}
+                },
+                AppState::KernelView => match key.code {
+                    KeyCode::Esc | KeyCode::Char('J') => {
+                        app.go_back();
+                    },
+                    KeyCode::Up => app.selected_kernel = app.selected_kernel.saturating_sub(1),
+                    KeyCode::Down => {
+                        let count = app.kernels.as_ref().map_or(0, Vec::len);
+                        app.selected_kernel = (app.selected_kernel + 1).min(count.saturating_sub(1));
+                    },
+                    KeyCode::Char('a' | 'd') if app.read_only => app.refuse_read_only(),
+                    KeyCode::Char('a') => register_kernel(&mut app),
+                    KeyCode::Char('d') if app.kernels.as_ref().is_some_and(|kernels| !kernels.is_empty()) => {
+                        app.open(AppState::ConfirmRemoveKernel);
+                    },
+                    KeyCode::Char('r') => {
+                        load_kernels(&mut app);
+                    },
+                    _ => {}
+                },
+                AppState::ConfirmRemoveKernel => match key.code {
+                    KeyCode::Char('y') => {
+                        app.go_back();
+                        let name = app.kernels.as_ref()
+                            .and_then(|kernels| kernels.get(app.selected_kernel))
+                            .map(|kernel| kernel.name.clone());
+                        if let Some(name) = name {
+                            match jupyter::remove_kernel(&name) {
+                                Ok(()) => {
+                                    app.status_message = Some(format!("Removed kernel '{}'", name));
+                                    load_kernels(&mut app);
+                                },
+                                Err(e) => {
+                                    app.status_message = Some(format!("Error removing kernel: {}", e));
+                                    app.push_log(&format!("removing kernel {} failed: {}", name, e));
+                                }
+                            }
+                        }
+                    },
+                    KeyCode::Esc | KeyCode::Char('n') => {
+                        app.go_back();
+                    },
+                    _ => {}
+                },
+                AppState::HistoryView => match key.code {
+                    KeyCode::Esc | KeyCode::Char('H') => {
+                        app.go_back();
+                    },
+                    KeyCode::Up => app.history_scroll = app.history_scroll.saturating_sub(1),
+                    KeyCode::Down => app.history_scroll = app.history_scroll.saturating_add(1).min(app.history.len().saturating_sub(1) as u16),
+                    KeyCode::PageUp => app.history_scroll = app.history_scroll.saturating_sub(10),
+                    KeyCode::PageDown => app.history_scroll = app.history_scroll.saturating_add(10).min(app.history.len().saturating_sub(1) as u16),
+                    KeyCode::Home => app.history_scroll = 0,
+                    KeyCode::End => app.history_scroll = app.history.len().saturating_sub(1) as u16,
+                    _ => {}
+                },
+                AppState::LogView => match key.code {
+                    KeyCode::Esc | KeyCode::Char('l') => {
+                        app.go_back();
+                    },
+                    KeyCode::Up => app.log_scroll = app.log_scroll.saturating_sub(1),
+                    KeyCode::Down => app.log_scroll = app.log_scroll.saturating_add(1).min(app.log.len().saturating_sub(1) as u16),
+                    KeyCode::PageUp => app.log_scroll = app.log_scroll.saturating_sub(10),
+                    KeyCode::PageDown => app.log_scroll = app.log_scroll.saturating_add(10).min(app.log.len().saturating_sub(1) as u16),
+                    KeyCode::Home => app.log_scroll = 0,
+                    KeyCode::End => app.log_scroll = app.log.len().saturating_sub(1) as u16,
+                    _ => {}
+                },
+                AppState::SearchEnvironment => match key.code {
+                    KeyCode::Esc => {
+                        app.go_back();
+                    },
+                    KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.search_case_sensitive = !app.search_case_sensitive;
+                    },
+                    KeyCode::Enter => {
+                        if !app.input_text.is_empty() {
+                            let filtered_envs = app.search_environments(&app.input_text);
+                            
+                            if !filtered_envs.is_empty() {
+                                app.selected_environment = Some(filtered_envs[0]);
+                                package_listing = spawn_package_listing(&mut app);
+                                if filtered_envs.len() == 1 {
+                                    app.status_message = Some("Found 1 matching environment".to_string());
+                                }
+                                // More than one: the status bar shows the position until the search is cleared
+                                app.search_query = app.input_text.clone();
+                                app.search_match = 0;
+                                app.search_matches = if filtered_envs.len() > 1 { filtered_envs } else { Vec::new() };
+                            } else {
+                                app.search_matches.clear();
+                                app.status_message = Some("No matching environments found".to_string());
+                            }
+                        }
+                        app.reset_state();
+                    },
+                    KeyCode::Char(c) => app.input_insert(c),
+                    KeyCode::Backspace => app.input_backspace(),
+                    KeyCode::Delete => app.input_delete(),
+                    KeyCode::Left => app.input_left(),
+                    KeyCode::Right => app.input_right(),
+                    KeyCode::Home => app.input_home(),
+                    KeyCode::End => app.input_end(),
+                    _ => {}
+                },
+            }
+            
+            // A newly selected package's details start at the top
+            if app.selected_package != selected_package {
+                app.details_scroll = 0;
+            }
+        }
+
+        if last_tick.elapsed() >= tick_rate {
+            last_tick = std::time::Instant::now();
+            
+            // Type-ahead starts over after a second without typing
+            if !app.type_ahead.is_empty() {
+                app.type_ahead_idle = app.type_ahead_idle.saturating_add(1);
+                if app.type_ahead_idle > type_ahead_ticks {
+                    app.type_ahead.clear();
+                    dirty = true;
+                }
+            }
+            
+            // Clear status message after a delay
+            if app.status_message.is_some() {
+                app.status_message_timer = app.status_message_timer.saturating_add(1);
+                if app.status_message_timer > status_message_ticks {
+                    app.status_message = None;
+                    app.status_message_timer = 0;
+                    dirty = true;
+                }
+            }
+            
+            // Progress of running background work, e.g. bulk installs, shows up on ticks
+            if bulk_install.is_some() {
+                dirty = true;
+            }
+            if app.creating.is_some() || app.working.is_some() {
+                app.spinner_frame = app.spinner_frame.wrapping_add(1);
+                dirty = true;
+            }
+        }
+    }
+
+    // Restore terminal
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+    terminal.show_cursor()?;
+
+    config.sidebar_percent = app.sidebar_percent;
+    config.pinned_environments = app.pinned.clone();
+    config.hide_system_environments = app.hide_system_environments;
+    config.group_environments = app.group_environments;
+    if let Some(path) = &config_path
+        && let Err(e) = config.save(path) {
+        eprintln!("Error saving config: {}", e);
+    }
+    if let Err(e) = app.usage.save() {
+        eprintln!("Error saving usage data: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Suspend the TUI and run the user's shell with `env` activated, returning
+/// to the TUI when the shell exits.
+fn open_shell(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, env: &PythonEnvironment) -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
+    terminal.show_cursor()?;
+    
+    let shell = std::env::var_os("SHELL")
+        .or_else(|| cfg!(windows).then(|| std::env::var_os("COMSPEC")).flatten())
+        .unwrap_or_else(|| if cfg!(windows) { "cmd.exe".into() } else { "/bin/sh".into() });
+    println!("LazyEnv: shell with {} activated; type 'exit' to return.", env.name);
+    let result = std::process::Command::new(&shell)
+        .envs(activation_vars(env))
+        .env_remove("PYTHONHOME")
+        .status();
+    
+    // Restore the TUI even if the shell couldn't be started
+    enable_raw_mode()?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture)?;
+    terminal.clear()?;
+    
+    result.map(|_| ())
+}
+
+/// Put the terminal back to normal before a panic message is printed, so a
+/// crash doesn't leave the shell in raw mode on the alternate screen.
+fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture);
+        default_hook(info);
+    }));
+}
+
+/// Print the detected environments as a table, for `--list`.
+fn print_environments() -> io::Result<()> {
+    let (environments, warnings) = list_environments()?;
+    for warning in &warnings {
+        eprintln!("warning: {}", warning);
+    }
+    
+    let name_width = environments.iter().map(|env| env.name.len()).max().unwrap_or(0).max(4);
+    let type_width = environments.iter().map(|env| env.env_type.len()).max().unwrap_or(0).max(4);
+    let version_width = environments.iter().map(|env| env.python_version.len()).max().unwrap_or(0).max(7);
+    
+    println!("{:name_width$}  {:type_width$}  {:version_width$}  PATH", "NAME", "TYPE", "VERSION");
+    for env in &environments {
+        println!(
+            "{:name_width$}  {:type_width$}  {:version_width$}  {}",
+            env.name,
+            env.env_type,
+            env.python_version,
+            env.path.display(),
+        );
+    }
+    
+    Ok(())
+}
+
+/// `lazyenv install <environment> <package>`: install into the environment
+/// whose name matches case-insensitively, with the same options as the TUI.
+fn install_from_cli(config: &Config, environment: &str, package: &str) -> io::Result<()> {
+    let (environments, _) = list_environments()?;
+    
+    // "myenv" also matches prefixed names like "conda: myenv"
+    let wanted = environment.to_lowercase();
+    let matches_name = |name: &str| {
+        let name = name.to_lowercase();
+        name == wanted || name.split_once(": ").is_some_and(|(_, short)| short == wanted)
+    };
+    let matches: Vec<&PythonEnvironment> = environments.iter().filter(|env| matches_name(&env.name)).collect();
+    
+    let env = match matches[..] {
+        [env] => env,
+        [] => return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no environment named '{}' (see lazyenv --list)", environment),
+        )),
+        _ => {
+            let names: Vec<String> = matches.iter().map(|env| format!("{} ({})", env.name, env.path.display())).collect();
+            return Err(io::Error::other(
+                format!("'{}' matches several environments: {}", environment, names.join(", ")),
+            ));
+        }
+    };
+    
+    let result = install_package(&env.path, package, &config.install_options());
+    let entry = HistoryEntry::new(
+        Operation::Install, env.path.display().to_string(), Some(package), None, result.as_ref().err().map(ToString::to_string),
+    );
+    if let Err(e) = history::append(&entry) {
+        eprintln!("lazyenv: couldn't record history: {}", e);
+    }
+    result?;
+    println!("Package '{}' installed into {}", package, env.name);
+    
+    Ok(())
+}
+
+/// Progress reported by a `BulkInstall` thread.
+enum BulkProgress {
+    /// `output` gets pip's output for this package, line by line
+    Started { package: String, position: usize, output: Receiver<String> },
+    Finished { package: String, result: Result<(), PythonError> },
+}
+
+/// Several packages being installed one at a time on a background thread,
+/// so the UI keeps running and can show which one is in progress.
+struct BulkInstall {
+    /// What's being installed, for status messages
+    label: String,
+    env_path: PathBuf,
+    /// Whether to check for outdated packages again once done
+    recheck_outdated: bool,
+    total: usize,
+    failed: Vec<String>,
+    progress: Receiver<BulkProgress>,
+    /// pip's output for the package being installed, for following which
+    /// of its dependencies it's on
+    output: Option<Receiver<String>>,
+}
+
+impl BulkInstall {
+    fn spawn(label: String, env_path: PathBuf, packages: Vec<String>, options: InstallOptions, recheck_outdated: bool) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let total = packages.len();
+        let path = env_path.clone();
+        
+        thread::spawn(move || {
+            make_cancellable();
+            for (idx, package) in packages.into_iter().enumerate() {
+                // A channel per package, so lines of the last one can't mark packages of the next
+                let (output_tx, output_rx) = mpsc::channel();
+                if tx.send(BulkProgress::Started { package: package.clone(), position: idx + 1, output: output_rx }).is_err() {
+                    return;
+                }
+                let result = install_package_with_progress(&path, &package, &options, &output_tx);
+                if tx.send(BulkProgress::Finished { package, result }).is_err() {
+                    return;
+                }
+            }
+        });
+        
+        Self { label, env_path, recheck_outdated, total, failed: Vec::new(), progress: rx, output: None }
+    }
+    
+    /// Apply any progress to the status bar, log and history. Returns true
+    /// once every package has been attempted.
+    fn poll(&mut self, app: &mut App) -> bool {
+        loop {
+            match self.progress.try_recv() {
+                Ok(BulkProgress::Started { package, position, output }) => {
+                    self.output = Some(output);
+                    app.installing = None;
+                    app.status_message = Some(format!("{}: installing {} ({}/{})…", self.label, package, position, self.total));
+                    app.status_message_timer = 0;
+                },
+                Ok(BulkProgress::Finished { package, result }) => {
+                    let entry = HistoryEntry::new(
+                        Operation::Install, self.env_path.display().to_string(), Some(&package), None,
+                        result.as_ref().err().map(ToString::to_string),
+                    );
+                    record_history(app, entry);
+                    if let Err(e) = result {
+                        app.push_log(&format!("{}: installing {} failed: {}", self.label, package, e));
+                        self.failed.push(package);
+                    }
+                },
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    app.installing = None;
+                    self.finish(app);
+                    return true;
+                },
+            }
+        }
+        
+        if let Some(output) = &self.output {
+            while let Ok(line) = output.try_recv() {
+                if let Some(packages) = pip_phase_packages(&line) {
+                    app.installing = Some((self.env_path.clone(), packages));
+                }
+            }
+        }
+        false
+    }
+    
+    /// Report the outcome once every package has been attempted.
+    fn finish(&self, app: &mut App) {
+        let installed = self.total - self.failed.len();
+        app.status_message = Some(if self.failed.is_empty() {
+            format!("{}: installed {} package(s)", self.label, installed)
+        } else {
+            format!("{}: installed {} of {}; failed: {} (l: log)", self.label, installed, self.total, self.failed.join(", "))
+        });
+        app.status_message_timer = 0;
+    }
+}
+
+/// Result of `spawn_outdated_check` for the environment at `path`.
+struct OutdatedCheck {
+    path: PathBuf,
+    /// The packages listed again first, when asked to after upgrading
+    packages: Option<Result<Vec<Package>, PythonError>>,
+    outdated: Result<HashMap<String, String>, PythonError>,
+}
+
+/// Check the environment at `env_path` for outdated packages on a background
+/// thread, since `pip list --outdated` waits on the index. With `relist` the
+/// packages are listed again first, e.g. after upgrading.
+fn spawn_outdated_check(env_path: PathBuf, include_pre: bool, relist: bool) -> Receiver<OutdatedCheck> {
+    let (tx, rx) = mpsc::channel();
+    
+    thread::spawn(move || {
+        make_cancellable();
+        let packages = relist.then(|| list_packages(&env_path));
+        let outdated = if let Some(Err(_)) = &packages {
+            // Not checked: the listing error is what gets reported
+            Err(PythonError::Cancelled)
+        } else {
+            list_outdated_packages(&env_path, include_pre)
+        };
+        let _ = tx.send(OutdatedCheck { path: env_path, packages, outdated });
+    });
+    
+    rx
+}
+
+/// Show the result of an outdated check, unless the user has since moved to
+/// another environment. A re-check after upgrading leaves the status bar
+/// alone unless something fails.
+fn apply_outdated_check(app: &mut App, check: OutdatedCheck) {
+    let still_selected = app.current_environment()
+        .is_some_and(|env| env.path == check.path);
+    if !still_selected || app.show_global_packages {
+        return;
+    }
+    let announce = check.packages.is_none();
+    if let Some(packages) = check.packages {
+        match packages {
+            Ok(pkgs) => app.set_packages(pkgs),
+            Err(e) => {
+                app.status_message = Some(format!("Error listing packages: {}", e));
+                return;
+            }
+        }
+    }
+    match check.outdated {
+        Ok(outdated) => {
+            if announce {
+                app.status_message = Some(format!("{} outdated package(s)", outdated.len()));
+            }
+            app.outdated = Some(outdated);
+        },
+        Err(e) => {
+            app.status_message = Some(format!("Error checking for outdated packages: {}", e));
+        }
+    }
+}
+
+/// Count packages for every environment on a background thread, sending
+/// results back as they're found so the list fills in without blocking.
+fn spawn_package_counts(environments: &[PythonEnvironment]) -> Receiver<(PathBuf, usize, Option<bool>)> {
+    let (tx, rx) = mpsc::channel();
+    let paths: Vec<PathBuf> = environments.iter().map(|env| env.path.clone()).collect();
+    
+    thread::spawn(move || {
+        for path in paths {
+            if let Some(count) = count_packages(&path)
+                && tx.send((path.clone(), count, has_pip(&path))).is_err() {
+                // The receiver was replaced by a newer pass
+                break;
+            }
+        }
+    });
+    
+    rx
+}
+
+/// Hand everything waiting on `rx` to `handle`. Returns `true` once the
+/// sender is gone, i.e. the background work has finished.
+fn drain<T>(rx: &Receiver<T>, mut handle: impl FnMut(T)) -> bool {
+    loop {
+        match rx.try_recv() {
+            Ok(value) => handle(value),
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Disconnected) => return true,
+        }
+    }
+}
+
+/// Measure each virtual environment's size on disk on a background thread,
+/// sending results back as they're found.
+fn spawn_disk_usage_scan(venvs: Vec<(String, PathBuf)>) -> Receiver<(String, PathBuf, u64)> {
+    let (tx, rx) = mpsc::channel();
+    
+    thread::spawn(move || {
+        for (name, path) in venvs {
+            let size = directory_size(&path);
+            if tx.send((name, path, size)).is_err() {
+                break;
+            }
+        }
+    });
+    
+    rx
+}
+
+/// Re-probe environments whose interpreter was too slow to answer during
+/// detection, sending back versions as they come in.
+fn spawn_version_probes(environments: &[PythonEnvironment]) -> Receiver<(PathBuf, String)> {
+    let (tx, rx) = mpsc::channel();
+    let paths: Vec<PathBuf> = environments
+        .iter()
+        .filter(|env| env.python_version == PENDING_VERSION)
+        .map(|env| env.path.clone())
+        .collect();
+    
+    thread::spawn(move || {
+        for path in paths {
+            if let Some(version) = probe_version(&path)
+                && tx.send((path, version)).is_err() {
+                // The receiver was replaced by a newer pass
+                break;
+            }
+        }
+    });
+    
+    rx
+}
+
+/// Load (and if needed download) the PyPI package name index on a background
+/// thread; it can take a while the first time.
+fn spawn_package_index_load() -> Receiver<io::Result<Vec<String>>> {
+    let (tx, rx) = mpsc::channel();
+    
+    thread::spawn(move || {
+        let _ = tx.send(package_index::load());
+    });
+    
+    rx
+}
+
+/// Find out which pip handles operations in the environment at `path`, on a
+/// background thread.
+fn spawn_pip_probe(path: PathBuf) -> Receiver<(PathBuf, Option<PipInfo>)> {
+    let (tx, rx) = mpsc::channel();
+    
+    thread::spawn(move || {
+        let info = pip_info(&path).ok();
+        let _ = tx.send((path, info));
+    });
+    
+    rx
+}
+
+/// `(total, explicit)` package counts of the environment at the path, from
+/// `spawn_dependency_probe`.
+type DependencyProbe = Receiver<(PathBuf, Option<(usize, usize)>)>;
+
+/// Count the explicit installs and dependencies of the environment at `path`
+/// on a background thread; it takes two pip runs.
+fn spawn_dependency_probe(path: PathBuf) -> DependencyProbe {
+    let (tx, rx) = mpsc::channel();
+    
+    thread::spawn(move || {
+        let counts = dependency_counts(&path).ok();
+        let _ = tx.send((path, counts));
+    });
+    
+    rx
+}
+
+/// Progress reported by a `spawn_hash_export` thread.
+enum HashProgress {
+    Hashing { requirement: String, position: usize, total: usize },
+    /// Requirements that were left out, e.g. editable installs, and why
+    Skipped { requirement: String, reason: String },
+    /// The file written, with how many requirements were hashed and skipped
+    Finished(Result<(PathBuf, usize, usize), PythonError>),
+}
+
+/// Freeze `env` and write a `--require-hashes` requirements file to
+/// `output` on a background thread, downloading each pinned package to hash
+/// it. Requirements that can't be hashed are written as comments.
+fn spawn_hash_export(env: PythonEnvironment, options: InstallOptions, output: PathBuf) -> Receiver<HashProgress> {
+    let (tx, rx) = mpsc::channel();
+    
+    thread::spawn(move || {
+        make_cancellable();
+        let requirements = match freeze_requirements(&env.path) {
+            Ok(requirements) => requirements,
+            Err(e) => {
+                let _ = tx.send(HashProgress::Finished(Err(e)));
+                return;
+            }
+        };
+        let pinned: Vec<&str> = requirements
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+        
+        let mut content = format!(
+            "# Generated by LazyEnv from pip freeze of {}\n# Install with: pip install --require-hashes -r {}\n",
+            env.name,
+            output.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned()),
+        );
+        let (mut hashed, mut skipped) = (0, 0);
+        for (idx, requirement) in pinned.iter().enumerate() {
+            // Editable installs and direct URLs have no index files to hash
+            let reason = if requirement.starts_with('-') || requirement.contains(" @ ") || !requirement.contains("==") {
+                Some("not pinned to an index version".to_string())
+            } else {
+                let progress = HashProgress::Hashing { requirement: requirement.to_string(), position: idx + 1, total: pinned.len() };
+                if tx.send(progress).is_err() {
+                    return;
+                }
+                match requirement_hashes(&env.path, requirement, &options) {
+                    Ok(hashes) => {
+                        content.push_str(requirement);
+                        for hash in hashes {
+                            content.push_str(" \\\n    ");
+                            content.push_str(&hash);
+                        }
+                        content.push('\n');
+                        hashed += 1;
+                        None
+                    },
+                    Err(e) => Some(e.to_string()),
+                }
+            };
+            if let Some(reason) = reason {
+                content.push_str(&format!("# {}  (skipped: {})\n", requirement, reason.lines().next().unwrap_or_default()));
+                skipped += 1;
+                let _ = tx.send(HashProgress::Skipped { requirement: requirement.to_string(), reason });
+            }
+        }
+        
+        let result = std::fs::write(&output, content).map(|()| (output, hashed, skipped)).map_err(PythonError::from);
+        let _ = tx.send(HashProgress::Finished(result));
+    });
+    
+    rx
+}
+
+fn apply_hash_progress(app: &mut App, progress: HashProgress) {
+    match progress {
+        HashProgress::Hashing { requirement, position, total } => {
+            app.status_message = Some(format!("Hashing requirements: {} ({}/{})…", requirement, position, total));
+        },
+        HashProgress::Skipped { requirement, reason } => {
+            app.push_log(&format!("hashed requirements: skipped {}: {}", requirement, reason));
+        },
+        HashProgress::Finished(Ok((path, hashed, skipped))) => {
+            app.status_message = Some(if skipped == 0 {
+                format!("Wrote {} hashed requirements to {}", hashed, path.display())
+            } else {
+                format!("Wrote {} hashed requirements to {}; skipped {} (l: log)", hashed, path.display(), skipped)
+            });
+        },
+        HashProgress::Finished(Err(e)) => {
+            app.status_message = Some(format!("Error generating hashed requirements: {}", e));
+        },
+    }
+    app.status_message_timer = 0;
+}
+
+/// Packages per `pip show` call in the license pass; one call per package
+/// would pay pip's startup cost every time
+const LICENSE_BATCH_SIZE: usize = 20;
+
+/// Licenses from one `pip show` call of the background license pass.
+struct LicenseBatch {
+    env_path: PathBuf,
+    result: Result<Vec<(String, String)>, PythonError>,
+    /// Packages looked up so far, out of `total`
+    done: usize,
+    total: usize,
+}
+
+/// Fetch the licenses of `names` on a background thread, a batch at a time,
+/// so the status bar can show progress.
+fn spawn_license_fetch(env_path: PathBuf, names: Vec<String>) -> Receiver<LicenseBatch> {
+    let (tx, rx) = mpsc::channel();
+    
+    thread::spawn(move || {
+        make_cancellable();
+        let total = names.len();
+        let mut done = 0;
+        for chunk in names.chunks(LICENSE_BATCH_SIZE) {
+            let result = package_licenses(&env_path, chunk);
+            done += chunk.len();
+            let batch = LicenseBatch { env_path: env_path.clone(), result, done, total };
+            if tx.send(batch).is_err() {
+                break;
+            }
+        }
+    });
+    
+    rx
+}
+
+/// Cache a batch of licenses and show how far the pass has got.
+fn apply_license_batch(app: &mut App, batch: LicenseBatch) {
+    match batch.result {
+        Ok(licenses) => {
+            let cache = app.licenses.entry(batch.env_path).or_default();
+            for (name, license) in licenses {
+                cache.insert(normalize_package_name(&name), license);
+            }
+        },
+        Err(e) => app.push_log(&format!("Error fetching licenses: {}", e)),
+    }
+    
+    app.status_message = Some(if batch.done < batch.total {
+        format!("Fetching licenses {}/{}…", batch.done, batch.total)
+    } else {
+        format!("Fetched licenses for {} packages; filter with {}<name>", batch.total, LICENSE_FILTER_PREFIX)
+    });
+}
+
+/// Packages of the environment at the path, from `spawn_package_listing`.
+type PackageListing = Receiver<(PathBuf, Result<Vec<Package>, PythonError>)>;
+
+/// List the packages of the selected environment on a background thread. The
+/// packages pane shows a loading placeholder until the result arrives.
+fn spawn_package_listing(app: &mut App) -> Option<PackageListing> {
+    let (tx, rx) = mpsc::channel();
+    let path = app.current_environment()?.path.clone();
+    app.loading_packages = true;
+    
+    thread::spawn(move || {
+        make_cancellable();
+        let result = list_packages(&path);
+        let _ = tx.send((path, result));
+    });
+    
+    Some(rx)
+}
+
+/// Leaf packages of the environment at the path, from `spawn_leaf_listing`.
+type LeafListing = Receiver<(PathBuf, Result<HashSet<String>, PythonError>)>;
+
+/// Run `pip list --not-required` for the environment at `env_path` on a
+/// background thread.
+fn spawn_leaf_listing(env_path: PathBuf) -> LeafListing {
+    let (tx, rx) = mpsc::channel();
+    
+    thread::spawn(move || {
+        make_cancellable();
+        let result = list_leaf_packages(&env_path);
+        let _ = tx.send((env_path, result));
+    });
+    
+    rx
+}
+
+/// Result of `spawn_conda_export`: the environment's name, and the file
+/// written or what went wrong.
+struct CondaExport {
+    name: String,
+    from_history: bool,
+    output: Result<PathBuf, String>,
+}
+
+/// Write `conda env export` of the selected environment to
+/// `environment-<env>.yml` in the current directory, optionally with
+/// `--from-history`. conda can take a while, so it runs on a background
+/// thread and `finish_conda_export` reports the path.
+fn spawn_conda_export(app: &mut App, from_history: bool) -> Option<Receiver<CondaExport>> {
+    let env = app.current_environment()?.clone();
+    
+    let (tx, rx) = mpsc::channel();
+    app.working = Some(format!("Exporting '{}'", env.name));
+    thread::spawn(move || {
+        make_cancellable();
+        let output = conda_env_export(&env.path, from_history)
+            .map_err(|e| e.to_string())
+            .and_then(|yaml| {
+                // Written next to where LazyEnv was started, like the hashed requirements
+                let dir = std::env::current_dir().map_err(|e| format!("Error finding the current directory: {}", e))?;
+                let output = dir.join(format!("environment-{}.yml", safe_file_name(&env.name)));
+                std::fs::write(&output, yaml).map_err(|e| format!("Error writing {}: {}", output.display(), e))?;
+                Ok(output)
+            });
+        let _ = tx.send(CondaExport { name: env.name, from_history, output });
+    });
+    
+    Some(rx)
+}
+
+/// Report where a `spawn_conda_export` wrote the environment, or why it couldn't.
+fn finish_conda_export(app: &mut App, export: CondaExport) {
+    app.working = None;
+    app.status_message = Some(match export.output {
+        Ok(output) => {
+            app.push_log(&format!("exported {} to {}", export.name, output.display()));
+            format!("Wrote {}{}", output.display(), if export.from_history { " (--from-history)" } else { "" })
+        },
+        Err(e) => format!("Error exporting environment: {}", e),
+    });
+    app.status_message_timer = 0;
+}
+
+/// Copy `pip freeze` for the selected environment to the clipboard. Without a
+/// usable clipboard the requirements are written to a temp file instead.
+fn copy_freeze(app: &mut App) {
+    let Some(env) = app.current_environment() else {
+        return;
+    };
+    
+    let requirements = match freeze_requirements(&env.path) {
+        Ok(requirements) => requirements,
+        Err(e) => {
+            app.status_message = Some(format!("Error freezing requirements: {}", e));
+            return;
+        }
+    };
+    let count = requirements.lines().filter(|line| !line.trim().is_empty()).count();
+    
+    app.status_message = Some(match clipboard::copy(&requirements) {
+        Ok(()) => format!("Copied {} requirements to clipboard", count),
+        Err(_) => {
+            let path = fallback_path(&env.name, "requirements.txt");
+            match std::fs::write(&path, &requirements) {
+                Ok(()) => format!("No clipboard available; wrote {} requirements to {}", count, path.display()),
+                Err(e) => format!("No clipboard available and could not write {}: {}", path.display(), e),
+            }
+        }
+    });
+}
+
+/// Copy the visible packages, as filtered, to the clipboard as a markdown
+/// table of name, version and summary. Without a usable clipboard the table
+/// is written to a temp file instead.
+fn copy_package_table(app: &mut App) {
+    let visible = app.visible_packages();
+    if visible.is_empty() {
+        app.status_message = Some("No packages to copy".to_string());
+        return;
+    }
+    
+    // Pipes would end a cell early, and a cell can't span lines
+    let cell = |text: &str| text.replace('|', "\\|").replace(['\r', '\n'], " ");
+    let mut table = String::from("| Name | Version | Summary |\n| --- | --- | --- |\n");
+    for &idx in &visible {
+        let package = &app.packages[idx];
+        table.push_str(&format!("| {} | {} | {} |\n", cell(&package.name), cell(&package.version), cell(package.summary.trim())));
+    }
+    
+    let label = if app.show_global_packages {
+        "global".to_string()
+    } else {
+        app.current_environment().map_or_else(|| "packages".to_string(), |env| env.name.clone())
+    };
+    app.status_message = Some(match clipboard::copy(&table) {
+        Ok(()) => format!("Copied a table of {} packages to clipboard", visible.len()),
+        Err(_) => {
+            let path = fallback_path(&label, "packages.md");
+            match std::fs::write(&path, &table) {
+                Ok(()) => format!("No clipboard available; wrote a table of {} packages to {}", visible.len(), path.display()),
+                Err(e) => format!("No clipboard available and could not write {}: {}", path.display(), e),
+            }
+        }
+    });
+}
+
+/// Temp file for text that couldn't go to the clipboard, named after the
+/// environment `name` (made safe for a file name) and `suffix`.
+fn fallback_path(name: &str, suffix: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("lazyenv-{}-{}", safe_file_name(name), suffix))
+}
+
+/// `name` with anything that could upset a file system replaced by `_`.
+fn safe_file_name(name: &str) -> String {
+    name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// Open the popup listing the variables in the selected environment's `.env`
+/// file, if it has one.
+fn show_dotenv(app: &mut App) {
+    let Some(env) = app.current_environment() else {
+        return;
+    };
+    let Some(path) = dotenv::find(&env.path) else {
+        app.status_message = Some(format!("No .env file found for {}", env.name));
+        return;
+    };
+    
+    match dotenv::load(&path) {
+        Ok(vars) => {
+            app.dotenv_path = Some(path);
+            app.dotenv_vars = vars;
+            app.dotenv_scroll = 0;
+            app.open(AppState::DotenvView);
+        },
+        Err(e) => {
+            app.status_message = Some(format!("Error reading {}: {}", path.display(), e));
+        }
+    }
+}
+
+/// Read the registered Jupyter kernels into `app.kernels`, keeping the
+/// selection in range. Returns `false`, with the reason in the status bar,
+/// if they couldn't be listed, e.g. because Jupyter isn't installed.
+fn load_kernels(app: &mut App) -> bool {
+    match jupyter::list_kernels() {
+        Ok(kernels) => {
+            app.selected_kernel = app.selected_kernel.min(kernels.len().saturating_sub(1));
+            app.kernels = Some(kernels);
+            true
+        },
+        Err(e) => {
+            app.status_message = Some(format!("Error listing Jupyter kernels: {}", e));
+            false
+        }
+    }
+}
+
+/// Register the selected environment as a Jupyter kernel named after it,
+/// unless a kernel already launches its interpreter.
+fn register_kernel(app: &mut App) {
+    let Some(env) = app.current_environment().cloned() else {
+        app.status_message = Some("Select an environment to register as a kernel".to_string());
+        return;
+    };
+    if let Some(kernel) = app.kernels.as_deref().and_then(|kernels| jupyter::kernel_for(kernels, &env)) {
+        app.status_message = Some(format!("{} is already registered as kernel '{}'", env.name, kernel.name));
+        return;
+    }
+    
+    let name = jupyter::kernel_name(&env.name);
+    match jupyter::register_kernel(&env.path, &name, &format!("Python ({})", env.name)) {
+        Ok(()) => {
+            app.status_message = Some(format!("Registered {} as kernel '{}'", env.name, name));
+            load_kernels(app);
+            if let Some(idx) = app.kernels.as_ref().and_then(|kernels| kernels.iter().position(|kernel| kernel.name == name)) {
+                app.selected_kernel = idx;
+            }
+        },
+        Err(e) => {
+            app.status_message = Some(format!("Error registering kernel: {}", e));
+            app.push_log(&format!("registering {} as a kernel failed: {}", env.name, e));
+        }
+    }
+}
+
+/// Look for broken environments and open the cleanup dialog listing them.
+/// Only virtual environments are offered for deletion; broken system and
+/// pyenv interpreters are listed so the user knows about them.
+fn begin_cleanup(app: &mut App) {
+    app.cleanup_candidates = find_broken_venvs();
+    app.cleanup_reported = app.environments
+        .iter()
+        .filter(|env| matches!(&env.env_type[..], "system" | "pyenv") && env.python_version == "Unknown")
+        .map(|env| format!("{} ({})", env.name, env.path.display()))
+        .collect();
+    
+    if app.cleanup_candidates.is_empty() && app.cleanup_reported.is_empty() {
+        app.status_message = Some("No broken environments found".to_string());
+    } else {
+        app.open(AppState::CleanupBroken);
+    }
+}
+
+/// Copy a `pip install name==version` command for the selected package. Without
+/// a usable clipboard the command is shown in the status bar instead.
+fn copy_install_command(app: &mut App) {
+    let Some(pkg) = app.selected_package.and_then(|idx| app.packages.get(idx)) else {
+        return;
+    };
+    
+    let command = format!("pip install {}=={}", pkg.name, pkg.version);
+    app.status_message = Some(match clipboard::copy(&command) {
+        Ok(()) => format!("Copied '{}' to clipboard", command),
+        Err(_) => format!("No clipboard available: {}", command),
+    });
+}
+
+/// Compare the selected environment with the requirements file named in the
+/// input box and show the differences.
+fn compare_requirements(app: &mut App) {
+    let Some(env) = app.current_environment() else {
+        return;
+    };
+    let path = PathBuf::from(app.input_text.trim());
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) => {
+            app.status_message = Some(format!("Error reading {}: {}", path.display(), e));
+            return;
+        }
+    };
+    // Listed fresh so the comparison doesn't depend on what's on screen; only
+    // pip's packages, since conda-only ones like openssl never appear in requirements
+    let installed = match list_pip_packages(&env.path) {
+        Ok(installed) => installed,
+        Err(e) => {
+            app.status_message = Some(format!("Error listing packages: {}", e));
+            return;
+        }
+    };
+    
+    let diff = requirements::diff(requirements::parse(&content), &installed);
+    app.requirements_diff = Some((path, diff));
+    app.requirements_diff_scroll = 0;
+    app.open(AppState::RequirementsDiffView);
+}
+
+/// Run the pip command typed into the input box against the selected
+/// environment and show its output in the log view.
+fn run_pip_command_line(app: &mut App) {
+    let args = match split_pip_args(&app.input_text) {
+        Ok(args) => args,
+        Err(e) => {
+            app.status_message = Some(e);
+            return;
+        }
+    };
+    
+    let Some(env_path) = app.current_environment().map(|env| env.path.clone()) else {
+        app.reset_state();
+        return;
+    };
+    
+    app.push_log(&format!("$ pip {}", args.join(" ")));
+    match run_pip_command(&env_path, &args) {
+        Ok(output) => {
+            app.push_log(&String::from_utf8_lossy(&output.stdout));
+            app.push_log(&String::from_utf8_lossy(&output.stderr));
+            app.status_message = Some(if output.status.success() {
+                "pip command finished".to_string()
+            } else {
+                format!("pip command failed ({})", output.status)
+            });
+        },
+        Err(e) => {
+            app.push_log(&format!("error: {}", e));
+            app.status_message = Some(format!("Error running pip: {}", e));
+        }
+    }
+    app.show_log();
+}
+
+/// Create the environment named in the create dialog in the background (see
+/// `run_create`), or first offer the templates if there are any.
+fn continue_create(app: &mut App) -> Option<Creation> {
+    if app.templates.is_empty() {
+        run_create(app, None)
+    } else {
+        app.open(AppState::PickTemplate);
+        None
+    }
+}
+
+/// An environment being created on a background thread, since `python -m
+/// venv` bootstrapping pip can take several seconds.
+struct Creation {
+    name: String,
+    /// Packages to install once it exists
+    template: Option<Template>,
+    result: Receiver<Result<PythonEnvironment, PythonError>>,
+}
+
+/// Start creating the environment named in the create dialog with the
+/// dialog's interpreter, or the configured default; one that can't be found is
+/// an error rather than a silent fallback to `python`. Problems found up front
+/// leave the dialog open; otherwise it closes and the status bar shows
+/// progress until `finish_create`.
+fn run_create(app: &mut App, template: Option<Template>) -> Option<Creation> {
+    let spec = match app.python_override.trim() {
+        "" => app.default_python.clone(),
+        spec => Some(spec.to_string()),
+    };
+    let python = match spec.as_deref().map(resolve_interpreter).transpose() {
+        Ok(python) => python,
+        Err(e) => {
+            app.status_message = Some(format!("Error creating environment: {}", e));
+            return None;
+        }
+    };
+    
+    let venv_dir = match environment_dir(&app.input_text) {
+        Ok(venv_dir) => venv_dir,
+        Err(e) => {
+            app.status_message = Some(format!("Error creating environment: {}", e));
+            return None;
+        }
+    };
+    // Clear out the directory the user agreed to replace
+    if app.confirmed_overwrite.take().is_some_and(|dir| dir == venv_dir) && venv_dir.exists() {
+        let result = delete_environment_for_overwrite(&venv_dir);
+        record_history(app, HistoryEntry::new(
+            Operation::Delete, venv_dir.display().to_string(), None, None, result.as_ref().err().map(ToString::to_string),
+        ));
+        if let Err(e) = result {
+            app.status_message = Some(format!("Error removing the existing environment: {}", e));
+            return None;
+        }
+        if let Some(idx) = app.environments.iter().position(|env| env.path == venv_dir) {
+            app.remove_environment(idx);
+        }
+    }
+    
+    let name = app.input_text.clone();
+    let backend = app.creation_backend;
+    let (tx, rx) = mpsc::channel();
+    {
+        let name = name.clone();
+        thread::spawn(move || {
+            make_cancellable();
+            let _ = tx.send(create_environment(&name, python.as_deref(), backend));
+        });
+    }
+    
+    app.reset_state();
+    app.creating = Some(format!("Creating environment '{}'", name));
+    Some(Creation { name, template, result: rx })
+}
+
+/// Progress reported by a `BatchCreation` thread.
+enum CreateProgress {
+    Started { name: String, position: usize },
+    Finished { name: String, result: Result<PythonEnvironment, PythonError> },
+}
+
+/// Several environments being created one after another on a background
+/// thread, from comma-separated names in the create dialog. A failure is
+/// recorded and the rest are still attempted.
+struct BatchCreation {
+    total: usize,
+    created: Vec<String>,
+    failed: Vec<String>,
+    progress: Receiver<CreateProgress>,
+}
+
+impl BatchCreation {
+    /// Start creating every name in the create dialog, trimmed, skipping empty
+    /// entries. Every name must be valid for `environment_dir`. Names that
+    /// already have a directory are never overwritten; they're reported as failed.
+    fn spawn(app: &mut App) -> Option<Self> {
+        let mut names: Vec<String> = Vec::new();
+        for name in app.input_text.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            if let Err(e) = environment_dir(name) {
+                app.status_message = Some(format!("Error creating environments: {}", e));
+                return None;
+            }
+            if !names.iter().any(|existing| existing == name) {
+                names.push(name.to_string());
+            }
+        }
+        if names.is_empty() {
+            app.status_message = Some("Enter at least one environment name".to_string());
+            return None;
+        }
+        let spec = match app.python_override.trim() {
+            "" => app.default_python.clone(),
+            spec => Some(spec.to_string()),
+        };
+        let python = match spec.as_deref().map(resolve_interpreter).transpose() {
+            Ok(python) => python,
+            Err(e) => {
+                app.status_message = Some(format!("Error creating environments: {}", e));
+                return None;
+            }
+        };
+        
+        let backend = app.creation_backend;
+        let total = names.len();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            make_cancellable();
+            for (idx, name) in names.into_iter().enumerate() {
+                if tx.send(CreateProgress::Started { name: name.clone(), position: idx + 1 }).is_err() {
+                    return;
+                }
+                let result = match environment_dir(&name) {
+                    Ok(dir) if dir.exists() => Err(PythonError::Other(format!("{} already exists", dir.display()))),
+                    _ => create_environment(&name, python.as_deref(), backend),
+                };
+                if tx.send(CreateProgress::Finished { name, result }).is_err() {
+                    return;
+                }
+            }
+        });
+        
+        app.reset_state();
+        app.creating = Some(format!("Creating 1 of {}", total));
+        Some(Self { total, created: Vec::new(), failed: Vec::new(), progress: rx })
+    }
+    
+    /// Add finished environments to the list and show progress. Returns
+    /// true once every name has been attempted.
+    fn poll(&mut self, app: &mut App) -> bool {
+        loop {
+            match self.progress.try_recv() {
+                Ok(CreateProgress::Started { name, position }) => {
+                    app.creating = Some(format!("Creating {} of {}: {}", position, self.total, name));
+                },
+                Ok(CreateProgress::Finished { name, result }) => {
+                    let environment = match &result {
+                        Ok(env) => env.path.display().to_string(),
+                        Err(_) => name.clone(),
+                    };
+                    record_history(app, HistoryEntry::new(
+                        Operation::Create, environment, None, None, result.as_ref().err().map(ToString::to_string),
+                    ));
+                    match result {
+                        Ok(env) => {
+                            app.add_environment(env);
+                            self.created.push(name);
+                        },
+                        Err(e) => {
+                            app.push_log(&format!("creating {} failed: {}", name, e));
+                            self.failed.push(name);
+                        },
+                    }
+                },
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+        
+        app.creating = None;
+        app.status_message = Some(if self.failed.is_empty() {
+            format!("Created {} environments: {}", self.created.len(), self.created.join(", "))
+        } else {
+            format!(
+                "Created {} of {} environments; failed: {} (l: log)",
+                self.created.len(), self.total, self.failed.join(", "),
+            )
+        });
+        app.status_message_timer = 0;
+        true
+    }
+}
+
+/// Add and select the environment a `Creation` produced, or report why it
+/// couldn't be created. Returns the new environment's path; its packages are
+/// left for the caller to list in the background.
+fn finish_create(app: &mut App, name: &str, result: Result<PythonEnvironment, PythonError>) -> Option<PathBuf> {
+    app.creating = None;
+    let environment = match &result {
+        Ok(env) => env.path.display().to_string(),
+        Err(_) => name.to_string(),
+    };
+    record_history(app, HistoryEntry::new(
+        Operation::Create, environment, None, None, result.as_ref().err().map(ToString::to_string),
+    ));
+    
+    match result {
+        Ok(env) => {
+            let env_path = env.path.clone();
+            app.add_environment(env);
+            app.status_message = Some(if app.creation_backend == CreationBackend::Virtualenv && virtualenv_tool().is_none() {
+                format!("Environment '{}' created with python -m venv; virtualenv isn't installed", name)
+            } else {
+                format!("Environment '{}' created successfully", name)
+            });
+            Some(env_path)
+        },
+        Err(e) => {
+            app.push_log(&format!("creating {} failed: {}", name, e));
+            app.status_message = Some(format!("Error creating environment '{}': {}", name, e));
+            None
+        }
+    }
+}
+
+/// Install the package named in the input box into the selected environment.
+/// If the environment is externally managed (PEP 668), ask before retrying
+/// with `--break-system-packages`; that flag is never passed unconfirmed.
+fn run_install(app: &mut App, break_system_packages: bool) {
+    if !app.input_text.is_empty()
+        && let Some(env) = app.current_environment() {
+        let env_path = &env.path.clone();
+        let options = InstallOptions {
+            break_system_packages,
+            ..app.effective_install_options()
+        };
+        match install_package(env_path, &app.input_text, &options) {
+            Ok(_) => {
+                match list_packages(env_path) {
+                    Ok(pkgs) => {
+                        app.set_packages(pkgs);
+                        // Land on the new package; pip may spell its name differently
+                        let name = app.input_text.clone();
+                        app.select_package(&name);
+                        app.clamp_package_selection();
+                    },
+                    Err(e) => {
+                        app.status_message = Some(format!("Error listing packages: {}", e));
+                    }
+                }
+                let version = app.installed_package(&app.input_text).map(|pkg| pkg.version.clone());
+                let entry = HistoryEntry::new(
+                    Operation::Install, env_path.display().to_string(), Some(&app.input_text), version.as_deref(), None,
+                );
+                record_history(app, entry);
+                app.status_message = Some(format!("Package '{}' installed successfully", app.input_text));
+            },
+            // Not recorded: nothing was attempted until the user confirms the retry
+            Err(PythonError::ExternallyManaged) if !break_system_packages => {
+                app.open(AppState::ConfirmBreakSystem);
+            },
+            // Not recorded either: offer to bootstrap pip, then retry
+            Err(PythonError::PipMissing) => {
+                app.pipless.insert(env_path.clone());
+                app.open(AppState::ConfirmEnsurepip);
+            },
+            Err(e) => {
+                let entry = HistoryEntry::new(
+                    Operation::Install, env_path.display().to_string(), Some(&app.input_text), None, Some(e.to_string()),
+                );
+                record_history(app, entry);
+                app.status_message = Some(format!("Error installing package: {}", e));
+            }
+        }
+    }
+}
+
+/// Open the install dialog filled in with the requirement on the clipboard,
+/// so the user sees exactly what will be installed before pressing Enter.
+fn install_from_clipboard(app: &mut App) {
+    let text = match clipboard::paste() {
+        Ok(text) => text,
+        Err(e) => {
+            app.status_message = Some(format!("Couldn't read the clipboard: {}", e));
+            return;
+        }
+    };
+    
+    let Some(requirement) = parse_requirement(&text) else {
+        app.status_message = Some("The clipboard doesn't hold a package name or requirement".to_string());
+        return;
+    };
+    
+    app.open(AppState::InstallPackage);
+    app.input_text = requirement;
+    app.input_end();
+    app.index_override.clear();
+    app.install_upgrade = false;
+    app.install_pre = false;
+    app.editing_index = false;
+    app.status_message = Some("Pasted from the clipboard; press Enter to install".to_string());
+}
+
+/// Open the details popup for the selected package with everything `pip show` knows.
+fn show_package_details(app: &mut App) {
+    if app.show_global_packages {
+        app.status_message = Some("Package details are only available inside an environment; press g to switch back".to_string());
+        return;
+    }
+    let Some(env) = app.current_environment() else {
+        return;
+    };
+    let Some(pkg) = app.selected_package.and_then(|idx| app.packages.get(idx)) else {
+        return;
+    };
+    
+    match package_metadata(&env.path, &pkg.name) {
+        Ok(Some(metadata)) => {
+            app.package_metadata = Some(metadata);
+            app.metadata_scroll = 0;
+            app.open(AppState::PackageDetails);
+        },
+        Ok(None) => {
+            app.status_message = Some(format!("pip has no information about '{}'; try refreshing the list", pkg.name));
+        },
+        Err(e) => {
+            app.status_message = Some(format!("Error reading package details: {}", e));
+        }
+    }
+}
+
+/// Result of `spawn_install_preview` for `requirement` in the environment at `env_path`.
+struct InstallPreview {
+    env_path: PathBuf,
+    requirement: String,
+    packages: Result<Vec<Package>, PythonError>,
+}
+
+/// Dry-run the install typed into the install dialog on a background thread,
+/// since pip resolves (and downloads) everything first. The preview opens
+/// once it's done (see `show_install_preview`).
+fn spawn_install_preview(app: &mut App) -> Option<Receiver<InstallPreview>> {
+    let env_path = app.current_environment()?.path.clone();
+    let requirement = app.input_text.clone();
+    let options = app.effective_install_options();
+    
+    let (tx, rx) = mpsc::channel();
+    app.working = Some(format!("Resolving '{}'", requirement));
+    thread::spawn(move || {
+        make_cancellable();
+        let packages = preview_install(&env_path, &requirement, &options);
+        let _ = tx.send(InstallPreview { env_path, requirement, packages });
+    });
+    
+    Some(rx)
+}
+
+/// Open the install preview once a dry run finishes, if the install dialog is
+/// still open for the same requirement.
+fn show_install_preview(app: &mut App, preview: InstallPreview) {
+    app.working = None;
+    let still_wanted = app.state == AppState::InstallPackage
+        && app.input_text == preview.requirement
+        && app.current_environment().is_some_and(|env| env.path == preview.env_path);
+    if !still_wanted {
+        return;
+    }
+    match preview.packages {
+        Ok(pkgs) => {
+            app.install_preview = pkgs;
+            app.open(AppState::InstallPreview);
+        },
+        Err(e) => {
+            app.status_message = Some(format!("Error previewing install: {}", e));
+        }
+    }
+}
+
+/// Result of `spawn_version_lookup` for a package of the environment at `env_path`.
+struct VersionLookup {
+    env_path: PathBuf,
+    package: Package,
+    /// The versions, newest first, and whether they're pip's cached ones
+    /// because the index couldn't be reached
+    versions: Result<(Vec<String>, bool), String>,
+}
+
+/// Fetch the versions the index offers for the selected package on a
+/// background thread, since `pip index versions` waits on the network. The
+/// version picker opens once they arrive (see `open_version_picker`).
+fn spawn_version_lookup(app: &mut App) -> Option<Receiver<VersionLookup>> {
+    let env_path = app.current_environment()?.path.clone();
+    let package = app.selected_package.and_then(|idx| app.packages.get(idx))?.clone();
+    if package.manager == PackageManager::Conda {
+        app.status_message = Some(format!("'{}' is managed by conda; pick a version with conda install {}=<version>", package.name, package.name));
+        return None;
+    }
+    
+    let (tx, rx) = mpsc::channel();
+    let options = app.install_options.clone();
+    app.working = Some(format!("Fetching versions of '{}'", package.name));
+    thread::spawn(move || {
+        make_cancellable();
+        let versions = match available_versions(&env_path, &package.name, &options) {
+            Ok(versions) => Ok((versions, false)),
+            // Offline: offer what pip has cached, which is better than nothing
+            Err(e @ (PythonError::Network(_) | PythonError::Timeout(_))) => match cached_versions(&env_path, &package.name) {
+                Ok(versions) if versions.iter().any(|v| *v != package.version) => Ok((versions, true)),
+                _ => Err(format!("Couldn't fetch versions of '{}' and none are cached: {}", package.name, e)),
+            },
+            Err(e) => Err(format!("Couldn't fetch versions of '{}': {}", package.name, e)),
+        };
+        let _ = tx.send(VersionLookup { env_path, package, versions });
+    });
+    
+    Some(rx)
+}
+
+/// Open the version picker on the installed version once a lookup finishes,
+/// unless the user has since selected another environment or package.
+fn open_version_picker(app: &mut App, lookup: VersionLookup) {
+    app.working = None;
+    let still_selected = app.current_environment().is_some_and(|env| env.path == lookup.env_path)
+        && app.selected_package.and_then(|idx| app.packages.get(idx)).is_some_and(|pkg| pkg.name == lookup.package.name);
+    if !still_selected || app.state != AppState::Normal {
+        return;
+    }
+    let pkg = lookup.package;
+    let (mut versions, offline) = match lookup.versions {
+        Ok(found) => found,
+        Err(e) => {
+            app.status_message = Some(e);
+            return;
+        }
+    };
+    
+    // The installed version is always offered, marked, even if the cache lacks it
+    if !versions.contains(&pkg.version) {
+        let idx = versions.iter().position(|v| version::compare(v, &pkg.version) == Some(Ordering::Less)).unwrap_or(versions.len());
+        versions.insert(idx, pkg.version.clone());
+    }
+    app.selected_version = versions.iter().position(|v| *v == pkg.version).unwrap_or(0);
+    app.available_versions = versions;
+    app.versions_offline = offline;
+    app.open(AppState::PickVersion);
+}
+
+/// Install the version picked in the version picker, upgrading or
+/// downgrading the selected package to it.
+fn install_version(app: &mut App) {
+    let Some(env) = app.current_environment() else {
+        return;
+    };
+    let Some(pkg) = app.selected_package.and_then(|idx| app.packages.get(idx)) else {
+        return;
+    };
+    let Some(version) = app.available_versions.get(app.selected_version) else {
+        return;
+    };
+    if pkg.manager == PackageManager::Conda {
+        app.status_message = Some(format!("'{}' is managed by conda; pick a version with conda install {}=<version>", pkg.name, pkg.name));
+        return;
+    }
+    if *version == pkg.version {
+        app.status_message = Some(format!("{} {} is already installed", pkg.name, version));
+        return;
+    }
+    
+    let env_path = env.path.clone();
+    let name = pkg.name.clone();
+    let version = version.clone();
+    let requirement = format!("{}=={}", name, version);
+    let result = install_package(&env_path, &requirement, &app.install_options);
+    if let Err(PythonError::ExternallyManaged) = &result {
+        // Same confirmation as the install dialog, which then retries the install
+        app.input_text = requirement;
+        app.input_end();
+        app.index_override.clear();
+        app.install_upgrade = false;
+        app.install_pre = false;
+        app.open(AppState::ConfirmBreakSystem);
+        return;
+    }
+    
+    record_history(app, HistoryEntry::new(
+        Operation::Install, env_path.display().to_string(), Some(&name), Some(&version), result.as_ref().err().map(ToString::to_string),
+    ));
+    match result {
+        Ok(()) => {
+            match list_packages(&env_path) {
+                Ok(pkgs) => {
+                    app.set_packages(pkgs);
+                    app.select_package(&name);
+                    app.clamp_package_selection();
+                },
+                Err(e) => {
+                    app.status_message = Some(format!("Error listing packages: {}", e));
+                    return;
+                }
+            }
+            app.status_message = Some(format!("Installed {} {}", name, version));
+        },
+        Err(e) => {
+            app.status_message = Some(format!("Error installing {}: {}", requirement, e));
+        }
+    }
+}
+
+/// Append an entry to the history file, noting in the log if that fails.
+fn record_history(app: &mut App, entry: HistoryEntry) {
+    if let Err(e) = history::append(&entry) {
+        app.push_log(&format!("warning: couldn't record history: {}", e));
+    }
+}
+
+/// Open the force-reinstall confirmation for the selected package. Reinstalling
+/// goes through pip, so conda-managed packages are refused.
+fn begin_reinstall(app: &mut App) {
+    if app.selected_environment.is_some()
+        && let Some(pkg) = app.selected_package.and_then(|idx| app.packages.get(idx)) {
+        if pkg.manager == PackageManager::Conda {
+            app.status_message = Some(format!("'{}' is managed by conda; reinstall it with conda install --force-reinstall", pkg.name));
+            return;
+        }
+        app.open(AppState::ReinstallPackage);
+        app.dialog_state = DialogState::Confirm;
+    }
+}
+
+/// Open the uninstall confirmation for the selected package, looking up
+/// which installed packages depend on it so the dialog can warn about them.
+fn begin_uninstall(app: &mut App) {
+    if let Some(env) = app.current_environment()
+        && let Some(pkg_idx) = app.selected_package
+        && pkg_idx < app.packages.len() {
+        let env_path = &env.path;
+        app.required_by = package_dependents(env_path, &app.packages[pkg_idx].name).unwrap_or_default();
+        app.open(AppState::UninstallPackage);
+        app.dialog_state = DialogState::Confirm;
+    }
 }
 
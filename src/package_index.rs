@@ -0,0 +1,130 @@
+//! A cached list of every package name on PyPI, used to complete names in the
+//! install dialog. The list is large, so it's kept gzipped in the cache
+//! directory and refreshed once it's older than `MAX_AGE`. There's no HTTP or
+//! gzip support in LazyEnv itself; Python's standard library does both.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+use crate::paths;
+use crate::process::{operation_timeout, output_with_timeout, probe_timeout};
+use crate::python::resolve_interpreter;
+
+/// How old the cached index can get before it's downloaded again
+const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Most suggestions shown or cycled through at once
+pub const MAX_SUGGESTIONS: usize = 50;
+
+/// Download the PEP 691 JSON simple index and write its names, one per line,
+/// gzipped. Written to a temporary file first so a failed download never
+/// replaces a good index.
+const DOWNLOAD_SCRIPT: &str = r#"
+import gzip, json, os, sys, urllib.request
+path = sys.argv[1]
+request = urllib.request.Request("https://pypi.org/simple/", headers={"Accept": "application/vnd.pypi.simple.v1+json"})
+with urllib.request.urlopen(request, timeout=60) as response:
+    projects = json.load(response)["projects"]
+names = sorted({project["name"] for project in projects}, key=str.lower)
+with gzip.open(path + ".tmp", "wt", encoding="utf-8") as f:
+    f.write("\n".join(names))
+os.replace(path + ".tmp", path)
+"#;
+
+const READ_SCRIPT: &str = r#"
+import gzip, sys
+with gzip.open(sys.argv[1], "rt", encoding="utf-8") as f:
+    sys.stdout.write(f.read())
+"#;
+
+pub fn path() -> io::Result<PathBuf> {
+    Ok(paths::cache_dir()?.join("pypi-names.txt.gz"))
+}
+
+/// Package names from the cached index, downloading it first if it's missing
+/// or stale. A stale index is still used if the download fails, e.g. offline.
+/// Names are sorted case-insensitively for `completions`.
+pub fn load() -> io::Result<Vec<String>> {
+    let path = path()?;
+    let python = resolve_interpreter("python3").or_else(|_| resolve_interpreter("python"))?;
+
+    let age = fs::metadata(&path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+    if age.is_none_or(|age| age > MAX_AGE)
+        && let Err(e) = download(&python, &path)
+        && age.is_none() {
+        return Err(e);
+    }
+
+    let output = output_with_timeout(
+        Command::new(&python).args(["-c", READ_SCRIPT]).arg(&path),
+        probe_timeout(),
+    )?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "Failed to read the package index: {}",
+            String::from_utf8_lossy(&output.stderr).trim(),
+        )));
+    }
+
+    let mut names: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect();
+    // Python's sort already matches, but don't rely on it for the binary search
+    names.sort_by_cached_key(|name| name.to_lowercase());
+    Ok(names)
+}
+
+fn download(python: &Path, path: &Path) -> io::Result<()> {
+    let output = output_with_timeout(
+        Command::new(python).args(["-c", DOWNLOAD_SCRIPT]).arg(path),
+        operation_timeout(),
+    )?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "Failed to download the PyPI package index: {}",
+            String::from_utf8_lossy(&output.stderr).trim(),
+        )))
+    }
+}
+
+/// Names starting with `prefix`, ignoring case, in index order. At most
+/// `MAX_SUGGESTIONS` are returned.
+pub fn completions<'a>(names: &'a [String], prefix: &str) -> Vec<&'a str> {
+    let prefix = prefix.to_lowercase();
+    let start = names.partition_point(|name| name.to_lowercase() < prefix);
+    names[start..]
+        .iter()
+        .take_while(|name| name.to_lowercase().starts_with(&prefix))
+        .take(MAX_SUGGESTIONS)
+        .map(String::as_str)
+        .collect()
+}
+
+/// The longest prefix all of `names` share, ignoring case; spelled as in the
+/// first name.
+pub fn common_prefix(names: &[&str]) -> String {
+    let Some((first, rest)) = names.split_first() else {
+        return String::new();
+    };
+    let mut len = first.len();
+    for name in rest {
+        len = first
+            .char_indices()
+            .zip(name.chars())
+            .take_while(|((_, a), b)| a.eq_ignore_ascii_case(b))
+            .last()
+            .map_or(0, |((idx, c), _)| idx + c.len_utf8())
+            .min(len);
+    }
+    first[..len].to_string()
+}
@@ -0,0 +1,41 @@
+//! Platform-correct locations for LazyEnv's files.
+//!
+//! Linux follows the XDG base directory spec (`~/.config`, `~/.cache`,
+//! `~/.local/share`, `~/.local/state`), macOS uses `~/Library/...` and Windows
+//! uses `%APPDATA%` / `%LOCALAPPDATA%`. Directories are created on first use.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+const APP_DIR: &str = "lazyenv";
+
+/// User preferences, e.g. `config.json`.
+pub fn config_dir() -> io::Result<PathBuf> {
+    ensure(dirs::config_dir(), "config")
+}
+
+/// Re-creatable data such as downloaded indexes.
+pub fn cache_dir() -> io::Result<PathBuf> {
+    ensure(dirs::cache_dir(), "cache")
+}
+
+/// Data the user would want to keep, such as history.
+pub fn data_dir() -> io::Result<PathBuf> {
+    ensure(dirs::data_dir(), "data")
+}
+
+/// Session state like usage counts. Only Linux has a dedicated state
+/// directory; other platforms keep it alongside the data directory.
+pub fn state_dir() -> io::Result<PathBuf> {
+    ensure(dirs::state_dir().or_else(dirs::data_dir), "state")
+}
+
+fn ensure(base: Option<PathBuf>, kind: &str) -> io::Result<PathBuf> {
+    let dir = base
+        .ok_or_else(|| io::Error::other(format!("Could not determine {} directory", kind)))?
+        .join(APP_DIR);
+
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
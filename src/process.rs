@@ -0,0 +1,337 @@
+use std::cell::Cell;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Default limit for quick probes: version checks, detection and package listing.
+const DEFAULT_PROBE_TIMEOUT_SECS: u64 = 30;
+/// Default limit for the per-environment `python --version` probe during
+/// detection; environments that take longer are listed as pending.
+const DEFAULT_DETECT_TIMEOUT_SECS: u64 = 5;
+/// Default limit for operations that may download or build: install, uninstall, create.
+const DEFAULT_OPERATION_TIMEOUT_SECS: u64 = 600;
+
+/// Timeout for detection and listing commands.
+/// Can be overridden with `LAZYENV_PROBE_TIMEOUT` (seconds).
+pub fn probe_timeout() -> Duration {
+    timeout_from_env("LAZYENV_PROBE_TIMEOUT", DEFAULT_PROBE_TIMEOUT_SECS)
+}
+
+/// Timeout for probing one environment's interpreter during detection, kept
+/// short so a slow network mount can't stall the whole scan.
+/// Can be overridden with `LAZYENV_DETECT_TIMEOUT` (seconds).
+pub fn detect_timeout() -> Duration {
+    timeout_from_env("LAZYENV_DETECT_TIMEOUT", DEFAULT_DETECT_TIMEOUT_SECS)
+}
+
+/// Timeout for commands that modify an environment.
+/// Can be overridden with `LAZYENV_OPERATION_TIMEOUT` (seconds).
+pub fn operation_timeout() -> Duration {
+    timeout_from_env("LAZYENV_OPERATION_TIMEOUT", DEFAULT_OPERATION_TIMEOUT_SECS)
+}
+
+fn timeout_from_env(var: &str, default_secs: u64) -> Duration {
+    let secs = std::env::var(var)
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(default_secs);
+
+    Duration::from_secs(secs)
+}
+
+/// Bumped by `cancel_running`; commands started before the bump are killed.
+static CANCEL_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// Whether `cancel_running` may kill the commands this thread runs
+    static CANCELLABLE: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Let `cancel_running` kill the commands this thread runs from now on.
+/// Only the threads behind operations Ctrl+X can stop call this, so a cancel
+/// never kills a probe running alongside them and gets its failure cached.
+pub fn make_cancellable() {
+    CANCELLABLE.with(|cancellable| cancellable.set(true));
+}
+
+/// Kill every command `output_with_timeout` is still waiting on in a thread
+/// that called `make_cancellable`. Each one fails with an
+/// `ErrorKind::Interrupted` error ("Operation cancelled"); commands started
+/// afterwards run normally.
+pub fn cancel_running() {
+    CANCEL_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Command lines `output_with_timeout` is waiting on, by a per-call id
+static RUNNING: Mutex<Vec<(u64, String)>> = Mutex::new(Vec::new());
+static NEXT_COMMAND_ID: AtomicU64 = AtomicU64::new(0);
+/// The most recently started command line, kept after it finishes
+static LAST_COMMAND: Mutex<Option<String>> = Mutex::new(None);
+
+/// Command lines of everything `output_with_timeout` is running, oldest first.
+pub fn running_commands() -> Vec<String> {
+    RUNNING.lock().map(|running| running.iter().map(|(_, line)| line.clone()).collect()).unwrap_or_default()
+}
+
+/// The command line of the last command started, running or not.
+pub fn last_command() -> Option<String> {
+    LAST_COMMAND.lock().ok().and_then(|last| last.clone())
+}
+
+/// `command` as it could be pasted into a shell: the program and its
+/// arguments, single-quoted where needed.
+pub fn command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| {
+            let arg = arg.to_string_lossy();
+            if !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c)) {
+                arg.into_owned()
+            } else {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Lists a command in `RUNNING` until dropped, however the wait ends.
+struct RunningGuard(u64);
+
+impl RunningGuard {
+    fn register(command: &Command) -> Self {
+        let id = NEXT_COMMAND_ID.fetch_add(1, Ordering::SeqCst);
+        let line = command_line(command);
+        if let Ok(mut last) = LAST_COMMAND.lock() {
+            *last = Some(line.clone());
+        }
+        if let Ok(mut running) = RUNNING.lock() {
+            running.push((id, line));
+        }
+        RunningGuard(id)
+    }
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        if let Ok(mut running) = RUNNING.lock() {
+            running.retain(|(id, _)| *id != self.0);
+        }
+    }
+}
+
+/// Flags that would make pip act outside the selected environment.
+const ESCAPING_PIP_FLAGS: &[&str] = &["--target", "--prefix", "--root", "--user", "--break-system-packages"];
+
+/// Real pip options that happen to be abbreviations of an escaping flag
+const ALLOWED_PREFIXES: &[&str] = &["--pre"];
+
+/// Short pip options that take a value, which may be attached (`-r reqs.txt`,
+/// `-rreqs.txt`) and ends a cluster such as `-qr reqs.txt`
+const SHORT_OPTIONS_WITH_VALUE: &str = "rcetdbif";
+
+/// Whether `arg` would make pip act outside the selected environment. pip
+/// accepts any unambiguous abbreviation of a long option (`--targ=/x`) and
+/// clusters of short ones (`-qt /x`), so both are checked.
+fn is_escaping_pip_flag(arg: &str) -> bool {
+    if let Some(long) = arg.strip_prefix("--") {
+        let name = long.split('=').next().unwrap_or_default();
+        let flag = format!("--{}", name);
+        return !name.is_empty()
+            && !ALLOWED_PREFIXES.contains(&flag.as_str())
+            && ESCAPING_PIP_FLAGS.iter().any(|escaping| escaping.starts_with(&flag));
+    }
+    let Some(cluster) = arg.strip_prefix('-') else {
+        return false;
+    };
+    for c in cluster.chars() {
+        if c == 't' {
+            return true;
+        }
+        if SHORT_OPTIONS_WITH_VALUE.contains(c) {
+            break;
+        }
+    }
+    false
+}
+
+/// Split a pip command line into arguments the way a POSIX shell would
+/// quote them, without ever invoking a shell. Handles single quotes, double
+/// quotes and backslash escapes. A leading `pip` is dropped.
+pub fn split_pip_args(input: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => current.push(c),
+                        None => return Err("Unterminated single quote".to_string()),
+                    }
+                }
+            },
+            '"' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => current.push(c),
+                            None => return Err("Unterminated double quote".to_string()),
+                        },
+                        Some(c) => current.push(c),
+                        None => return Err("Unterminated double quote".to_string()),
+                    }
+                }
+            },
+            '\\' => {
+                in_arg = true;
+                match chars.next() {
+                    Some(c) => current.push(c),
+                    None => return Err("Trailing backslash".to_string()),
+                }
+            },
+            c if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            },
+            c => {
+                in_arg = true;
+                current.push(c);
+            },
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+
+    if args.first().is_some_and(|arg| arg == "pip" || arg == "pip3") {
+        args.remove(0);
+    }
+    if args.is_empty() {
+        return Err("Enter a pip command, e.g. 'install --pre requests'".to_string());
+    }
+    if let Some(flag) = args.iter().find(|arg| is_escaping_pip_flag(arg)) {
+        return Err(format!("'{}' is not allowed: it acts outside the selected environment", flag));
+    }
+
+    Ok(args)
+}
+
+/// Like `Command::output`, but kills the child and returns an
+/// `ErrorKind::TimedOut` error ("<program> timed out") once `timeout` elapses,
+/// or an `ErrorKind::Interrupted` one if `cancel_running` is called first
+/// (in a thread that called `make_cancellable`).
+pub fn output_with_timeout(command: &mut Command, timeout: Duration) -> io::Result<Output> {
+    run_with_timeout(command, timeout, None)
+}
+
+/// Like `output_with_timeout`, but also sends each line of stdout to `lines`
+/// as the child writes it, for following a long command's progress.
+pub fn output_with_progress(command: &mut Command, timeout: Duration, lines: Sender<String>) -> io::Result<Output> {
+    run_with_timeout(command, timeout, Some(lines))
+}
+
+fn run_with_timeout(command: &mut Command, timeout: Duration, lines: Option<Sender<String>>) -> io::Result<Output> {
+    let generation = CANCEL_GENERATION.load(Ordering::SeqCst);
+    let cancellable = CANCELLABLE.with(Cell::get);
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let _running = RunningGuard::register(command);
+
+    // Drain both pipes on their own threads so a chatty child can't block on a full pipe
+    let stdout = child.stdout.take().map(|mut pipe| {
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            match lines {
+                Some(lines) => {
+                    let mut reader = BufReader::new(pipe);
+                    let mut line = Vec::new();
+                    while reader.read_until(b'\n', &mut line).is_ok_and(|read| read > 0) {
+                        // A receiver that went away only stops the forwarding
+                        let _ = lines.send(String::from_utf8_lossy(&line).trim_end().to_string());
+                        buf.append(&mut line);
+                    }
+                },
+                None => {
+                    let _ = pipe.read_to_end(&mut buf);
+                },
+            }
+            buf
+        })
+    });
+    let stderr = child.stderr.take().map(|mut pipe| {
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = pipe.read_to_end(&mut buf);
+            buf
+        })
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+
+        if cancellable && CANCEL_GENERATION.load(Ordering::SeqCst) != generation {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "Operation cancelled"));
+        }
+
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+
+            let program = Path::new(command.get_program())
+                .file_name()
+                .unwrap_or(command.get_program())
+                .to_string_lossy()
+                .to_string();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("{} timed out", program),
+            ));
+        }
+
+        thread::sleep(Duration::from_millis(20));
+    };
+
+    let stdout = stdout.and_then(|handle| handle.join().ok()).unwrap_or_default();
+    let stderr = stderr.and_then(|handle| handle.join().ok()).unwrap_or_default();
+
+    Ok(Output { status, stdout, stderr })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_pip_args_rejects_abbreviated_escaping_flags() {
+        for command in ["install --targ=/x requests", "install --pref /x requests", "install --us requests", "install -qt /x requests", "install -t/x requests"] {
+            assert!(split_pip_args(command).is_err(), "{} was allowed", command);
+        }
+        for command in ["install --pre requests", "install -r requirements.txt", "install -U --upgrade-strategy eager requests", "list --outdated"] {
+            assert!(split_pip_args(command).is_ok(), "{} was refused", command);
+        }
+    }
+}
@@ -1,119 +1,547 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fmt;
 use std::io;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Output};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, SystemTime};
 use std::fs;
 
+use serde::{Deserialize, Serialize};
+
+use crate::process::{output_with_progress, output_with_timeout, probe_timeout, detect_timeout, operation_timeout};
+use crate::version::{self, release_components};
+
 #[derive(Debug, Clone)]
 pub struct PythonEnvironment {
     pub name: String,
     pub path: PathBuf,
     pub python_version: String,
-    pub env_type: String, // "venv", "conda", "pyenv", "system"
+    pub env_type: String, // "venv", "conda", "mamba", "pyenv", "pipx", "hatch", "system"
 }
 
+/// `python_version` of an environment whose interpreter didn't answer within
+/// the detection timeout, e.g. on a slow network mount. A background
+/// `probe_version` fills in the real version later.
+pub const PENDING_VERSION: &str = "pending";
+
+/// License shown for packages that don't declare one
+pub const UNKNOWN_LICENSE: &str = "Unknown";
+
 #[derive(Debug, Clone)]
 pub struct Package {
     pub name: String,
     pub version: String,
     pub summary: String,
+    pub manager: PackageManager,
+}
+
+/// What installed a package, and so what has to remove it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PackageManager {
+    Pip,
+    /// Installed by conda or micromamba; may not be a Python package at all
+    Conda,
+}
+
+impl fmt::Display for PackageManager {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PackageManager::Pip => write!(f, "pip"),
+            PackageManager::Conda => write!(f, "conda"),
+        }
+    }
+}
+
+/// Tools that manage conda environments, in the order they're tried
+const CONDA_TOOLS: [&str; 2] = ["conda", "micromamba"];
+
+/// Everything `pip show --files` reports about an installed package.
+#[derive(Debug, Clone, Default)]
+pub struct PackageMetadata {
+    pub name: String,
+    pub version: String,
+    pub summary: String,
+    pub author: String,
+    pub license: String,
+    pub home_page: String,
+    pub location: String,
+    pub requires: Vec<String>,
+    pub required_by: Vec<String>,
+    /// Files the package installed, relative to `location`; `None` if pip
+    /// didn't list them
+    pub files: Option<Vec<String>>,
+    /// Project directory of an editable install, whose code lives there
+    /// rather than in the listed files
+    pub editable_location: String,
+}
+
+/// The pip that handles operations in an environment.
+#[derive(Debug, Clone)]
+pub struct PipInfo {
+    /// pip itself, or the python that runs it as a module
+    pub executable: PathBuf,
+    /// e.g. "pip 24.0 (python 3.12)"
+    pub version: String,
+}
+
+/// Tool that builds new environments.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CreationBackend {
+    /// The standard library's `python -m venv`
+    #[default]
+    Venv,
+    /// The `virtualenv` tool, falling back to `venv` when it isn't installed
+    Virtualenv,
+}
+
+/// Why an operation on a Python environment failed, so callers can react to
+/// the kind of failure instead of parsing messages.
+#[derive(Debug)]
+pub enum PythonError {
+    /// A command, interpreter or file that's needed doesn't exist
+    NotFound(String),
+    /// The OS refused access, e.g. to an environment owned by another user
+    PermissionDenied(String),
+    /// pip couldn't reach the package index; the text is pip's complaint
+    Network(String),
+    /// A command ran past its timeout and was killed
+    Timeout(String),
+    /// The user cancelled it with Ctrl+X
+    Cancelled,
+    /// pip refused to modify an interpreter that the OS package manager owns (PEP 668)
+    ExternallyManaged,
+    /// The environment has no pip, e.g. a venv created with `--without-pip`
+    PipMissing,
+    /// pip ran and reported failure while trying to `action`
+    PipFailure { action: String, stderr: String },
+    /// The tools installed can't do it, e.g. a pip too old for `--dry-run`
+    Unsupported(String),
+    Other(String),
+}
+
+impl PythonError {
+    /// Classify an I/O failure, prefixing its message with `context`.
+    fn io(context: impl fmt::Display, error: io::Error) -> Self {
+        Self::from_kind(error.kind(), format!("{}: {}", context, error))
+    }
+    
+    fn from_kind(kind: io::ErrorKind, message: String) -> Self {
+        match kind {
+            io::ErrorKind::NotFound => PythonError::NotFound(message),
+            io::ErrorKind::PermissionDenied => PythonError::PermissionDenied(message),
+            io::ErrorKind::TimedOut => PythonError::Timeout(message),
+            // `process::cancel_running` kills commands with this kind
+            io::ErrorKind::Interrupted => PythonError::Cancelled,
+            _ => PythonError::Other(message),
+        }
+    }
+    
+    /// The closest `io::ErrorKind`, for callers that deal in `io::Error`.
+    fn kind(&self) -> io::ErrorKind {
+        match self {
+            PythonError::NotFound(_) | PythonError::PipMissing => io::ErrorKind::NotFound,
+            PythonError::PermissionDenied(_) | PythonError::ExternallyManaged => io::ErrorKind::PermissionDenied,
+            PythonError::Network(_) => io::ErrorKind::ConnectionRefused,
+            PythonError::Timeout(_) => io::ErrorKind::TimedOut,
+            PythonError::Cancelled => io::ErrorKind::Interrupted,
+            PythonError::Unsupported(_) => io::ErrorKind::Unsupported,
+            PythonError::PipFailure { .. } | PythonError::Other(_) => io::ErrorKind::Other,
+        }
+    }
+}
+
+impl fmt::Display for PythonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PythonError::NotFound(message)
+            | PythonError::PermissionDenied(message)
+            | PythonError::Unsupported(message)
+            | PythonError::Other(message) => write!(f, "{}", message),
+            PythonError::Network(stderr) => write!(
+                f,
+                "Couldn't reach the package index; check your connection or proxy settings ({})",
+                stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or_default().trim(),
+            ),
+            PythonError::Timeout(message) => write!(
+                f,
+                "{}; raise LAZYENV_OPERATION_TIMEOUT (or LAZYENV_PROBE_TIMEOUT for listings) to wait longer",
+                message,
+            ),
+            PythonError::Cancelled => write!(f, "Operation cancelled"),
+            PythonError::ExternallyManaged => write!(f, "This Python is externally managed by your OS (PEP 668), so pip won't modify it; create a virtual environment and install there instead"),
+            PythonError::PipMissing => write!(f, "Could not find pip executable; this environment has no pip (created with --without-pip?)"),
+            PythonError::PipFailure { action, stderr } => write!(f, "Failed to {}: {}", action, stderr),
+        }
+    }
+}
+
+impl std::error::Error for PythonError {}
+
+impl From<io::Error> for PythonError {
+    fn from(error: io::Error) -> Self {
+        Self::from_kind(error.kind(), error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for PythonError {
+    fn from(error: serde_json::Error) -> Self {
+        PythonError::Other(format!("Unreadable output: {}", error))
+    }
+}
+
+impl From<PythonError> for io::Error {
+    fn from(error: PythonError) -> Self {
+        io::Error::new(error.kind(), error)
+    }
+}
+
+/// Phrases in pip's output that mean the package index couldn't be reached
+const NETWORK_FAILURES: [&str; 5] = [
+    "Failed to establish a new connection",
+    "Temporary failure in name resolution",
+    "ConnectTimeoutError",
+    "ProxyError",
+    "Could not fetch URL",
+];
+
+/// The requirement in `text` if it plausibly is one: a single line starting
+/// with a package name, optionally followed by extras, version specifiers or
+/// a marker. A leading `pip install` is dropped, so commands copied from a
+/// project page work too.
+pub fn parse_requirement(text: &str) -> Option<String> {
+    let text = text.trim();
+    if text.is_empty() || text.len() > 200 || text.contains(['\n', '\r']) {
+        return None;
+    }
+    
+    let text = ["pip install ", "pip3 install ", "python -m pip install "]
+        .iter()
+        .find_map(|prefix| text.strip_prefix(prefix))
+        .unwrap_or(text)
+        .trim();
+    let name_end = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .unwrap_or(text.len());
+    let (name, rest) = text.split_at(name_end);
+    
+    let plausible = name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && rest.chars().all(|c| c.is_ascii_alphanumeric() || " -_.[],<>=!~*;'\"".contains(c));
+    plausible.then(|| text.to_string())
+}
+
+/// Normalize a package name per PEP 503: lowercase, with runs of `-`, `_`
+/// and `.` collapsed to a single `-`, so `Flask_Login` matches `flask-login`.
+/// Exactly `re.sub(r"[-_.]+", "-", name).lower()`, so leading and trailing
+/// separators are kept too.
+pub fn normalize_package_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    let mut in_separator = false;
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !in_separator {
+                normalized.push('-');
+            }
+            in_separator = true;
+        } else {
+            in_separator = false;
+            normalized.extend(c.to_lowercase());
+        }
+    }
+    normalized
+}
+
+/// Options applied to every command that installs packages.
+#[derive(Debug, Clone, Default)]
+pub struct InstallOptions {
+    /// Replaces PyPI as the primary index (`--index-url`)
+    pub index_url: Option<String>,
+    /// Additional indexes to search (`--extra-index-url`)
+    pub extra_index_urls: Vec<String>,
+    /// Override PEP 668 protection (`--break-system-packages`). Only ever set
+    /// after the user explicitly confirms it.
+    pub break_system_packages: bool,
+    /// Upgrade the package if it's already installed (`--upgrade`)
+    pub upgrade: bool,
+    /// Consider pre-release versions (`--pre`)
+    pub pre: bool,
+    /// Flags from the config's `pip` section, such as `--trusted-host`, that
+    /// apply to uninstalls as well as installs
+    pub pip_flags: Vec<String>,
+    /// The config's `pip.extra_args`. Installs get them as-is; other commands
+    /// only get the ones they understand (see `general_pip_args` and
+    /// `index_pip_args`). They come after `pip_flags` and before the
+    /// per-operation options above, and pip lets the last occurrence of an
+    /// option win, so a per-operation override beats both.
+    pub extra_args: Vec<String>,
+}
+
+impl InstallOptions {
+    fn pip_args(&self) -> Vec<String> {
+        let mut args = self.pip_flags.clone();
+        args.extend(self.extra_args.iter().cloned());
+        if let Some(url) = &self.index_url {
+            args.push("--index-url".to_string());
+            args.push(url.clone());
+        }
+        for url in &self.extra_index_urls {
+            args.push("--extra-index-url".to_string());
+            args.push(url.clone());
+        }
+        if self.break_system_packages {
+            args.push("--break-system-packages".to_string());
+        }
+        if self.upgrade {
+            args.push("--upgrade".to_string());
+        }
+        if self.pre {
+            args.push("--pre".to_string());
+        }
+        args
+    }
+    
+    /// Build the full argument list for an install-style pip command.
+    fn with_args(&self, args: &[&str]) -> Vec<String> {
+        args.iter()
+            .map(|arg| arg.to_string())
+            .chain(self.pip_args())
+            .collect()
+    }
 }
 
-pub fn list_environments() -> io::Result<Vec<PythonEnvironment>> {
+/// Fallback for when pip can't be run: prints installed distributions as the
+/// same JSON shape as `pip list --format=json` (plus summaries). Uses
+/// `importlib.metadata`, falling back to `pkg_resources` on old Pythons.
+const LIST_PACKAGES_SCRIPT: &str = r#"
+import json
+
+packages = []
+try:
+    from importlib import metadata
+except ImportError:
+    # Python < 3.8: fall back to setuptools
+    import warnings
+    warnings.simplefilter("ignore")
+    import pkg_resources
+    for pkg in pkg_resources.working_set:
+        packages.append({
+            "name": pkg.project_name,
+            "version": pkg.version,
+            "summary": ""
+        })
+else:
+    seen = set()
+    for dist in metadata.distributions():
+        name = dist.metadata["Name"]
+        # The same distribution can appear on several sys.path entries
+        if not name or name.lower() in seen:
+            continue
+        seen.add(name.lower())
+        packages.append({
+            "name": name,
+            "version": dist.version,
+            "summary": dist.metadata["Summary"] or ""
+        })
+print(json.dumps(packages))
+"#;
+
+/// Detect every environment on the system. Detectors that fail don't abort
+/// the scan; their errors are returned as warnings alongside the environments.
+pub fn list_environments() -> Result<(Vec<PythonEnvironment>, Vec<String>), PythonError> {
     let mut environments = Vec::new();
+    let mut warnings = Vec::new();
     
     // Check for system Python
     if let Err(e) = detect_system_python(&mut environments) {
-        eprintln!("Warning: Failed to detect system Python: {}", e);
+        warnings.push(format!("Failed to detect system Python: {}", e));
     }
     
     // Check for virtualenv environments in common locations
     if let Err(e) = detect_venv_environments(&mut environments) {
-        eprintln!("Warning: Failed to detect venv environments: {}", e);
+        warnings.push(format!("Failed to detect venv environments: {}", e));
     }
     
     // Check for pyenv environments
     if let Err(e) = detect_pyenv_environments(&mut environments) {
-        eprintln!("Warning: Failed to detect pyenv environments: {}", e);
+        warnings.push(format!("Failed to detect pyenv environments: {}", e));
     }
     
     // Check for conda environments
     if let Err(e) = detect_conda_environments(&mut environments) {
-        eprintln!("Warning: Failed to detect conda environments: {}", e);
+        warnings.push(format!("Failed to detect conda environments: {}", e));
+    }
+    
+    // Check for micromamba environments
+    if let Err(e) = detect_micromamba_environments(&mut environments) {
+        warnings.push(format!("Failed to detect micromamba environments: {}", e));
+    }
+    
+    // Check for pipx-managed application environments
+    if let Err(e) = detect_pipx_environments(&mut environments) {
+        warnings.push(format!("Failed to detect pipx environments: {}", e));
+    }
+    
+    // Check for Hatch project environments
+    if let Err(e) = detect_hatch_environments(&mut environments) {
+        warnings.push(format!("Failed to detect Hatch environments: {}", e));
     }
     
     // Check for environments in the current directory
     if let Err(e) = detect_local_environments(&mut environments) {
-        eprintln!("Warning: Failed to detect local environments: {}", e);
+        warnings.push(format!("Failed to detect local environments: {}", e));
+    }
+    
+    Ok((dedup_environments(environments), warnings))
+}
+
+/// Drop environments that resolve to the same location as an earlier entry.
+/// Detectors overlap (e.g. `~/.venv` when run from `~`, or a pyenv version
+/// symlinked into `~/.virtualenvs`), so the first detected type wins.
+fn dedup_environments(environments: Vec<PythonEnvironment>) -> Vec<PythonEnvironment> {
+    let mut seen = HashSet::new();
+    
+    environments
+        .into_iter()
+        .filter(|env| {
+            let key = fs::canonicalize(&env.path).unwrap_or_else(|_| env.path.clone());
+            seen.insert(key)
+        })
+        .collect()
+}
+
+/// Pick the environment that best matches the current project, if any.
+/// The most specific signal wins: an activated `$VIRTUAL_ENV`, then a `.venv`
+/// directory in the working directory, then a pyenv `.python-version` file.
+pub fn project_environment(environments: &[PythonEnvironment]) -> Option<usize> {
+    if let Some(active) = std::env::var_os("VIRTUAL_ENV")
+        && let Some(idx) = environments.iter().position(|env| same_path(&env.path, Path::new(&active))) {
+        return Some(idx);
+    }
+    
+    let local_venv = Path::new(".venv");
+    if local_venv.is_dir()
+        && let Some(idx) = environments.iter().position(|env| same_path(&env.path, local_venv)) {
+        return Some(idx);
+    }
+    
+    // pyenv allows several versions in the file; the first one takes precedence
+    let content = fs::read_to_string(".python-version").ok()?;
+    let version = content
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))?;
+    
+    environments.iter().position(|env| {
+        env.env_type == "pyenv"
+            && env.path.file_name().is_some_and(|name| name == version)
+    })
+}
+
+/// The conda (or micromamba) environment activated in the shell LazyEnv was
+/// started from. `$CONDA_PREFIX` is checked first, then the name in `$CONDA_DEFAULT_ENV`.
+pub fn active_conda_environment(environments: &[PythonEnvironment]) -> Option<usize> {
+    let conda_envs = || environments.iter().enumerate().filter(|(_, env)| matches!(&env.env_type[..], "conda" | "mamba"));
+    
+    if let Some(prefix) = std::env::var_os("CONDA_PREFIX")
+        && let Some((idx, _)) = conda_envs().find(|(_, env)| same_path(&env.path, Path::new(&prefix))) {
+        return Some(idx);
     }
     
-    Ok(environments)
+    let name = std::env::var("CONDA_DEFAULT_ENV").ok()?;
+    conda_envs()
+        .find(|(_, env)| env.name == format!("{}: {}", env.env_type, name) || same_path(&env.path, Path::new(&name)))
+        .map(|(idx, _)| idx)
+}
+
+/// When an environment directory was created, or last modified on platforms
+/// that don't record creation times.
+pub fn environment_created(env_path: &Path) -> Option<SystemTime> {
+    let metadata = fs::metadata(env_path).ok()?;
+    metadata.created().or_else(|_| metadata.modified()).ok()
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    let a = fs::canonicalize(a).unwrap_or_else(|_| a.to_path_buf());
+    let b = fs::canonicalize(b).unwrap_or_else(|_| b.to_path_buf());
+    a == b
 }
 
 fn detect_system_python(environments: &mut Vec<PythonEnvironment>) -> io::Result<()> {
     // Try to get system Python
-    let output = Command::new("python")
-        .args(["--version"])
-        .output();
+    let output = match output_with_timeout(
+        Command::new("python").args(["--version"]),
+        probe_timeout(),
+    ) {
+        Err(e) if e.kind() == io::ErrorKind::TimedOut => return Err(e),
+        output => output,
+    };
     
-    if let Ok(output) = output {
+    if let Ok(output) = output
+        && output.status.success() {
+        let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let version = if version.is_empty() {
+            String::from_utf8_lossy(&output.stderr).trim().to_string()
+        } else {
+            version
+        };
+        
+        // Get executable path
+        let output = output_with_timeout(
+            Command::new("python").args(["-c", "import sys; print(sys.executable)"]),
+            probe_timeout(),
+        )?;
+        
         if output.status.success() {
-            let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            let version = if version.is_empty() {
-                String::from_utf8_lossy(&output.stderr).trim().to_string()
-            } else {
-                version
-            };
-            
-            // Get executable path
-            let output = Command::new("python")
-                .args(["-c", "import sys; print(sys.executable)"])
-                .output()?;
+            let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
             
-            if output.status.success() {
-                let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                
-                environments.push(PythonEnvironment {
-                    name: "System Python".to_string(),
-                    path: PathBuf::from(path),
-                    python_version: version,
-                    env_type: "system".to_string(),
-                });
-            }
+            environments.push(PythonEnvironment {
+                name: "System Python".to_string(),
+                path: PathBuf::from(path),
+                python_version: version,
+                env_type: "system".to_string(),
+            });
         }
     }
     
     // Also try python3
-    let output = Command::new("python3")
-        .args(["--version"])
-        .output();
+    let output = match output_with_timeout(
+        Command::new("python3").args(["--version"]),
+        probe_timeout(),
+    ) {
+        Err(e) if e.kind() == io::ErrorKind::TimedOut => return Err(e),
+        output => output,
+    };
     
-    if let Ok(output) = output {
+    if let Ok(output) = output
+        && output.status.success() {
+        let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let version = if version.is_empty() {
+            String::from_utf8_lossy(&output.stderr).trim().to_string()
+        } else {
+            version
+        };
+        
+        // Get executable path
+        let output = output_with_timeout(
+            Command::new("python3").args(["-c", "import sys; print(sys.executable)"]),
+            probe_timeout(),
+        )?;
+        
         if output.status.success() {
-            let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            let version = if version.is_empty() {
-                String::from_utf8_lossy(&output.stderr).trim().to_string()
-            } else {
-                version
-            };
+            let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            let path_buf = PathBuf::from(path);
             
-            // Get executable path
-            let output = Command::new("python3")
-                .args(["-c", "import sys; print(sys.executable)"])
-                .output()?;
-            
-            if output.status.success() {
-                let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                let path_buf = PathBuf::from(path);
-                
-                // Check if this is different from the previous python
-                if environments.iter().all(|env| env.path != path_buf) {
-                    environments.push(PythonEnvironment {
-                        name: "System Python 3".to_string(),
-                        path: path_buf,
-                        python_version: version,
-                        env_type: "system".to_string(),
-                    });
-                }
+            // Check if this is different from the previous python
+            if environments.iter().all(|env| env.path != path_buf) {
+                environments.push(PythonEnvironment {
+                    name: "System Python 3".to_string(),
+                    path: path_buf,
+                    python_version: version,
+                    env_type: "system".to_string(),
+                });
             }
         }
     }
@@ -126,25 +554,22 @@ fn detect_venv_environments(environments: &mut Vec<PythonEnvironment>) -> io::Re
     
     // Check for environments in ~/.virtualenvs (common for virtualenvwrapper)
     let virtualenvs_dir = home_dir.join(".virtualenvs");
-    if virtualenvs_dir.is_dir() {
-        if let Ok(entries) = fs::read_dir(&virtualenvs_dir) {
-            for entry in entries.filter_map(Result::ok) {
-                let path = entry.path();
-                if path.is_dir() && is_virtualenv(&path) {
-                    if let Some(env) = create_environment_from_path(&path, "venv") {
-                        environments.push(env);
-                    }
-                }
+    if virtualenvs_dir.is_dir()
+        && let Ok(entries) = fs::read_dir(&virtualenvs_dir) {
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            if path.is_dir() && is_virtualenv(&path)
+                && let Some(env) = create_environment_from_path(&path, "venv") {
+                environments.push(env);
             }
         }
     }
     
     // Check for environments in ~/.venv (another common location)
     let venv_dir = home_dir.join(".venv");
-    if venv_dir.is_dir() && is_virtualenv(&venv_dir) {
-        if let Some(env) = create_environment_from_path(&venv_dir, "venv") {
-            environments.push(env);
-        }
+    if venv_dir.is_dir() && is_virtualenv(&venv_dir)
+        && let Some(env) = create_environment_from_path(&venv_dir, "venv") {
+        environments.push(env);
     }
     
     Ok(())
@@ -155,44 +580,28 @@ fn detect_pyenv_environments(environments: &mut Vec<PythonEnvironment>) -> io::R
     
     // Check for pyenv versions
     let pyenv_versions_dir = home_dir.join(".pyenv").join("versions");
-    if pyenv_versions_dir.is_dir() {
-        if let Ok(entries) = fs::read_dir(&pyenv_versions_dir) {
-            for entry in entries.filter_map(Result::ok) {
-                let path = entry.path();
-                if path.is_dir() {
-                    // Check if this is a Python installation
-                    let bin_dir = path.join("bin");
-                    let python_exec = bin_dir.join("python");
+    if pyenv_versions_dir.is_dir()
+        && let Ok(entries) = fs::read_dir(&pyenv_versions_dir) {
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            if path.is_dir() {
+                // Check if this is a Python installation
+                let bin_dir = path.join("bin");
+                let python_exec = bin_dir.join("python");
+                
+                if python_exec.exists()
+                    && let Some(version) = detected_version(&path) {
+                    let name = path.file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string();
                     
-                    if python_exec.exists() {
-                        let name = path.file_name()
-                            .unwrap_or_default()
-                            .to_string_lossy()
-                            .to_string();
-                        
-                        // Get Python version
-                        let output = Command::new(&python_exec)
-                            .args(["--version"])
-                            .output();
-                        
-                        if let Ok(output) = output {
-                            if output.status.success() {
-                                let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                                let version = if version.is_empty() {
-                                    String::from_utf8_lossy(&output.stderr).trim().to_string()
-                                } else {
-                                    version
-                                };
-                                
-                                environments.push(PythonEnvironment {
-                                    name: format!("pyenv: {}", name),
-                                    path: path.clone(),
-                                    python_version: version,
-                                    env_type: "pyenv".to_string(),
-                                });
-                            }
-                        }
-                    }
+                    environments.push(PythonEnvironment {
+                        name: format!("pyenv: {}", name),
+                        path: path.clone(),
+                        python_version: version,
+                        env_type: "pyenv".to_string(),
+                    });
                 }
             }
         }
@@ -202,61 +611,178 @@ fn detect_pyenv_environments(environments: &mut Vec<PythonEnvironment>) -> io::R
 }
 
 fn detect_conda_environments(environments: &mut Vec<PythonEnvironment>) -> io::Result<()> {
-    // Try to get conda environments using 'conda env list'
-    let output = Command::new("conda")
-        .args(["env", "list", "--json"])
-        .output();
+    for path in conda_env_list("conda")?.unwrap_or_default() {
+        push_conda_environment(environments, path, "conda");
+    }
     
-    if let Ok(output) = output {
-        if output.status.success() {
-            let json_output = String::from_utf8_lossy(&output.stdout);
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&json_output) {
-                if let Some(envs) = json.get("envs").and_then(|e| e.as_array()) {
-                    for env in envs {
-                        if let Some(path_str) = env.as_str() {
-                            let path = PathBuf::from(path_str);
-                            
-                            // Get the name from the path
-                            let name = path.file_name()
-                                .unwrap_or_default()
-                                .to_string_lossy()
-                                .to_string();
-                            
-                            // Check for Python executable
-                            let python_exec = path.join("bin").join("python");
-                            let python_exec = if python_exec.exists() {
-                                python_exec
-                            } else {
-                                path.join("python.exe") // Windows
-                            };
-                            
-                            if python_exec.exists() {
-                                // Get Python version
-                                let output = Command::new(&python_exec)
-                                    .args(["--version"])
-                                    .output();
-                                
-                                if let Ok(output) = output {
-                                    if output.status.success() {
-                                        let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                                        let version = if version.is_empty() {
-                                            String::from_utf8_lossy(&output.stderr).trim().to_string()
-                                        } else {
-                                            version
-                                        };
-                                        
-                                        environments.push(PythonEnvironment {
-                                            name: format!("conda: {}", name),
-                                            path: path.clone(),
-                                            python_version: version,
-                                            env_type: "conda".to_string(),
-                                        });
-                                    }
-                                }
+    Ok(())
+}
+
+/// micromamba has the same `env list --json` as conda. Setups that only load
+/// its shell hook may not have the binary on PATH, so the environments under
+/// `$MAMBA_ROOT_PREFIX` are picked up directly too.
+fn detect_micromamba_environments(environments: &mut Vec<PythonEnvironment>) -> io::Result<()> {
+    let mut paths = conda_env_list("micromamba")?.unwrap_or_default();
+    if let Some(root) = std::env::var_os("MAMBA_ROOT_PREFIX").map(PathBuf::from) {
+        if let Ok(entries) = fs::read_dir(root.join("envs")) {
+            paths.extend(entries.filter_map(Result::ok).map(|entry| entry.path()));
+        }
+        paths.push(root);
+    }
+    paths.sort();
+    paths.dedup();
+    
+    for path in paths {
+        push_conda_environment(environments, path, "mamba");
+    }
+    
+    Ok(())
+}
+
+/// Environment prefixes from `<tool> env list --json`. `None` if the tool
+/// isn't installed or its output can't be read; a hung tool is an error.
+fn conda_env_list(tool: &str) -> io::Result<Option<Vec<PathBuf>>> {
+    let output = match output_with_timeout(
+        Command::new(tool).args(["env", "list", "--json"]),
+        probe_timeout(),
+    ) {
+        Err(e) if e.kind() == io::ErrorKind::TimedOut => return Err(e),
+        Err(_) => return Ok(None),
+        Ok(output) if !output.status.success() => return Ok(None),
+        Ok(output) => output,
+    };
+    
+    let json_output = String::from_utf8_lossy(&output.stdout);
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(&json_output) else {
+        return Ok(None);
+    };
+    Ok(json.get("envs").and_then(|e| e.as_array()).map(|envs| {
+        envs.iter()
+            .filter_map(|env| env.as_str())
+            .map(PathBuf::from)
+            .collect()
+    }))
+}
+
+/// Add the conda-style environment at `path` if it has a working Python.
+/// `env_type` ("conda" or "mamba") also prefixes its name.
+fn push_conda_environment(environments: &mut Vec<PythonEnvironment>, path: PathBuf, env_type: &str) {
+    // Named environments live in `<root>/envs/<name>`; anything
+    // else is the root prefix, which conda calls "base"
+    let in_envs_dir = path.parent()
+        .and_then(|parent| parent.file_name())
+        .is_some_and(|dir| dir == "envs");
+    let name = if in_envs_dir {
+        path.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    } else {
+        "base".to_string()
+    };
+    
+    // Check for Python executable
+    let python_exec = path.join("bin").join("python");
+    let python_exec = if python_exec.exists() {
+        python_exec
+    } else {
+        path.join("python.exe") // Windows
+    };
+    
+    if python_exec.exists()
+        && let Some(version) = detected_version(&path) {
+        environments.push(PythonEnvironment {
+            name: format!("{}: {}", env_type, name),
+            path,
+            python_version: version,
+            env_type: env_type.to_string(),
+        });
+    }
+}
+
+fn detect_pipx_environments(environments: &mut Vec<PythonEnvironment>) -> io::Result<()> {
+    let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    
+    // PIPX_HOME wins; otherwise check the current and the legacy default locations
+    let pipx_homes = match std::env::var_os("PIPX_HOME") {
+        Some(pipx_home) => vec![PathBuf::from(pipx_home)],
+        None => vec![
+            home_dir.join(".local").join("share").join("pipx"),
+            home_dir.join(".local").join("pipx"),
+        ],
+    };
+    
+    for pipx_home in pipx_homes {
+        let venvs_dir = pipx_home.join("venvs");
+        if venvs_dir.is_dir()
+            && let Ok(entries) = fs::read_dir(&venvs_dir) {
+            for entry in entries.filter_map(Result::ok) {
+                let path = entry.path();
+                if path.is_dir() && is_virtualenv(&path)
+                    && let Some(mut env) = create_environment_from_path(&path, "pipx") {
+                    env.name = format!("pipx: {}", env.name);
+                    environments.push(env);
+                }
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+/// Hatch keeps virtual environments under its data directory as
+/// `env/virtual/<project>/<id>/<env>` (older releases drop the `<id>` level),
+/// so they're named `hatch: <project>/<env>`. Projects that configure their own
+/// environment directory are found by asking `hatch env find` in the current
+/// project; without the `hatch` binary that step is skipped.
+fn detect_hatch_environments(environments: &mut Vec<PythonEnvironment>) -> io::Result<()> {
+    let data_dir = std::env::var_os("HATCH_DATA_DIR")
+        .map(PathBuf::from)
+        .or_else(|| dirs::data_local_dir().map(|dir| dir.join("hatch")));
+    
+    if let Some(data_dir) = data_dir
+        && let Ok(projects) = fs::read_dir(data_dir.join("env").join("virtual")) {
+        for project in projects.filter_map(Result::ok) {
+            let project_name = project.file_name().to_string_lossy().to_string();
+            let mut dirs = vec![project.path()];
+            // Look two levels down for the optional id directory
+            for _ in 0..2 {
+                let mut next = Vec::new();
+                for dir in dirs {
+                    let Ok(entries) = fs::read_dir(&dir) else {
+                        continue;
+                    };
+                    for path in entries.filter_map(Result::ok).map(|entry| entry.path()).filter(|path| path.is_dir()) {
+                        if is_virtualenv(&path) {
+                            if let Some(mut env) = create_environment_from_path(&path, "hatch") {
+                                env.name = format!("hatch: {}/{}", project_name, env.name);
+                                environments.push(env);
                             }
+                        } else {
+                            next.push(path);
                         }
                     }
                 }
+                dirs = next;
+            }
+        }
+    }
+    
+    if Path::new("pyproject.toml").is_file()
+        && let Some(hatch) = find_on_path("hatch")
+        && let Ok(output) = output_with_timeout(Command::new(hatch).args(["env", "find"]), probe_timeout())
+        && output.status.success() {
+        let project_name = std::env::current_dir()
+            .ok()
+            .and_then(|dir| dir.file_name().map(|name| name.to_string_lossy().to_string()))
+            .unwrap_or_else(|| "project".to_string());
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let path = PathBuf::from(line.trim());
+            if is_virtualenv(&path)
+                && !environments.iter().any(|env| same_path(&env.path, &path))
+                && let Some(mut env) = create_environment_from_path(&path, "hatch") {
+                env.name = format!("hatch: {}/{}", project_name, env.name);
+                environments.push(env);
             }
         }
     }
@@ -269,12 +795,9 @@ fn detect_local_environments(environments: &mut Vec<PythonEnvironment>) -> io::R
     if let Ok(entries) = fs::read_dir(".") {
         for entry in entries.filter_map(Result::ok) {
             let path = entry.path();
-            if path.is_dir() {
-                if is_virtualenv(&path) {
-                    if let Some(env) = create_environment_from_path(&path, "venv") {
-                        environments.push(env);
-                    }
-                }
+            if path.is_dir() && is_virtualenv(&path)
+                && let Some(env) = create_environment_from_path(&path, "venv") {
+                environments.push(env);
             }
         }
     }
@@ -282,6 +805,41 @@ fn detect_local_environments(environments: &mut Vec<PythonEnvironment>) -> io::R
     Ok(())
 }
 
+/// Virtual environments whose interpreter is missing or won't run, e.g. after
+/// the Python they were created from was uninstalled. Regular detection skips
+/// most of these because their `bin/python` link no longer resolves.
+pub fn find_broken_venvs() -> Vec<PathBuf> {
+    let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
+    
+    let mut candidates = Vec::new();
+    for dir in [home_dir.join(".virtualenvs"), PathBuf::from(".")] {
+        if let Ok(entries) = fs::read_dir(&dir) {
+            candidates.extend(entries.filter_map(Result::ok).map(|entry| entry.path()));
+        }
+    }
+    candidates.push(home_dir.join(".venv"));
+    
+    let mut seen = HashSet::new();
+    candidates
+        .into_iter()
+        .filter(|path| path.join("pyvenv.cfg").is_file())
+        .filter(|path| seen.insert(fs::canonicalize(path).unwrap_or_else(|_| path.clone())))
+        .filter(|path| !interpreter_runs(path))
+        .collect()
+}
+
+/// Whether the environment's interpreter exists and answers `--version`.
+fn interpreter_runs(env_path: &Path) -> bool {
+    let python_path = if cfg!(windows) {
+        env_path.join("Scripts").join("python.exe")
+    } else {
+        env_path.join("bin").join("python")
+    };
+    
+    output_with_timeout(Command::new(&python_path).arg("--version"), probe_timeout())
+        .is_ok_and(|output| output.status.success())
+}
+
 fn is_virtualenv(path: &Path) -> bool {
     // Check for common virtualenv directory structure
     let bin_dir = if cfg!(windows) {
@@ -307,29 +865,7 @@ fn is_virtualenv(path: &Path) -> bool {
 
 fn create_environment_from_path(path: &Path, env_type: &str) -> Option<PythonEnvironment> {
     let name = path.file_name()?.to_string_lossy().to_string();
-    
-    // Get Python version
-    let python_path = if cfg!(windows) {
-        path.join("Scripts").join("python.exe")
-    } else {
-        path.join("bin").join("python")
-    };
-    
-    let output = Command::new(&python_path)
-        .args(["--version"])
-        .output()
-        .ok()?;
-    
-    let version = if output.status.success() {
-        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        if stdout.is_empty() {
-            String::from_utf8_lossy(&output.stderr).trim().to_string()
-        } else {
-            stdout
-        }
-    } else {
-        "Unknown".to_string()
-    };
+    let version = detected_version(path)?;
     
     Some(PythonEnvironment {
         name,
@@ -339,28 +875,349 @@ fn create_environment_from_path(path: &Path, env_type: &str) -> Option<PythonEnv
     })
 }
 
-pub fn list_packages(env_path: &Path) -> io::Result<Vec<Package>> {
-    let mut packages = Vec::new();
+/// The directory holding an environment's executables. System and pyenv
+/// entries point at an interpreter, so that's the directory it lives in.
+pub fn environment_bin_dir(env_path: &Path) -> PathBuf {
+    if env_path.is_file() {
+        env_path.parent().map_or_else(|| env_path.to_path_buf(), Path::to_path_buf)
+    } else if cfg!(windows) {
+        env_path.join("Scripts")
+    } else {
+        env_path.join("bin")
+    }
+}
+
+/// The interpreter of an environment: the entry itself for system and pyenv
+/// interpreters, otherwise the `python` in its bin directory.
+pub fn environment_python(env_path: &Path) -> PathBuf {
+    if env_path.is_file() {
+        env_path.to_path_buf()
+    } else if cfg!(windows) {
+        environment_bin_dir(env_path).join("python.exe")
+    } else {
+        environment_bin_dir(env_path).join("python")
+    }
+}
+
+/// Environment variables that activate `env` in a child process, the way its
+/// activate script (or `conda activate`) would.
+pub fn activation_vars(env: &PythonEnvironment) -> Vec<(&'static str, OsString)> {
+    let bin_dir = environment_bin_dir(&env.path);
+    let mut paths = vec![bin_dir];
+    if let Some(path) = std::env::var_os("PATH") {
+        paths.extend(std::env::split_paths(&path));
+    }
     
-    // Try to find pip in different locations
-    let possible_pip_paths = vec![
-        if cfg!(windows) {
-            env_path.join("Scripts").join("pip.exe")
-        } else {
-            env_path.join("bin").join("pip")
+    let mut vars = Vec::new();
+    if let Ok(path) = std::env::join_paths(paths) {
+        vars.push(("PATH", path));
+    }
+    match &env.env_type[..] {
+        "conda" | "mamba" => {
+            let name = env.name.split_once(": ").map_or(&env.name[..], |(_, name)| name);
+            vars.push(("CONDA_PREFIX", env.path.clone().into_os_string()));
+            vars.push(("CONDA_DEFAULT_ENV", OsString::from(name)));
         },
-        if cfg!(windows) {
-            env_path.join("Scripts").join("pip3.exe")
-        } else {
-            env_path.join("bin").join("pip3")
-        },
-        // For system Python, try to use the Python executable to run pip as a module
-        if cfg!(windows) {
-            env_path.join("python.exe")
+        "system" | "pyenv" => {},
+        _ => vars.push(("VIRTUAL_ENV", env.path.clone().into_os_string())),
+    }
+    vars
+}
+
+/// The command that activates `env` in the user's current shell, e.g.
+/// `source /path/.venv/bin/activate`. `None` for system and pyenv
+/// interpreters, which have nothing to activate.
+pub fn activation_command(env: &PythonEnvironment) -> Option<String> {
+    let quote = |path: &Path| {
+        let path = path.display().to_string();
+        if !path.contains([' ', '\'', '"', '$', '&', '(', ')']) {
+            path
+        } else if cfg!(windows) {
+            format!("\"{}\"", path)
         } else {
-            env_path.join("bin").join("python")
-        },
-    ];
+            format!("'{}'", path.replace('\'', "'\\''"))
+        }
+    };
+    
+    match &env.env_type[..] {
+        "system" | "pyenv" => None,
+        "conda" => Some(format!("conda activate {}", quote(&env.path))),
+        "mamba" => Some(format!("micromamba activate {}", quote(&env.path))),
+        // `activate` runs activate.bat in cmd and Activate.ps1 in PowerShell
+        _ if cfg!(windows) => Some(quote(&env.path.join("Scripts").join("activate"))),
+        _ => {
+            let shell = std::env::var("SHELL").unwrap_or_default();
+            let script = if shell.ends_with("fish") {
+                "activate.fish"
+            } else if shell.ends_with("csh") {
+                "activate.csh"
+            } else {
+                "activate"
+            };
+            Some(format!("source {}", quote(&env.path.join("bin").join(script))))
+        }
+    }
+}
+
+/// The version of a detected environment's interpreter, probed with the short
+/// detection timeout. Environments that are slow to answer are kept, marked
+/// pending for `probe_version`, rather than dropped; `None` if it can't run.
+fn detected_version(env_path: &Path) -> Option<String> {
+    match interpreter_version(env_path, detect_timeout()) {
+        Ok(version) => Some(version),
+        Err(e) if e.kind() == io::ErrorKind::TimedOut => Some(PENDING_VERSION.to_string()),
+        Err(_) => None,
+    }
+}
+
+/// Probe a pending environment's interpreter again, this time with the full
+/// probe timeout. `None` if it still doesn't answer.
+pub fn probe_version(env_path: &Path) -> Option<String> {
+    interpreter_version(env_path, probe_timeout()).ok()
+}
+
+/// The interpreter's `sys.path`, in search order. Empty entries (the
+/// current directory) are kept as they are.
+pub fn sys_path(env_path: &Path) -> Result<Vec<String>, PythonError> {
+    let output = output_with_timeout(
+        Command::new(environment_python(env_path)).args(["-c", "import sys, json; print(json.dumps(sys.path))"]),
+        probe_timeout(),
+    )?;
+    if !output.status.success() {
+        return Err(PythonError::Other(format!(
+            "Failed to read sys.path: {}",
+            String::from_utf8_lossy(&output.stderr).trim(),
+        )));
+    }
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+/// The output of the environment's `python --version`, or "Unknown" if it fails.
+fn interpreter_version(env_path: &Path, timeout: Duration) -> io::Result<String> {
+    let python_path = if cfg!(windows) {
+        // conda keeps python.exe in the prefix itself rather than in Scripts
+        let scripts_python = env_path.join("Scripts").join("python.exe");
+        if scripts_python.exists() { scripts_python } else { env_path.join("python.exe") }
+    } else {
+        env_path.join("bin").join("python")
+    };
+    
+    let output = output_with_timeout(
+        Command::new(&python_path).args(["--version"]),
+        timeout,
+    )?;
+    
+    let version = if output.status.success() {
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if stdout.is_empty() {
+            String::from_utf8_lossy(&output.stderr).trim().to_string()
+        } else {
+            stdout
+        }
+    } else {
+        "Unknown".to_string()
+    };
+    
+    Ok(version)
+}
+
+/// The site-packages directories of an environment directory.
+/// Empty for environments that point at an interpreter rather than a directory.
+fn site_packages_dirs(env_path: &Path) -> Vec<PathBuf> {
+    if cfg!(windows) {
+        let dir = env_path.join("Lib").join("site-packages");
+        return if dir.is_dir() { vec![dir] } else { Vec::new() };
+    }
+    
+    // lib/python3.X/site-packages
+    let Ok(entries) = fs::read_dir(env_path.join("lib")) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("python"))
+        .map(|entry| entry.path().join("site-packages"))
+        .filter(|dir| dir.is_dir())
+        .collect()
+}
+
+/// Whether pip is installed in the environment, going by its site-packages.
+/// `None` for environments without a site-packages directory of their own.
+pub fn has_pip(env_path: &Path) -> Option<bool> {
+    let dirs = site_packages_dirs(env_path);
+    if dirs.is_empty() {
+        return None;
+    }
+    Some(dirs.iter().any(|dir| dir.join("pip").is_dir()))
+}
+
+/// Install pip into an environment that lacks it with its own `python -m
+/// ensurepip`, which uses the pip wheel bundled with Python and needs no
+/// network access.
+pub fn bootstrap_pip(env_path: &Path) -> Result<(), PythonError> {
+    let output = output_with_timeout(
+        Command::new(environment_python(env_path)).args(["-m", "ensurepip", "--upgrade", "--default-pip"]),
+        operation_timeout(),
+    )?;
+    if output.status.success() {
+        return Ok(());
+    }
+    
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("No module named ensurepip") {
+        // Debian and Ubuntu split ensurepip out of the base python package
+        Err(PythonError::NotFound(
+            "This Python has no ensurepip module (on Debian/Ubuntu install python3-venv); recreate the environment with pip instead".to_string(),
+        ))
+    } else {
+        Err(PythonError::Other(format!("Failed to bootstrap pip: {}", stderr.trim())))
+    }
+}
+
+/// Count installed distributions by their metadata directories.
+/// Much cheaper than asking pip, since nothing has to be spawned.
+pub fn count_packages(env_path: &Path) -> Option<usize> {
+    let dirs = site_packages_dirs(env_path);
+    if dirs.is_empty() {
+        return None;
+    }
+    
+    let count = dirs
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.filter_map(Result::ok))
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.ends_with(".dist-info") || name.ends_with(".egg-info")
+        })
+        .count();
+    
+    Some(count)
+}
+
+/// The JSON array in pip's output, skipping anything printed before it such
+/// as deprecation warnings or a "new release available" notice. Every `[` is
+/// tried in turn because notices can start with one (`[notice] ...`); text
+/// after the array is ignored.
+fn parse_json_list(output: &str) -> serde_json::Result<Vec<serde_json::Value>> {
+    let error = match serde_json::from_str(output) {
+        Ok(list) => return Ok(list),
+        Err(e) => e,
+    };
+    output
+        .match_indices('[')
+        .find_map(|(idx, _)| serde_json::Deserializer::from_str(&output[idx..]).into_iter().next()?.ok())
+        .ok_or(error)
+}
+
+/// Installed packages, sorted by name. In conda environments this includes
+/// what conda installed, which pip can't see or remove (see `PackageManager`).
+pub fn list_packages(env_path: &Path) -> Result<Vec<Package>, PythonError> {
+    let mut packages = list_pip_packages(env_path)?;
+    // Without conda on PATH the pip view is still right as far as it goes
+    if is_conda_environment(env_path)
+        && let Ok(conda_packages) = list_conda_packages(env_path) {
+        for conda_package in conda_packages {
+            let name = normalize_package_name(&conda_package.name);
+            match packages.iter_mut().find(|pkg| normalize_package_name(&pkg.name) == name) {
+                Some(pkg) => pkg.manager = PackageManager::Conda,
+                None => packages.push(conda_package),
+            }
+        }
+        packages.sort_by_key(|pkg| pkg.name.to_lowercase());
+    }
+    Ok(packages)
+}
+
+/// Whether `env_path` is a conda environment, which keeps its package
+/// records in `conda-meta`.
+pub fn is_conda_environment(env_path: &Path) -> bool {
+    env_path.join("conda-meta").is_dir()
+}
+
+/// Packages conda installed in the environment, from `conda list --json`.
+/// That list also has what pip installed, under the `pypi` channel; those
+/// are left out.
+fn list_conda_packages(env_path: &Path) -> Result<Vec<Package>, PythonError> {
+    let output = run_conda(env_path, &["list", "--json"], probe_timeout())?;
+    if !output.status.success() {
+        return Err(PythonError::Other(format!(
+            "Failed to list conda packages: {}",
+            String::from_utf8_lossy(&output.stderr).trim(),
+        )));
+    }
+    
+    let json_output = String::from_utf8_lossy(&output.stdout);
+    Ok(parse_json_list(&json_output)?
+        .iter()
+        .filter(|pkg| pkg.get("channel").and_then(|c| c.as_str()) != Some("pypi"))
+        .filter_map(|pkg| Some(Package {
+            name: pkg.get("name")?.as_str()?.to_string(),
+            version: pkg.get("version")?.as_str()?.to_string(),
+            summary: String::new(),
+            manager: PackageManager::Conda,
+        }))
+        .collect())
+}
+
+/// Remove a conda-managed package with `conda remove`, which also removes
+/// the packages that depend on it.
+pub fn conda_remove_package(env_path: &Path, package_name: &str) -> Result<(), PythonError> {
+    let output = run_conda(env_path, &["remove", "-y", package_name], operation_timeout())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(PythonError::Other(format!(
+            "Failed to remove package: {}",
+            String::from_utf8_lossy(&output.stderr).trim(),
+        )))
+    }
+}
+
+/// The conda environment at `env_path` as `conda env export` writes it, with
+/// every package pinned to its exact build. With `from_history`, only the
+/// specs the user asked conda for are listed, which travels better between
+/// platforms but leaves out pip packages.
+pub fn conda_env_export(env_path: &Path, from_history: bool) -> Result<String, PythonError> {
+    if !is_conda_environment(env_path) {
+        return Err(PythonError::Unsupported("Only conda environments can be exported to environment.yml".to_string()));
+    }
+    
+    let mut args = vec!["env", "export"];
+    if from_history {
+        args.push("--from-history");
+    }
+    let output = run_conda(env_path, &args, operation_timeout())?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(PythonError::Other(format!(
+            "Failed to export environment: {}",
+            String::from_utf8_lossy(&output.stderr).trim(),
+        )))
+    }
+}
+
+/// Run a conda subcommand against the environment at `env_path` with the
+/// first of `CONDA_TOOLS` that's installed.
+fn run_conda(env_path: &Path, args: &[&str], timeout: Duration) -> io::Result<Output> {
+    for tool in CONDA_TOOLS {
+        match output_with_timeout(Command::new(tool).args(args).arg("-p").arg(env_path), timeout) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            result => return result,
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::NotFound, "Neither conda nor micromamba is installed"))
+}
+
+/// Packages pip sees in the environment, without the conda-only ones
+/// `list_packages` adds for conda environments.
+pub fn list_pip_packages(env_path: &Path) -> Result<Vec<Package>, PythonError> {
+    let mut packages = Vec::new();
+    
+    // Try to find pip in different locations
+    let possible_pip_paths = pip_candidates(env_path);
     
     for pip_path in possible_pip_paths {
         if !pip_path.exists() {
@@ -368,20 +1225,22 @@ pub fn list_packages(env_path: &Path) -> io::Result<Vec<Package>> {
         }
         
         // If this is a Python executable, use it to run pip as a module
-        let output = if pip_path.file_name().map_or(false, |name| name == "python" || name == "python.exe") {
-            Command::new(&pip_path)
-                .args(["-m", "pip", "list", "--format=json"])
-                .output()
+        let output = if pip_path.file_name().is_some_and(|name| name == "python" || name == "python.exe") {
+            output_with_timeout(
+                Command::new(&pip_path).args(["-m", "pip", "list", "--format=json", "--disable-pip-version-check"]),
+                probe_timeout(),
+            )
         } else {
-            Command::new(&pip_path)
-                .args(["list", "--format=json"])
-                .output()
+            output_with_timeout(
+                Command::new(&pip_path).args(["list", "--format=json", "--disable-pip-version-check"]),
+                probe_timeout(),
+            )
         };
         
         match output {
             Ok(output) if output.status.success() => {
                 let json_output = String::from_utf8_lossy(&output.stdout);
-                match serde_json::from_str::<Vec<serde_json::Value>>(&json_output) {
+                match parse_json_list(&json_output) {
                     Ok(pkg_list) => {
                         for pkg in pkg_list {
                             if let (Some(name), Some(version)) = (
@@ -395,17 +1254,21 @@ pub fn list_packages(env_path: &Path) -> io::Result<Vec<Package>> {
                                         .and_then(|s| s.as_str())
                                         .unwrap_or("")
                                         .to_string(),
+                                    manager: PackageManager::Pip,
                                 });
                             }
                         }
                         return Ok(packages);
                     },
-                    Err(e) => {
-                        eprintln!("Warning: Failed to parse pip output: {}", e);
-                        // Try the next pip path
+                    Err(_) => {
+                        // Unparseable output; try the next pip path
                     }
                 }
             },
+            // A cancelled pip shouldn't be retried with the next one
+            Err(e) if matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::Interrupted) => {
+                return Err(e.into());
+            },
             _ => {
                 // Try the next pip path
             }
@@ -421,28 +1284,14 @@ pub fn list_packages(env_path: &Path) -> io::Result<Vec<Package>> {
     };
     
     if python_path.exists() {
-        let script = r#"
-import sys
-import json
-import pkg_resources
-
-packages = []
-for pkg in pkg_resources.working_set:
-    packages.append({
-        "name": pkg.project_name,
-        "version": pkg.version,
-        "summary": getattr(pkg, "summary", "")
-    })
-print(json.dumps(packages))
-"#;
-        
-        let output = Command::new(&python_path)
-            .args(["-c", script])
-            .output()?;
+        let output = output_with_timeout(
+            Command::new(&python_path).args(["-c", LIST_PACKAGES_SCRIPT]),
+            probe_timeout(),
+        )?;
         
         if output.status.success() {
             let json_output = String::from_utf8_lossy(&output.stdout);
-            if let Ok(pkg_list) = serde_json::from_str::<Vec<serde_json::Value>>(&json_output) {
+            if let Ok(pkg_list) = parse_json_list(&json_output) {
                 for pkg in pkg_list {
                     if let (Some(name), Some(version)) = (
                         pkg.get("name").and_then(|n| n.as_str()),
@@ -455,6 +1304,7 @@ print(json.dumps(packages))
                                 .and_then(|s| s.as_str())
                                 .unwrap_or("")
                                 .to_string(),
+                            manager: PackageManager::Pip,
                         });
                     }
                 }
@@ -467,62 +1317,290 @@ print(json.dumps(packages))
     Ok(packages)
 }
 
-pub fn list_global_packages() -> io::Result<Vec<Package>> {
+/// Latest available version for every outdated package, keyed by the
+/// normalized package name (see `normalize_package_name`). Queries the
+/// package index, so this can be slow and needs network access.
+pub fn list_outdated_packages(env_path: &Path, include_pre: bool) -> Result<HashMap<String, String>, PythonError> {
+    let mut args = vec!["list", "--outdated", "--format=json", "--disable-pip-version-check"];
+    // Without --pre pip only reports stable releases as the latest version
+    if include_pre {
+        args.push("--pre");
+    }
+    let output = pip_output(
+        env_path,
+        &args,
+        operation_timeout(),
+        "check for outdated packages",
+    )?;
+    
+    let json_output = String::from_utf8_lossy(&output.stdout);
+    let pkg_list = parse_json_list(&json_output)?;
+    
+    let outdated = pkg_list
+        .iter()
+        .filter_map(|pkg| {
+            let name = pkg.get("name").and_then(|n| n.as_str())?;
+            let latest = pkg.get("latest_version").and_then(|v| v.as_str())?;
+            Some((normalize_package_name(name), latest.to_string()))
+        })
+        .collect();
+    
+    Ok(outdated)
+}
+
+/// Versions of `package_name` the configured indexes offer, newest first, from
+/// `pip index versions` (pip 21.2+). Needs the network; failing to reach the
+/// index is an error (see `cached_versions` for an offline fallback).
+pub fn available_versions(env_path: &Path, package_name: &str, options: &InstallOptions) -> Result<Vec<String>, PythonError> {
+    // Only the index, --pre and network flags mean anything to `pip index`
+    let options = InstallOptions {
+        upgrade: false,
+        break_system_packages: false,
+        extra_args: index_pip_args(&options.extra_args),
+        ..options.clone()
+    };
+    let args = options.with_args(&["index", "versions", "--disable-pip-version-check", package_name]);
+    let output = pip_output(env_path, &as_strs(&args), operation_timeout(), "list available versions")?;
+    
+    // "Available versions: 2.32.3, 2.32.2, ..."
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let versions: Vec<String> = stdout
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Available versions:"))
+        .map(|list| list.split(',').map(str::trim).filter(|v| !v.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default();
+    if versions.is_empty() {
+        return Err(PythonError::NotFound(format!("No versions of '{}' found on the index", package_name)));
+    }
+    Ok(versions)
+}
+
+/// pip's general options, which every pip command accepts, with whether each
+/// takes a value
+const GENERAL_PIP_OPTIONS: [(&str, bool); 16] = [
+    ("--proxy", true), ("--cert", true), ("--client-cert", true), ("--trusted-host", true),
+    ("--timeout", true), ("--retries", true), ("--cache-dir", true), ("--log", true),
+    ("--keyring-provider", true), ("--use-feature", true), ("--use-deprecated", true), ("--isolated", false),
+    ("--no-cache-dir", false), ("--no-input", false), ("--no-color", false), ("--verbose", false),
+];
+
+/// Options for finding packages that `pip index versions` and `pip download`
+/// accept on top of the general ones, besides those `InstallOptions` sets itself
+const INDEX_PIP_OPTIONS: [(&str, bool); 10] = [
+    ("--find-links", true), ("-f", true), ("--platform", true), ("--python-version", true),
+    ("--implementation", true), ("--abi", true), ("--only-binary", true), ("--no-binary", true),
+    ("--no-index", false), ("--prefer-binary", false),
+];
+
+/// The part of `extra_args` that's one of the `known` options. Install-only
+/// options such as `--no-deps` or `--user` would make other commands fail, so
+/// they're dropped along with their values.
+fn known_pip_args(extra_args: &[String], known: &[&[(&str, bool)]]) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut args = extra_args.iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, _)) => (flag, true),
+            None => (arg.as_str(), false),
+        };
+        // Anything else is dropped; an unknown option's value is too, when the loop reaches it
+        if let Some(&(_, takes_value)) = known.iter().flat_map(|options| options.iter()).find(|(option, _)| *option == flag) {
+            kept.push(arg.clone());
+            if takes_value && !inline_value {
+                kept.extend(args.next().cloned());
+            }
+        }
+    }
+    kept
+}
+
+/// The part of `extra_args` that any pip command accepts, e.g. for `pip uninstall`.
+fn general_pip_args(extra_args: &[String]) -> Vec<String> {
+    known_pip_args(extra_args, &[&GENERAL_PIP_OPTIONS])
+}
+
+/// The part of `extra_args` that `pip index versions` and `pip download` accept.
+fn index_pip_args(extra_args: &[String]) -> Vec<String> {
+    known_pip_args(extra_args, &[&GENERAL_PIP_OPTIONS, &INDEX_PIP_OPTIONS])
+}
+
+/// Versions of `package_name` that pip has wheels of in its local cache, newest
+/// first, from `pip cache list` (pip 20.1+). Works offline, but only knows
+/// versions that were built here before.
+pub fn cached_versions(env_path: &Path, package_name: &str) -> Result<Vec<String>, PythonError> {
+    let output = pip_output(
+        env_path,
+        &["cache", "list", package_name, "--format=abspath", "--disable-pip-version-check"],
+        probe_timeout(),
+        "list cached wheels",
+    )?;
+    
+    // e.g. /home/me/.cache/pip/wheels/ab/cd/Flask_Login-0.6.3-py3-none-any.whl
+    let name = normalize_package_name(package_name);
+    let mut versions: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| Path::new(line.trim()).file_name()?.to_str()?.strip_suffix(".whl").map(str::to_string))
+        .filter_map(|wheel| {
+            let mut parts = wheel.splitn(3, '-');
+            let (dist, version) = (parts.next()?, parts.next()?);
+            (normalize_package_name(dist) == name).then(|| version.to_string())
+        })
+        .collect();
+    versions.sort_by(|a, b| version::compare(b, a).unwrap_or(Ordering::Equal));
+    versions.dedup();
+    Ok(versions)
+}
+
+/// `--hash=sha256:…` options for a pinned `requirement`, from `pip download
+/// --no-deps` into a scratch directory and `pip hash` on what it fetched.
+/// Only covers the files pip picks for this platform, which is what a
+/// `--require-hashes` install here will ask for. Needs the network.
+pub fn requirement_hashes(env_path: &Path, requirement: &str, options: &InstallOptions) -> Result<Vec<String>, PythonError> {
+    // Only the index and config flags mean anything to `pip download`
+    let options = InstallOptions {
+        upgrade: false,
+        break_system_packages: false,
+        pre: false,
+        extra_args: index_pip_args(&options.extra_args),
+        ..options.clone()
+    };
+    let download_dir = std::env::temp_dir().join(format!(
+        "lazyenv-hashes-{}-{}",
+        std::process::id(),
+        normalize_package_name(requirement.split("==").next().unwrap_or(requirement)),
+    ));
+    fs::create_dir_all(&download_dir)?;
+    
+    let result = download_hashes(env_path, requirement, &options, &download_dir);
+    let _ = fs::remove_dir_all(&download_dir);
+    result
+}
+
+fn download_hashes(env_path: &Path, requirement: &str, options: &InstallOptions, download_dir: &Path) -> Result<Vec<String>, PythonError> {
+    let dir = download_dir.to_string_lossy();
+    let args = options.with_args(&["download", "--no-deps", "--disable-pip-version-check", "-d", &dir, requirement]);
+    pip_output(env_path, &as_strs(&args), operation_timeout(), "download package")?;
+    
+    let mut hashes = Vec::new();
+    for entry in fs::read_dir(download_dir)? {
+        let file = entry?.path().to_string_lossy().into_owned();
+        let output = pip_output(env_path, &["hash", "--disable-pip-version-check", &file], probe_timeout(), "hash package")?;
+        hashes.extend(
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter_map(|line| line.trim().strip_prefix("--hash=").map(|hash| format!("--hash={}", hash))),
+        );
+    }
+    if hashes.is_empty() {
+        return Err(PythonError::Other(format!("pip downloaded nothing to hash for {}", requirement)));
+    }
+    hashes.sort();
+    hashes.dedup();
+    Ok(hashes)
+}
+
+/// Normalized names of the packages nothing else in the environment depends
+/// on. pip doesn't record what was installed explicitly, but these "leaves"
+/// are almost always what the user asked for.
+pub fn list_leaf_packages(env_path: &Path) -> Result<HashSet<String>, PythonError> {
+    let output = pip_output(
+        env_path,
+        &["list", "--not-required", "--format=json", "--disable-pip-version-check"],
+        probe_timeout(),
+        "list leaf packages",
+    )?;
+    
+    let json_output = String::from_utf8_lossy(&output.stdout);
+    let pkg_list = parse_json_list(&json_output)?;
+    
+    let leaves = pkg_list
+        .iter()
+        .filter_map(|pkg| pkg.get("name").and_then(|n| n.as_str()))
+        .map(normalize_package_name)
+        .collect();
+    
+    Ok(leaves)
+}
+
+/// `(total, explicit)` counts of what pip installed: every package, and the
+/// ones nothing else depends on (`pip list --not-required`), which are
+/// usually the ones installed on purpose.
+pub fn dependency_counts(env_path: &Path) -> Result<(usize, usize), PythonError> {
+    let total = list_pip_packages(env_path)?.len();
+    let explicit = list_leaf_packages(env_path)?.len();
+    Ok((total, explicit))
+}
+
+/// The environment's `pip freeze` output, one requirement per line.
+pub fn freeze_requirements(env_path: &Path) -> Result<String, PythonError> {
+    let output = pip_output(
+        env_path,
+        &["freeze", "--disable-pip-version-check"],
+        probe_timeout(),
+        "freeze requirements",
+    )?;
+    
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+pub fn list_global_packages() -> Result<Vec<Package>, PythonError> {
     let mut packages = Vec::new();
     
     // Try with pip
-    let output = Command::new("pip")
-        .args(["list", "--format=json"])
-        .output();
+    let output = output_with_timeout(
+        Command::new("pip").args(["list", "--format=json", "--disable-pip-version-check"]),
+        probe_timeout(),
+    );
     
-    if let Ok(output) = output {
-        if output.status.success() {
-            let json_output = String::from_utf8_lossy(&output.stdout);
-            if let Ok(pkg_list) = serde_json::from_str::<Vec<serde_json::Value>>(&json_output) {
-                for pkg in pkg_list {
-                    if let (Some(name), Some(version)) = (
-                        pkg.get("name").and_then(|n| n.as_str()),
-                        pkg.get("version").and_then(|v| v.as_str()),
-                    ) {
-                        packages.push(Package {
-                            name: name.to_string(),
-                            version: version.to_string(),
-                            summary: pkg.get("summary")
-                                .and_then(|s| s.as_str())
-                                .unwrap_or("")
-                                .to_string(),
-                        });
-                    }
+    if let Ok(output) = output
+        && output.status.success() {
+        let json_output = String::from_utf8_lossy(&output.stdout);
+        if let Ok(pkg_list) = parse_json_list(&json_output) {
+            for pkg in pkg_list {
+                if let (Some(name), Some(version)) = (
+                    pkg.get("name").and_then(|n| n.as_str()),
+                    pkg.get("version").and_then(|v| v.as_str()),
+                ) {
+                    packages.push(Package {
+                        name: name.to_string(),
+                        version: version.to_string(),
+                        summary: pkg.get("summary")
+                            .and_then(|s| s.as_str())
+                            .unwrap_or("")
+                            .to_string(),
+                        manager: PackageManager::Pip,
+                    });
                 }
             }
-            
-            return Ok(packages);
         }
+        
+        return Ok(packages);
     }
     
     // Try with pip3 if pip failed
-    let output = Command::new("pip3")
-        .args(["list", "--format=json"])
-        .output();
+    let output = output_with_timeout(
+        Command::new("pip3").args(["list", "--format=json", "--disable-pip-version-check"]),
+        probe_timeout(),
+    );
     
-    if let Ok(output) = output {
-        if output.status.success() {
-            let json_output = String::from_utf8_lossy(&output.stdout);
-            if let Ok(pkg_list) = serde_json::from_str::<Vec<serde_json::Value>>(&json_output) {
-                for pkg in pkg_list {
-                    if let (Some(name), Some(version)) = (
-                        pkg.get("name").and_then(|n| n.as_str()),
-                        pkg.get("version").and_then(|v| v.as_str()),
-                    ) {
-                        packages.push(Package {
-                            name: name.to_string(),
-                            version: version.to_string(),
-                            summary: pkg.get("summary")
-                                .and_then(|s| s.as_str())
-                                .unwrap_or("")
-                                .to_string(),
-                        });
-                    }
+    if let Ok(output) = output
+        && output.status.success() {
+        let json_output = String::from_utf8_lossy(&output.stdout);
+        if let Ok(pkg_list) = parse_json_list(&json_output) {
+            for pkg in pkg_list {
+                if let (Some(name), Some(version)) = (
+                    pkg.get("name").and_then(|n| n.as_str()),
+                    pkg.get("version").and_then(|v| v.as_str()),
+                ) {
+                    packages.push(Package {
+                        name: name.to_string(),
+                        version: version.to_string(),
+                        summary: pkg.get("summary")
+                            .and_then(|s| s.as_str())
+                            .unwrap_or("")
+                            .to_string(),
+                        manager: PackageManager::Pip,
+                    });
                 }
             }
         }
@@ -530,47 +1608,33 @@ pub fn list_global_packages() -> io::Result<Vec<Package>> {
     
     // If both pip and pip3 failed, try using Python directly
     if packages.is_empty() {
-        let script = r#"
-import sys
-import json
-import pkg_resources
-
-packages = []
-for pkg in pkg_resources.working_set:
-    packages.append({
-        "name": pkg.project_name,
-        "version": pkg.version,
-        "summary": getattr(pkg, "summary", "")
-    })
-print(json.dumps(packages))
-"#;
-        
         for python_cmd in &["python", "python3"] {
-            let output = Command::new(python_cmd)
-                .args(["-c", script])
-                .output();
+            let output = output_with_timeout(
+                Command::new(python_cmd).args(["-c", LIST_PACKAGES_SCRIPT]),
+                probe_timeout(),
+            );
             
-            if let Ok(output) = output {
-                if output.status.success() {
-                    let json_output = String::from_utf8_lossy(&output.stdout);
-                    if let Ok(pkg_list) = serde_json::from_str::<Vec<serde_json::Value>>(&json_output) {
-                        for pkg in pkg_list {
-                            if let (Some(name), Some(version)) = (
-                                pkg.get("name").and_then(|n| n.as_str()),
-                                pkg.get("version").and_then(|v| v.as_str()),
-                            ) {
-                                packages.push(Package {
-                                    name: name.to_string(),
-                                    version: version.to_string(),
-                                    summary: pkg.get("summary")
-                                        .and_then(|s| s.as_str())
-                                        .unwrap_or("")
-                                        .to_string(),
-                                });
-                            }
+            if let Ok(output) = output
+                && output.status.success() {
+                let json_output = String::from_utf8_lossy(&output.stdout);
+                if let Ok(pkg_list) = parse_json_list(&json_output) {
+                    for pkg in pkg_list {
+                        if let (Some(name), Some(version)) = (
+                            pkg.get("name").and_then(|n| n.as_str()),
+                            pkg.get("version").and_then(|v| v.as_str()),
+                        ) {
+                            packages.push(Package {
+                                name: name.to_string(),
+                                version: version.to_string(),
+                                summary: pkg.get("summary")
+                                    .and_then(|s| s.as_str())
+                                    .unwrap_or("")
+                                    .to_string(),
+                                manager: PackageManager::Pip,
+                            });
                         }
-                        break;
                     }
+                    break;
                 }
             }
         }
@@ -579,103 +1643,406 @@ print(json.dumps(packages))
     Ok(packages)
 }
 
-pub fn create_environment(name: &str) -> io::Result<PythonEnvironment> {
+/// Where `create_environment` puts its environments.
+fn virtualenvs_dir() -> PathBuf {
     let home_dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
-    let venv_dir = home_dir.join(".virtualenvs").join(name);
+    home_dir.join(".virtualenvs")
+}
+
+/// Where `create_environment` puts an environment called `name`. The name
+/// must be a single directory name: joining `.`, `..` or an absolute path
+/// would point somewhere else entirely, which an overwrite then deletes.
+pub fn environment_dir(name: &str) -> Result<PathBuf, PythonError> {
+    let problem = if name.trim().is_empty() {
+        Some("it is empty")
+    } else if name == "." || name == ".." {
+        Some("it refers to a directory itself")
+    } else if name.contains(['/', '\\']) || Path::new(name).is_absolute() {
+        Some("it contains a path separator")
+    } else {
+        None
+    };
+    match problem {
+        Some(problem) => Err(PythonError::Other(format!("'{}' isn't a valid environment name: {}", name, problem))),
+        None => Ok(virtualenvs_dir().join(name)),
+    }
+}
+
+/// Delete an existing environment at `venv_dir` so `create_environment` can
+/// build a new one there. Refuses anything that isn't a virtualenv directly
+/// inside `~/.virtualenvs`.
+pub fn delete_environment_for_overwrite(venv_dir: &Path) -> Result<(), PythonError> {
+    if venv_dir.parent() != Some(virtualenvs_dir().as_path()) {
+        return Err(PythonError::Other(format!("{} isn't inside {}", venv_dir.display(), virtualenvs_dir().display())));
+    }
+    if !is_virtualenv(venv_dir) {
+        return Err(PythonError::Other(format!("{} isn't a virtualenv; remove it yourself", venv_dir.display())));
+    }
+    delete_environment(venv_dir)
+}
+
+/// Create `~/.virtualenvs/<name>` with `python` (or whatever `python` is on
+/// PATH when `None`), using `backend`.
+pub fn create_environment(name: &str, python: Option<&Path>, backend: CreationBackend) -> Result<PythonEnvironment, PythonError> {
+    let venv_dir = environment_dir(name)?;
     
     // Create the .virtualenvs directory if it doesn't exist
-    let virtualenvs_dir = home_dir.join(".virtualenvs");
-    if !virtualenvs_dir.exists() {
-        fs::create_dir_all(&virtualenvs_dir)?;
+    if let Some(virtualenvs_dir) = venv_dir.parent()
+        && !virtualenvs_dir.exists() {
+        fs::create_dir_all(virtualenvs_dir)?;
     }
     
-    let output = Command::new("python")
-        .args(["-m", "venv", venv_dir.to_str().unwrap()])
-        .output()?;
+    // Anything we create is removed again if a later step fails, so a failed
+    // creation never leaves a half-built environment behind. A directory that
+    // was already there is left alone.
+    let existed = venv_dir.exists();
+    let result = match virtualenv_tool().filter(|_| backend == CreationBackend::Virtualenv) {
+        Some(virtualenv) => build_with_virtualenv(&venv_dir, &virtualenv, python),
+        None => build_environment(&venv_dir, python.unwrap_or(Path::new("python"))),
+    };
+    if result.is_err() && !existed {
+        let _ = fs::remove_dir_all(&venv_dir);
+    }
+    result
+}
+
+/// Run `python -m venv` into `venv_dir` and check that the result is usable.
+/// Venvs embed their own path in scripts, so they're built in place rather
+/// than in a temporary directory that is renamed afterwards.
+fn build_environment(venv_dir: &Path, python: &Path) -> Result<PythonEnvironment, PythonError> {
+    let output = output_with_timeout(
+        // Passed as an OsStr so spaces and non-UTF-8 names arrive intact
+        Command::new(python).args(["-m", "venv"]).arg(venv_dir),
+        operation_timeout(),
+    )
+    .map_err(|e| PythonError::io(format_args!("Failed to run {} -m venv", python.display()), e))?;
     
+    check_built_environment(venv_dir, &output)
+}
+
+/// Like `build_environment`, but with the `virtualenv` tool. Without an
+/// explicit interpreter virtualenv picks its own default.
+fn build_with_virtualenv(venv_dir: &Path, virtualenv: &Path, python: Option<&Path>) -> Result<PythonEnvironment, PythonError> {
+    let mut command = Command::new(virtualenv);
+    if let Some(python) = python {
+        command.arg("--python").arg(python);
+    }
+    let output = output_with_timeout(command.arg(venv_dir), operation_timeout())
+        .map_err(|e| PythonError::io("Failed to run virtualenv", e))?;
+    
+    check_built_environment(venv_dir, &output)
+}
+
+/// The `virtualenv` executable on PATH, if it's installed.
+pub fn virtualenv_tool() -> Option<PathBuf> {
+    find_on_path("virtualenv")
+}
+
+/// Check that the tool that built `venv_dir` succeeded and left a usable environment.
+fn check_built_environment(venv_dir: &Path, output: &Output) -> Result<PythonEnvironment, PythonError> {
     if !output.status.success() {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
+        return Err(PythonError::Other(
             format!("Failed to create environment: {}", String::from_utf8_lossy(&output.stderr)),
         ));
     }
     
-    if let Some(env) = create_environment_from_path(&venv_dir, "venv") {
-        Ok(env)
-    } else {
-        Err(io::Error::new(
-            io::ErrorKind::Other,
-            "Failed to create environment",
-        ))
+    if !is_virtualenv(venv_dir) {
+        return Err(PythonError::Other(
+            "Failed to create environment: python or the activate script is missing".to_string(),
+        ));
+    }
+    
+    match create_environment_from_path(venv_dir, "venv") {
+        Some(env) if env.python_version != "Unknown" => Ok(env),
+        _ => Err(PythonError::Other(
+            "Failed to create environment: the new interpreter did not run".to_string(),
+        )),
     }
 }
 
-pub fn delete_environment(env_path: &Path) -> io::Result<()> {
-    fs::remove_dir_all(env_path)
+/// Repair a virtual environment whose interpreter broke, e.g. after the base
+/// Python was upgraded or moved, with `python -m venv --upgrade`. Installed
+/// packages survive as long as the replacement interpreter has the same
+/// minor version.
+pub fn repair_venv(venv_dir: &Path) -> Result<PythonEnvironment, PythonError> {
+    let python = base_interpreter(venv_dir)?;
+    let output = output_with_timeout(
+        Command::new(&python).args(["-m", "venv", "--upgrade"]).arg(venv_dir),
+        operation_timeout(),
+    )
+    .map_err(|e| PythonError::io(format_args!("Failed to run {} -m venv", python.display()), e))?;
+    
+    if !output.status.success() {
+        return Err(PythonError::Other(
+            format!("Failed to repair environment: {}", String::from_utf8_lossy(&output.stderr)),
+        ));
+    }
+    
+    match create_environment_from_path(venv_dir, "venv") {
+        Some(env) if is_virtualenv(venv_dir) && env.python_version != "Unknown" => Ok(env),
+        _ => Err(PythonError::Other(
+            "Failed to repair environment: the interpreter still doesn't run".to_string(),
+        )),
+    }
 }
 
-pub fn install_package(env_path: &Path, package_name: &str) -> io::Result<()> {
-    // Try to find pip in different locations
-    let possible_pip_paths = vec![
-        if cfg!(windows) {
-            env_path.join("Scripts").join("pip.exe")
-        } else {
-            env_path.join("bin").join("pip")
-        },
-        if cfg!(windows) {
-            env_path.join("Scripts").join("pip3.exe")
-        } else {
-            env_path.join("bin").join("pip3")
-        },
-        // For system Python, try to use the Python executable to run pip as a module
-        if cfg!(windows) {
-            env_path.join("python.exe")
-        } else {
-            env_path.join("bin").join("python")
-        },
-    ];
+/// The interpreter to rebuild a venv from, going by its `pyvenv.cfg`: the
+/// same minor version from pyenv or PATH, then the python in its `home`
+/// directory, then `python3` on PATH.
+fn base_interpreter(venv_dir: &Path) -> Result<PathBuf, PythonError> {
+    let config = fs::read_to_string(venv_dir.join("pyvenv.cfg"))?;
+    let value = |key: &str| {
+        config.lines().find_map(|line| {
+            let (name, value) = line.split_once('=')?;
+            (name.trim() == key).then(|| value.trim().to_string())
+        })
+    };
     
-    for pip_path in possible_pip_paths {
-        if !pip_path.exists() {
-            continue;
+    // virtualenv writes version_info instead of version
+    let minor_version = value("version")
+        .or_else(|| value("version_info"))
+        .and_then(|version| release_components(&version))
+        .filter(|components| components.len() >= 2)
+        .map(|components| format!("{}.{}", components[0], components[1]));
+    if let Some(version) = minor_version
+        && let Ok(python) = resolve_interpreter(&version) {
+        return Ok(python);
+    }
+    
+    if let Some(home) = value("home") {
+        let names: &[&str] = if cfg!(windows) { &["python.exe"] } else { &["python3", "python"] };
+        if let Some(python) = names.iter().map(|name| Path::new(&home).join(name)).find(|python| python.is_file()) {
+            return Ok(python);
         }
-        
-        // If this is a Python executable, use it to run pip as a module
-        let output = if pip_path.file_name().map_or(false, |name| name == "python" || name == "python.exe") {
-            Command::new(&pip_path)
-                .args(["-m", "pip", "install", package_name])
-                .output()
+    }
+    
+    resolve_interpreter("python3")
+}
+
+/// Find the interpreter for a spec from the config or the create dialog: a
+/// path, a command on PATH such as `python3.11`, or a bare version like `3.11`
+/// which is looked up in pyenv first and then as `python3.11` on PATH.
+pub fn resolve_interpreter(spec: &str) -> Result<PathBuf, PythonError> {
+    let spec = spec.trim();
+    let path = Path::new(spec);
+    
+    if path.is_absolute() || path.components().count() > 1 {
+        return if path.is_file() {
+            Ok(path.to_path_buf())
         } else {
-            Command::new(&pip_path)
-                .args(["install", package_name])
-                .output()
+            Err(PythonError::NotFound(format!("Interpreter {} does not exist", spec)))
         };
-        
-        match output {
-            Ok(output) if output.status.success() => {
-                return Ok(());
-            },
-            Ok(output) => {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("Failed to install package: {}", String::from_utf8_lossy(&output.stderr)),
-                ));
-            },
-            Err(_) => {
-                // Try the next pip path
+    }
+    
+    let found = if spec.starts_with(|c: char| c.is_ascii_digit()) {
+        pyenv_interpreter(spec).or_else(|| find_on_path(&format!("python{}", spec)))
+    } else {
+        find_on_path(spec)
+    };
+    
+    found.ok_or_else(|| PythonError::NotFound(
+        format!("Python '{}' not found in pyenv or on PATH", spec),
+    ))
+}
+
+/// pyenv's global version, read from `~/.pyenv/version` the way `pyenv
+/// global` reads it: the first version listed, or "system" without the file.
+/// `None` when pyenv isn't installed.
+pub fn pyenv_global() -> Option<String> {
+    let pyenv_root = dirs::home_dir()?.join(".pyenv");
+    if !pyenv_root.is_dir() {
+        return None;
+    }
+    let content = fs::read_to_string(pyenv_root.join("version")).unwrap_or_default();
+    Some(content.split_whitespace().next().unwrap_or("system").to_string())
+}
+
+/// Make `version` pyenv's global default with `pyenv global`.
+pub fn set_pyenv_global(version: &str) -> Result<(), PythonError> {
+    // Installs that only set up shims in the shell's rc file aren't on PATH yet
+    let pyenv = find_on_path("pyenv")
+        .or_else(|| dirs::home_dir().map(|home| home.join(".pyenv").join("bin").join("pyenv")))
+        .ok_or_else(|| PythonError::NotFound("pyenv isn't installed".to_string()))?;
+    let output = output_with_timeout(Command::new(&pyenv).args(["global", version]), probe_timeout())
+        .map_err(|e| PythonError::io("Failed to run pyenv", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(PythonError::Other(format!(
+            "Failed to set the pyenv global version: {}",
+            String::from_utf8_lossy(&output.stderr).trim(),
+        )))
+    }
+}
+
+/// The newest pyenv version matching `version` exactly or as a prefix
+/// (`3.11` matches `3.11.9`).
+fn pyenv_interpreter(version: &str) -> Option<PathBuf> {
+    let home_dir = dirs::home_dir()?;
+    let versions_dir = home_dir.join(".pyenv").join("versions");
+    
+    fs::read_dir(versions_dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy())
+                .is_some_and(|name| name == version || name.starts_with(&format!("{}.", version)))
+        })
+        .map(|path| path.join("bin").join("python"))
+        .filter(|python| python.is_file())
+        .max_by_key(|python| {
+            let name = python.parent().and_then(Path::parent).and_then(Path::file_name);
+            name.and_then(|name| release_components(&name.to_string_lossy()))
+        })
+}
+
+/// The first executable named `program` in `$PATH`.
+fn find_on_path(program: &str) -> Option<PathBuf> {
+    let program = if cfg!(windows) {
+        format!("{}.exe", program)
+    } else {
+        program.to_string()
+    };
+    
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(&program))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Total size in bytes of the files under `path`. Symlinks aren't followed,
+/// so a venv's link to its base interpreter only counts as the link itself.
+pub fn directory_size(path: &Path) -> u64 {
+    let mut total = 0;
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.filter_map(Result::ok) {
+            // Unlike fs::metadata, this describes a symlink rather than its target
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else {
+                total += metadata.len();
             }
         }
     }
+    total
+}
+
+pub fn delete_environment(env_path: &Path) -> Result<(), PythonError> {
+    fs::remove_dir_all(env_path).map_err(|e| PythonError::io(format_args!("Failed to delete {}", env_path.display()), e))
+}
+
+pub fn install_package(env_path: &Path, package_name: &str, options: &InstallOptions) -> Result<(), PythonError> {
+    let args = options.with_args(&["install", package_name]);
+    run_pip(env_path, &as_strs(&args), "install package")
+}
+
+/// Like `install_package`, but sends each line pip prints to `progress` as
+/// it's printed; see `pip_phase_packages` for making sense of them.
+pub fn install_package_with_progress(
+    env_path: &Path,
+    package_name: &str,
+    options: &InstallOptions,
+    progress: &Sender<String>,
+) -> Result<(), PythonError> {
+    let args = options.with_args(&["install", package_name]);
+    let output = launch_pip(env_path, &as_strs(&args), operation_timeout(), Some(progress))?;
+    check_pip_output(output, "install package").map(|_| ())
+}
+
+/// The packages a line of `pip install` output says pip is now working on,
+/// normalized: the one in a `Collecting requests>=2.0` line, or every one in
+/// `Installing collected packages: idna, requests`. `None` for other lines.
+pub fn pip_phase_packages(line: &str) -> Option<Vec<String>> {
+    let line = line.trim();
+    if let Some(names) = line.strip_prefix("Installing collected packages:") {
+        return Some(names.split(',').map(str::trim).filter(|name| !name.is_empty()).map(normalize_package_name).collect());
+    }
+    let requirement = line.strip_prefix("Collecting ")?;
+    let name_end = requirement
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .unwrap_or(requirement.len());
+    let name = &requirement[..name_end];
+    // Wheels, archives and local paths aren't package names
+    if !name.chars().any(|c| c.is_ascii_alphanumeric()) || [".whl", ".zip", ".tar.gz"].iter().any(|ext| name.ends_with(ext)) {
+        return None;
+    }
+    Some(vec![normalize_package_name(name)])
+}
+
+/// Uninstall a package. Only the general flags of `options` apply; index,
+/// upgrade and install-only settings mean nothing to `pip uninstall`.
+pub fn uninstall_package(env_path: &Path, package_name: &str, options: &InstallOptions) -> Result<(), PythonError> {
+    let args: Vec<String> = ["uninstall", "-y", package_name]
+        .iter()
+        .map(|arg| arg.to_string())
+        .chain(options.pip_flags.iter().cloned())
+        .chain(general_pip_args(&options.extra_args))
+        .collect();
+    run_pip(env_path, &as_strs(&args), "uninstall package")
+}
+
+/// Ask pip what `pip install <package>` would do without changing anything.
+/// Returns the packages (with versions) that would be installed; empty when
+/// everything is already satisfied. Needs pip 22.2+ for `--dry-run`.
+pub fn preview_install(env_path: &Path, package_name: &str, options: &InstallOptions) -> Result<Vec<Package>, PythonError> {
+    let args = options.with_args(&["install", "--dry-run", "--disable-pip-version-check", package_name]);
+    let output = match pip_output(
+        env_path,
+        &as_strs(&args),
+        operation_timeout(),
+        "preview install",
+    ) {
+        Err(PythonError::PipFailure { stderr, .. }) if stderr.contains("no such option: --dry-run") => {
+            return Err(PythonError::Unsupported(
+                "Install preview needs pip 22.2 or newer".to_string(),
+            ));
+        },
+        result => result?,
+    };
     
-    Err(io::Error::new(
-        io::ErrorKind::Other,
-        "Could not find pip executable",
-    ))
+    // e.g. "Would install Flask-3.0.0 Jinja2-3.1.2 typing_extensions-4.8.0"
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let packages = stdout
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Would install "))
+        .map(|specs| {
+            specs
+                .split_whitespace()
+                .filter_map(|spec| spec.rsplit_once('-'))
+                .map(|(name, version)| Package {
+                    name: name.to_string(),
+                    version: version.to_string(),
+                    summary: String::new(),
+                    manager: PackageManager::Pip,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    
+    Ok(packages)
 }
 
-pub fn uninstall_package(env_path: &Path, package_name: &str) -> io::Result<()> {
-    // Try to find pip in different locations
-    let possible_pip_paths = vec![
+/// Force-reinstall a single package without touching its dependencies.
+/// Useful for repairing a package whose installed files got corrupted.
+pub fn reinstall_package(env_path: &Path, package_name: &str, options: &InstallOptions) -> Result<(), PythonError> {
+    let args = options.with_args(&["install", "--force-reinstall", "--no-deps", package_name]);
+    run_pip(env_path, &as_strs(&args), "reinstall package")
+}
+
+fn as_strs(args: &[String]) -> Vec<&str> {
+    args.iter().map(String::as_str).collect()
+}
+
+fn pip_candidates(env_path: &Path) -> Vec<PathBuf> {
+    vec![
         if cfg!(windows) {
             env_path.join("Scripts").join("pip.exe")
         } else {
@@ -692,33 +2059,235 @@ pub fn uninstall_package(env_path: &Path, package_name: &str) -> io::Result<()>
         } else {
             env_path.join("bin").join("python")
         },
-    ];
+    ]
+}
+
+/// Names of installed packages that depend on `package_name`, from `pip show`'s "Required-by" field.
+pub fn package_dependents(env_path: &Path, package_name: &str) -> Result<Vec<String>, PythonError> {
+    let output = pip_output(env_path, &["show", package_name], probe_timeout(), "show package")?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
     
-    for pip_path in possible_pip_paths {
+    let dependents = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("Required-by:"))
+        .map(|names| {
+            names
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    
+    Ok(dependents)
+}
+
+/// Full metadata for an installed package from `pip show --files`. `None`
+/// when pip doesn't know the package, e.g. it was removed behind our back.
+pub fn package_metadata(env_path: &Path, package_name: &str) -> Result<Option<PackageMetadata>, PythonError> {
+    // pip exits non-zero for unknown packages, so look at the output instead of the status
+    let output = launch_pip(env_path, &["show", "--files", "--verbose", "--disable-pip-version-check", package_name], probe_timeout(), None)?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    
+    if let Some(metadata) = parse_pip_show(&stdout).into_iter().next() {
+        Ok(Some(metadata))
+    } else if output.status.success() || output.stderr.is_empty() {
+        Ok(None)
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("not found") {
+            Ok(None)
+        } else {
+            Err(PythonError::PipFailure { action: "show package".to_string(), stderr: stderr.into_owned() })
+        }
+    }
+}
+
+/// Licenses of the named packages from a single `pip show`, as
+/// `(name, license)` pairs. Packages that declare no license get
+/// [`UNKNOWN_LICENSE`]; packages pip can't find are left out.
+pub fn package_licenses(env_path: &Path, package_names: &[String]) -> Result<Vec<(String, String)>, PythonError> {
+    let mut args = vec!["show", "--verbose", "--disable-pip-version-check"];
+    args.extend(package_names.iter().map(String::as_str));
+    let output = launch_pip(env_path, &args, operation_timeout(), None)?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    
+    let packages = parse_pip_show(&stdout);
+    if packages.is_empty() && !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(PythonError::PipFailure { action: "show packages".to_string(), stderr: stderr.trim().to_string() });
+    }
+    
+    Ok(packages
+        .into_iter()
+        .map(|metadata| {
+            let license = if metadata.license.is_empty() { UNKNOWN_LICENSE.to_string() } else { metadata.license };
+            (metadata.name, license)
+        })
+        .collect())
+}
+
+/// Parse `pip show` output, which separates packages with a `---` line.
+fn parse_pip_show(stdout: &str) -> Vec<PackageMetadata> {
+    let lines: Vec<&str> = stdout.lines().collect();
+    let mut packages = Vec::new();
+    for block in lines.split(|line| line.trim_end() == "---") {
+        let mut metadata = PackageMetadata::default();
+        let mut found = false;
+        let mut license = String::new();
+        let mut license_expression = String::new();
+        let mut license_classifier = String::new();
+        // Header of the indented list being read, e.g. "Files" or "Classifiers"
+        let mut section = "";
+        for line in block {
+            if line.starts_with(' ') {
+                match section {
+                    "Files" => metadata.files.get_or_insert_with(Vec::new).push(line.trim().to_string()),
+                    "Classifiers" => {
+                        // e.g. "License :: OSI Approved :: MIT License"
+                        if let Some(classifier) = line.trim().strip_prefix("License ::")
+                            && license_classifier.is_empty() {
+                            license_classifier = classifier.rsplit("::").next().unwrap_or_default().trim().to_string();
+                        }
+                    },
+                    _ => {}
+                }
+                continue;
+            }
+            
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            section = "";
+            let value = value.trim().to_string();
+            let list = |value: &str| value.split(',').map(str::trim).filter(|name| !name.is_empty()).map(str::to_string).collect();
+            match key {
+                "Name" => {
+                    found = true;
+                    metadata.name = value;
+                },
+                "Version" => metadata.version = value,
+                "Summary" => metadata.summary = value,
+                "Author" => metadata.author = value,
+                // Some packages paste their whole license text here, and only the first line comes through
+                "License" => license = value,
+                "License-Expression" => license_expression = value,
+                "Home-page" => metadata.home_page = value,
+                "Location" => metadata.location = value,
+                "Editable project location" => metadata.editable_location = value,
+                "Requires" => metadata.requires = list(&value),
+                "Required-by" => metadata.required_by = list(&value),
+                "Files" => {
+                    section = "Files";
+                    metadata.files = Some(Vec::new());
+                },
+                "Classifiers" => section = "Classifiers",
+                _ => {}
+            }
+        }
+        
+        if !found {
+            continue;
+        }
+        // Prefer the SPDX expression, then a short License field, then the trove classifier
+        let license_is_usable = !license.is_empty()
+            && !license.eq_ignore_ascii_case("UNKNOWN")
+            && license.chars().count() <= 60;
+        metadata.license = if !license_expression.is_empty() {
+            license_expression
+        } else if license_is_usable {
+            license
+        } else if !license_classifier.is_empty() {
+            license_classifier
+        } else {
+            license
+        };
+        packages.push(metadata);
+    }
+    packages
+}
+
+/// Run a pip subcommand against the first usable pip found in the environment.
+/// `action` is used in the error message, e.g. "install package".
+fn run_pip(env_path: &Path, args: &[&str], action: &str) -> Result<(), PythonError> {
+    pip_output(env_path, args, operation_timeout(), action).map(|_| ())
+}
+
+/// Like `run_pip`, but hands back the successful output for parsing.
+fn pip_output(env_path: &Path, args: &[&str], timeout: Duration, action: &str) -> Result<Output, PythonError> {
+    check_pip_output(launch_pip(env_path, args, timeout, None)?, action)
+}
+
+/// `output` if pip succeeded, otherwise the error its stderr describes.
+fn check_pip_output(output: Output, action: &str) -> Result<Output, PythonError> {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if output.status.success() {
+        Ok(output)
+    } else if stderr.contains("externally-managed-environment") {
+        Err(PythonError::ExternallyManaged)
+    } else if stderr.contains("No module named pip") {
+        // Only python was left to try, and it has no pip module
+        Err(PythonError::PipMissing)
+    } else if NETWORK_FAILURES.iter().any(|failure| stderr.contains(failure)) {
+        Err(PythonError::Network(stderr.into_owned()))
+    } else {
+        Err(PythonError::PipFailure { action: action.to_string(), stderr: stderr.into_owned() })
+    }
+}
+
+/// Run an arbitrary pip command line (already split into arguments) and
+/// return its output whether or not pip reported success.
+pub fn run_pip_command(env_path: &Path, args: &[String]) -> Result<Output, PythonError> {
+    launch_pip(env_path, &as_strs(args), operation_timeout(), None)
+}
+
+/// Which pip `launch_pip` would use for `env_path`, and its version. Walks
+/// the same fallback chain (`pip`, `pip3`, `python -m pip`), so this is the
+/// pip that actually handles operations.
+pub fn pip_info(env_path: &Path) -> Result<PipInfo, PythonError> {
+    for pip_path in pip_candidates(env_path) {
         if !pip_path.exists() {
             continue;
         }
         
-        // If this is a Python executable, use it to run pip as a module
-        let output = if pip_path.file_name().map_or(false, |name| name == "python" || name == "python.exe") {
-            Command::new(&pip_path)
-                .args(["-m", "pip", "uninstall", "-y", package_name])
-                .output()
-        } else {
-            Command::new(&pip_path)
-                .args(["uninstall", "-y", package_name])
-                .output()
+        let output = match launch_pip_at(&pip_path, &["--version"], probe_timeout(), None) {
+            Ok(output) => output,
+            Err(e) if matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::Interrupted) => return Err(e.into()),
+            Err(_) => continue,
         };
+        // `python -m pip` runs even without pip, then fails
+        if !output.status.success() {
+            continue;
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let line = stdout.lines().next().unwrap_or_default().trim();
+        // "pip 24.0 from /long/site-packages/pip (python 3.12)"; the location adds nothing
+        let version = match line.split_once(" from ").and_then(|(pip, rest)| Some((pip, rest.rsplit_once(" (")?.1))) {
+            Some((pip, python)) => format!("{} ({}", pip, python),
+            None if line.is_empty() => "unknown version".to_string(),
+            None => line.to_string(),
+        };
+        return Ok(PipInfo { executable: pip_path, version });
+    }
+    
+    Err(PythonError::PipMissing)
+}
+
+/// Run pip with `args` using the first pip in the environment that can be launched.
+fn launch_pip(env_path: &Path, args: &[&str], timeout: Duration, progress: Option<&Sender<String>>) -> Result<Output, PythonError> {
+    for pip_path in pip_candidates(env_path) {
+        if !pip_path.exists() {
+            continue;
+        }
         
-        match output {
-            Ok(output) if output.status.success() => {
-                return Ok(());
-            },
+        match launch_pip_at(&pip_path, args, timeout, progress) {
             Ok(output) => {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("Failed to uninstall package: {}", String::from_utf8_lossy(&output.stderr)),
-                ));
+                return Ok(output);
+            },
+            // A cancelled pip shouldn't be retried with the next one
+            Err(e) if matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::Interrupted) => {
+                return Err(e.into());
             },
             Err(_) => {
                 // Try the next pip path
@@ -726,9 +2295,103 @@ pub fn uninstall_package(env_path: &Path, package_name: &str) -> io::Result<()>
         }
     }
     
-    Err(io::Error::new(
-        io::ErrorKind::Other,
-        "Could not find pip executable",
-    ))
+    Err(PythonError::PipMissing)
 }
 
+/// Run one pip candidate; a Python executable runs pip as a module. With
+/// `progress`, each line pip prints is sent there as it's printed.
+fn launch_pip_at(pip_path: &Path, args: &[&str], timeout: Duration, progress: Option<&Sender<String>>) -> io::Result<Output> {
+    let mut command = Command::new(pip_path);
+    if pip_path.file_name().is_some_and(|name| name == "python" || name == "python.exe") {
+        command.args(["-m", "pip"]);
+    }
+    command.args(args);
+    match progress {
+        // Python block-buffers a piped stdout, which would hold the lines back until pip exits
+        Some(progress) => output_with_progress(command.env("PYTHONUNBUFFERED", "1"), timeout, progress.clone()),
+        None => output_with_timeout(&mut command, timeout),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh, empty directory under the system temp dir for one test.
+    fn fixture_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lazyenv-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn environment(name: &str, path: PathBuf, env_type: &str) -> PythonEnvironment {
+        PythonEnvironment { name: name.to_string(), path, python_version: "3.12.0".to_string(), env_type: env_type.to_string() }
+    }
+
+    #[test]
+    fn normalize_package_name_follows_pep_503() {
+        assert_eq!(normalize_package_name("Foo__Bar"), "foo-bar");
+        assert_eq!(normalize_package_name("foo.-bar"), "foo-bar");
+        assert_eq!(normalize_package_name("FOO-bar"), "foo-bar");
+        assert_eq!(normalize_package_name("_foo_"), "-foo-");
+        assert_eq!(normalize_package_name("Flask_Login"), "flask-login");
+    }
+
+    #[test]
+    fn build_environment_handles_spaces_and_non_ascii_paths() {
+        // Needs a real interpreter; nothing to check without one
+        let Some(python) = find_on_path("python3").or_else(|| find_on_path("python")) else {
+            return;
+        };
+        let dir = fixture_dir("build");
+        let venv_dir = dir.join("my envs").join("prøject ünï");
+        fs::create_dir_all(venv_dir.parent().unwrap()).unwrap();
+
+        let env = build_environment(&venv_dir, &python).unwrap();
+        assert_eq!(env.path, venv_dir);
+        assert!(environment_python(&venv_dir).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dedup_environments_keeps_the_first_of_overlapping_detections() {
+        let dir = fixture_dir("dedup");
+        let venv = dir.join("project").join(".venv");
+        fs::create_dir_all(&venv).unwrap();
+        fs::create_dir_all(dir.join("virtualenvs")).unwrap();
+        // e.g. a pyenv version symlinked into ~/.virtualenvs
+        std::os::unix::fs::symlink(&venv, dir.join("virtualenvs").join("linked")).unwrap();
+        let other = dir.join("other");
+        fs::create_dir_all(&other).unwrap();
+
+        let environments = dedup_environments(vec![
+            environment(".venv", venv.clone(), "venv"),
+            environment("linked", dir.join("virtualenvs").join("linked"), "pyenv"),
+            environment(".venv", dir.join("project").join("..").join("project").join(".venv"), "venv"),
+            environment("other", other.clone(), "venv"),
+        ]);
+
+        let found: Vec<(&Path, &str)> = environments.iter().map(|env| (env.path.as_path(), env.env_type.as_str())).collect();
+        assert_eq!(found, vec![(venv.as_path(), "venv"), (other.as_path(), "venv")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn environment_dir_rejects_names_that_leave_virtualenvs() {
+        for name in ["", " ", ".", "..", "/srv/data", "a/b", "..\\b"] {
+            assert!(environment_dir(name).is_err(), "{:?} was accepted", name);
+        }
+        assert_eq!(environment_dir("my env").unwrap(), virtualenvs_dir().join("my env"));
+        assert!(delete_environment_for_overwrite(&virtualenvs_dir()).is_err());
+    }
+    
+    #[test]
+    fn uninstall_and_index_args_drop_install_only_flags() {
+        let extra_args: Vec<String> = ["--no-deps", "--proxy", "http://proxy:3128", "--prefer-binary", "--no-warn-script-location", "--timeout=5"]
+            .iter().map(|arg| arg.to_string()).collect();
+        assert_eq!(general_pip_args(&extra_args), ["--proxy", "http://proxy:3128", "--timeout=5"]);
+        assert_eq!(index_pip_args(&extra_args), ["--proxy", "http://proxy:3128", "--prefer-binary", "--timeout=5"]);
+    }
+}
@@ -0,0 +1,205 @@
+//! Comparing an environment against a requirements file: what's missing,
+//! what's installed at a version the file doesn't allow, and what's
+//! installed without being listed.
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+use crate::python::{normalize_package_name, Package};
+use crate::version;
+
+/// Packages every environment has that requirements files rarely list
+const TOOLING: [&str; 3] = ["pip", "setuptools", "wheel"];
+
+/// One requirement line, reduced to what's needed to check it.
+#[derive(Debug, Clone)]
+pub struct Requirement {
+    /// The line as written, without comments, for display
+    pub text: String,
+    pub name: String,
+    /// `(operator, version)` pairs, e.g. `(">=", "2.0")`
+    pub specifiers: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RequirementsDiff {
+    /// Requirements with nothing installed under their name
+    pub missing: Vec<Requirement>,
+    /// Requirements whose installed version doesn't satisfy them, with that
+    /// version and whether the mismatch is certain (see `version::compare`)
+    pub mismatched: Vec<(Requirement, String, bool)>,
+    /// Installed packages the file doesn't mention, apart from pip's own tooling
+    pub extra: Vec<Package>,
+    /// How many requirements are satisfied
+    pub matched: usize,
+}
+
+/// Requirements in a requirements file. Comments, blank lines and options
+/// such as `-r` or `--index-url` are skipped, backslash continuations are
+/// joined, and extras and environment markers are dropped. Direct
+/// references (`name @ url`) keep their name with no specifiers.
+pub fn parse(content: &str) -> Vec<Requirement> {
+    let mut requirements = Vec::new();
+    let mut pending = String::new();
+    for line in content.lines() {
+        // Comments start at a `#` at the start of a line or after whitespace
+        let comment = line
+            .char_indices()
+            .find(|&(idx, c)| c == '#' && line[..idx].chars().next_back().is_none_or(char::is_whitespace));
+        let line = comment.map_or(line, |(idx, _)| &line[..idx]);
+        if let Some(continued) = line.trim_end().strip_suffix('\\') {
+            pending.push_str(continued);
+            pending.push(' ');
+            continue;
+        }
+        pending.push_str(line);
+        let text = std::mem::take(&mut pending);
+        let text = text.trim();
+        if text.is_empty() || text.starts_with('-') {
+            continue;
+        }
+        // Per-requirement options such as `--hash=sha256:...` follow the specifiers
+        let text = text
+            .match_indices("--")
+            .find(|&(idx, _)| text[..idx].ends_with(char::is_whitespace))
+            .map_or(text, |(idx, _)| text[..idx].trim_end());
+        if let Some(requirement) = parse_line(text) {
+            requirements.push(requirement);
+        }
+    }
+    requirements
+}
+
+fn parse_line(text: &str) -> Option<Requirement> {
+    let without_marker = text.split(';').next().unwrap_or(text).trim();
+    let name_end = without_marker
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .unwrap_or(without_marker.len());
+    let (name, rest) = without_marker.split_at(name_end);
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut rest = rest.trim_start();
+    if rest.starts_with('[') {
+        rest = rest.split_once(']').map_or("", |(_, after)| after).trim_start();
+    }
+    let specifiers = if rest.starts_with('@') {
+        Vec::new()
+    } else {
+        rest.split(',')
+            .map(str::trim)
+            .filter(|spec| !spec.is_empty())
+            .filter_map(|spec| {
+                let op_end = spec.find(|c: char| !matches!(c, '=' | '<' | '>' | '!' | '~'))?;
+                let (op, version) = spec.split_at(op_end);
+                Some((op.to_string(), version.trim().to_string()))
+            })
+            .collect()
+    };
+
+    Some(Requirement { text: text.to_string(), name: name.to_string(), specifiers })
+}
+
+/// Whether `installed` satisfies `op version`. `None` when it can't be told,
+/// e.g. for pre-releases of the same release (see `version::compare`).
+pub fn satisfies(installed: &str, op: &str, version: &str) -> Option<bool> {
+    if let Some(prefix) = version.strip_suffix(".*") {
+        // Prefix matching: 1.4.2 matches ==1.4.*
+        let matches = installed == prefix || installed.starts_with(&format!("{}.", prefix));
+        return match op {
+            "==" => Some(matches),
+            "!=" => Some(!matches),
+            _ => None,
+        };
+    }
+
+    let ordering = version::compare(installed, version);
+    match op {
+        "===" => Some(installed == version),
+        "==" => ordering.map(|ordering| ordering == Ordering::Equal),
+        "!=" => ordering.map(|ordering| ordering != Ordering::Equal),
+        ">=" => ordering.map(|ordering| ordering != Ordering::Less),
+        "<=" => ordering.map(|ordering| ordering != Ordering::Greater),
+        ">" => ordering.map(|ordering| ordering == Ordering::Greater),
+        "<" => ordering.map(|ordering| ordering == Ordering::Less),
+        // Compatible release: ~=1.4.2 means >=1.4.2, ==1.4.*
+        "~=" => {
+            let components: Vec<&str> = version.split('.').collect();
+            if components.len() < 2 {
+                return None;
+            }
+            let prefix = components[..components.len() - 1].join(".");
+            let in_series = installed == prefix || installed.starts_with(&format!("{}.", prefix));
+            ordering.map(|ordering| in_series && ordering != Ordering::Less)
+        },
+        _ => None,
+    }
+}
+
+/// Compare `requirements` with the `installed` packages.
+pub fn diff(requirements: Vec<Requirement>, installed: &[Package]) -> RequirementsDiff {
+    let mut result = RequirementsDiff::default();
+    let mut listed = HashSet::new();
+
+    for requirement in requirements {
+        let name = normalize_package_name(&requirement.name);
+        listed.insert(name.clone());
+        let Some(package) = installed.iter().find(|pkg| normalize_package_name(&pkg.name) == name) else {
+            result.missing.push(requirement);
+            continue;
+        };
+
+        let checks: Vec<Option<bool>> = requirement.specifiers
+            .iter()
+            .map(|(op, version)| satisfies(&package.version, op, version))
+            .collect();
+        if checks.contains(&Some(false)) {
+            result.mismatched.push((requirement, package.version.clone(), true));
+        } else if checks.contains(&None) {
+            result.mismatched.push((requirement, package.version.clone(), false));
+        } else {
+            result.matched += 1;
+        }
+    }
+
+    result.extra = installed
+        .iter()
+        .filter(|pkg| {
+            let name = normalize_package_name(&pkg.name);
+            !listed.contains(&name) && !TOOLING.contains(&name.as_str())
+        })
+        .cloned()
+        .collect();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::python::PackageManager;
+
+    fn package(name: &str, version: &str) -> Package {
+        Package { name: name.to_string(), version: version.to_string(), summary: String::new(), manager: PackageManager::Pip }
+    }
+
+    #[test]
+    fn hashed_requirements_match_installed_versions() {
+        let content = "\
+# Generated by LazyEnv from pip freeze of demo
+requests==2.31.0 \\
+    --hash=sha256:58cd2187c01e70e6e26505bca751777aa9f2ee0b7f4300988b709f44e013003f \\
+    --hash=sha256:942c5a758f98d790eaed1a29cb6eefc7ffb0d1cf7af05c3d2791656dbd6ad1e1
+idna==3.6 --hash=sha256:c05567e9c24a6b9faaa835c4821bad0590fbb9d5779e7caa6e1cc4978e7eb24f
+";
+        let requirements = parse(content);
+        assert_eq!(requirements.len(), 2);
+        assert_eq!(requirements[0].specifiers, vec![("==".to_string(), "2.31.0".to_string())]);
+        assert_eq!(requirements[1].text, "idna==3.6");
+
+        let result = diff(requirements, &[package("requests", "2.31.0"), package("idna", "3.6")]);
+        assert_eq!(result.matched, 2);
+        assert!(result.mismatched.is_empty());
+        assert!(result.missing.is_empty());
+    }
+}
@@ -1,15 +1,36 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use ratatui::{
-    backend::Backend,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::{Span, Text},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Clear},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, Paragraph, Clear, Tabs, Wrap},
     Frame,
 };
 
-use crate::app::{App, AppState, DialogState, Focus};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::app::{env_group, App, AppState, DialogState, EnvSort, Focus, READ_ONLY_KEYS};
+use crate::dotenv;
+use crate::jupyter;
+use crate::package_index::MAX_SUGGESTIONS;
+use crate::python::{activation_command, environment_created, normalize_package_name, PackageManager, PENDING_VERSION};
+use crate::version::{short_version, staleness, Staleness};
+
+/// Below this many columns the panes are stacked vertically instead of side by side.
+const NARROW_WIDTH: u16 = 80;
+
+/// Smallest terminal the interface is drawn in; below it only a notice is shown.
+const MIN_WIDTH: u16 = 30;
+const MIN_HEIGHT: u16 = 8;
 
 pub fn ui(f: &mut Frame, app: &mut App) {
+    let size = f.size();
+    if size.width < MIN_WIDTH || size.height < MIN_HEIGHT {
+        render_too_small(f, size);
+        return;
+    }
+    
     // Create main layout
     let chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -17,19 +38,47 @@ pub fn ui(f: &mut Frame, app: &mut App) {
             Constraint::Min(1),
             Constraint::Length(1),
         ])
-        .split(f.size());
+        .split(size);
 
-    let main_area = chunks[0];
+    let mut main_area = chunks[0];
     let status_area = chunks[1];
+    
+    // The tab bar only takes a row once there's more than one tab
+    if app.tabs.len() > 1 {
+        let tab_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1),
+                Constraint::Min(1),
+            ])
+            .split(main_area);
+        render_tab_bar(f, app, tab_chunks[0]);
+        main_area = tab_chunks[1];
+    }
 
-    // Split main area into sidebar and content
-    let main_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage(30),
-            Constraint::Percentage(70),
-        ])
-        .split(main_area);
+    // Split main area into sidebar and content. Narrow terminals stack the
+    // panes instead, with the focused one expanded.
+    let main_chunks = if main_area.width < NARROW_WIDTH {
+        let environments_percent = match app.focus {
+            Focus::Environments => 70,
+            Focus::Packages => 30,
+        };
+        Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Percentage(environments_percent),
+                Constraint::Percentage(100 - environments_percent),
+            ])
+            .split(main_area)
+    } else {
+        Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Percentage(app.sidebar_percent),
+                Constraint::Percentage(100 - app.sidebar_percent),
+            ])
+            .split(main_area)
+    };
 
     // Draw environments sidebar
     render_environments(f, app, main_chunks[0]);
@@ -43,45 +92,220 @@ pub fn ui(f: &mut Frame, app: &mut App) {
     // Render dialogs on top if needed
     match app.state {
         AppState::CreateEnvironment => {
-            render_input_dialog(f, "Create New Environment", "Enter environment name:", &app.input_text);
+            render_create_dialog(f, app);
         },
-        AppState::DeleteEnvironment => {
-            if app.dialog_state == DialogState::Confirm {
-                if let Some(idx) = app.selected_environment {
-                    let env_name = &app.environments[idx].name;
-                    render_confirm_dialog(f, "Delete Environment", &format!("Are you sure you want to delete '{}'? (y/n)", env_name));
-                }
+        AppState::DeleteEnvironment if app.dialog_state == DialogState::Confirm => {
+            if let Some(env) = app.current_environment() {
+                render_confirm_dialog(f, "Delete Environment", &format!("Are you sure you want to delete '{}'? (y/n)", env.name), None);
             }
         },
         AppState::InstallPackage => {
-            render_input_dialog(f, "Install Package", "Enter package name:", &app.input_text);
-        },
-        AppState::UninstallPackage => {
-            if app.dialog_state == DialogState::Confirm {
-                if let Some(pkg_idx) = app.selected_package {
-                    if pkg_idx < app.packages.len() {
-                        let pkg_name = &app.packages[pkg_idx].name;
-                        render_confirm_dialog(f, "Uninstall Package", &format!("Are you sure you want to uninstall '{}'? (y/n)", pkg_name));
-                    }
-                }
+            render_install_dialog(f, app);
+        },
+        AppState::InstallPreview => {
+            render_install_preview(f, app);
+        },
+        AppState::UninstallPackage if app.dialog_state == DialogState::Confirm => {
+            if let Some(pkg_idx) = app.selected_package
+                && pkg_idx < app.packages.len() {
+                let pkg = &app.packages[pkg_idx];
+                let warning = if app.required_by.is_empty() {
+                    None
+                } else {
+                    let verb = if app.required_by.len() == 1 { "depends" } else { "depend" };
+                    Some(format!("Warning: {} {} on this", app.required_by.join(", "), verb))
+                };
+                render_confirm_dialog(f, "Uninstall Package", &format!("Are you sure you want to uninstall '{}' {} with {}? (y/n)", pkg.name, pkg.version, pkg.manager), warning.as_deref());
+            }
+        },
+        AppState::ReinstallPackage if app.dialog_state == DialogState::Confirm => {
+            if let Some(pkg_idx) = app.selected_package
+                && pkg_idx < app.packages.len() {
+                let pkg_name = &app.packages[pkg_idx].name;
+                render_confirm_dialog(f, "Reinstall Package", &format!("Force-reinstall '{}'? Only this package's files are reinstalled; its dependencies are not touched. (y/n)", pkg_name), None);
             }
         },
         AppState::SearchEnvironment => {
-            render_input_dialog(f, "Search Environments", "Enter search term:", &app.input_text);
+            let title = if app.search_case_sensitive { "Search Environments [case-sensitive]" } else { "Search Environments" };
+            render_input_dialog(f, title, "Enter search term (Ctrl+S: toggle case-sensitive):", &app.input_text, app.input_cursor);
+        },
+        AppState::CompareRequirements => {
+            render_input_dialog(f, "Compare with Requirements", "Requirements file to compare the environment with:", &app.input_text, app.input_cursor);
+        },
+        AppState::PackageFiles => {
+            render_package_files(f, app);
+        },
+        AppState::RequirementsDiffView => {
+            render_requirements_diff(f, app);
+        },
+        AppState::PipCommand => {
+            render_input_dialog(f, "Run pip Command", "pip arguments (no shell), e.g. install --pre requests:", &app.input_text, app.input_cursor);
+        },
+        AppState::LogView => {
+            render_log_view(f, app);
+        },
+        AppState::HistoryView => {
+            render_history_view(f, app);
+        },
+        AppState::PackageDetails => {
+            render_package_details(f, app);
+        },
+        AppState::ConfirmUpgrade => {
+            if let Some(pkg) = app.installed_package(&app.input_text) {
+                render_confirm_dialog(
+                    f,
+                    "Already Installed",
+                    &format!("{} {} is already installed. Upgrade instead? (y/n)", pkg.name, pkg.version),
+                    None,
+                );
+            }
+        },
+        AppState::ConfirmBreakSystem => {
+            let env_name = app.current_environment().map_or("This environment", |env| &env.name);
+            render_confirm_dialog(
+                f,
+                "Externally Managed Environment",
+                &format!("{} is managed by your OS (PEP 668). Install '{}' anyway with --break-system-packages? (y/n)", env_name, app.input_text),
+                Some("This can break OS tools that depend on this Python. Creating a virtual environment (n) is safer."),
+            );
+        },
+        AppState::ConfirmEnsurepip => {
+            let env_name = app.current_environment().map_or("This environment", |env| &env.name);
+            render_confirm_dialog(
+                f,
+                "No pip",
+                &format!("{} has no pip. Install pip with python -m ensurepip, then install '{}'? (y/n)", env_name, app.input_text),
+                None,
+            );
+        },
+        AppState::CleanupBroken => {
+            render_cleanup_dialog(f, app);
+        },
+        AppState::ConfirmUpgradeAll => {
+            render_upgrade_all_dialog(f, app);
+        },
+        AppState::PickTemplate => {
+            render_template_picker(f, app);
+        },
+        AppState::DiskUsage => {
+            render_disk_usage(f, app);
+        },
+        AppState::DotenvView => {
+            render_dotenv(f, app);
+        },
+        AppState::KernelView => {
+            render_kernels(f, app);
+        },
+        AppState::SysPathView => {
+            render_sys_path(f, app);
+        },
+        AppState::ConfirmRemoveKernel => {
+            let name = app.kernels.as_ref()
+                .and_then(|kernels| kernels.get(app.selected_kernel))
+                .map_or("", |kernel| kernel.name.as_str());
+            render_confirm_dialog(
+                f,
+                "Remove Kernel",
+                &format!("Remove the Jupyter kernel '{}'? (y/n)", name),
+                Some("The environment itself is left untouched."),
+            );
+        },
+        AppState::PickVersion => {
+            render_version_picker(f, app);
+        },
+        AppState::ConfirmOverwrite => {
+            render_confirm_dialog(
+                f,
+                "Environment Exists",
+                &format!("An environment named '{}' already exists. Overwrite? (y/n)", app.input_text),
+                Some("Overwriting deletes the existing directory and everything installed in it."),
+            );
         },
         AppState::HelpMenu => {
-            render_help_menu(f);
+            render_help_menu(f, app);
+        },
+        AppState::ConfirmQuit => {
+            render_confirm_dialog(f, "Quit", "Quit LazyEnv? (y/n)", None);
+        },
+        AppState::ConfirmCondaExport => {
+            let env_name = app.current_environment().map_or("", |env| &env.name);
+            render_confirm_dialog(
+                f,
+                "Export environment.yml",
+                &format!(
+                    "Export {} with conda env export to an environment-*.yml file in the current directory? \
+                     (y: exact builds | h: only explicitly requested packages, --from-history | n: cancel)",
+                    env_name,
+                ),
+                Some("--from-history travels better between platforms but leaves out pip packages."),
+            );
+        },
+        AppState::ConfirmPyenvGlobal => {
+            let version = app.current_environment().and_then(App::pyenv_version).unwrap_or_default();
+            render_confirm_dialog(
+                f,
+                "pyenv global",
+                &format!(
+                    "Make {} the pyenv global version (currently {})? (y/n)",
+                    version, app.pyenv_global.as_deref().unwrap_or("system"),
+                ),
+                Some("This changes the default python for every shell that uses pyenv."),
+            );
         },
         _ => {}
     }
 }
 
+/// Shown instead of the interface when the terminal is below the minimum size.
+fn render_too_small(f: &mut Frame, area: Rect) {
+    let message = format!(
+        "Terminal too small ({}x{}). Resize to at least {}x{}.",
+        area.width, area.height, MIN_WIDTH, MIN_HEIGHT
+    );
+    let paragraph = Paragraph::new(message)
+        .style(Style::default().fg(Color::Yellow))
+        .wrap(Wrap { trim: true });
+    f.render_widget(paragraph, area);
+}
+
+fn render_tab_bar(f: &mut Frame, app: &App, area: Rect) {
+    let titles: Vec<Line> = (0..app.tabs.len())
+        .map(|idx| Line::from(format!("{}: {}", idx + 1, app.tab_title(idx))))
+        .collect();
+    
+    let tabs = Tabs::new(titles)
+        .select(app.active_tab)
+        .style(Style::default().fg(Color::Gray))
+        .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD));
+    
+    f.render_widget(tabs, area);
+}
+
 fn render_environments(f: &mut Frame, app: &App, area: Rect) {
     let title = if app.show_global_packages {
         "Python Environments (Global Packages)"
     } else {
         "Python Environments"
     };
+    let title = match app.env_sort {
+        EnvSort::Detected => title.to_string(),
+        sort => format!("{} [by {}]", title, sort.label()),
+    };
+    let visible = app.visible_environments();
+    let title = match app.environments.len() - visible.len() {
+        0 => title,
+        hidden => format!("{} [{} hidden]", title, hidden),
+    };
+    let title = match app.detection_warnings.len() {
+        0 => Line::from(title),
+        n => Line::from(vec![
+            Span::raw(title),
+            Span::styled(
+                format!(" ⚠ {} warning{} (l: log)", n, if n == 1 { "" } else { "s" }),
+                Style::default().fg(Color::Yellow),
+            ),
+        ]),
+    };
 
     // Set border color based on focus
     let border_style = if app.focus == Focus::Environments {
@@ -90,21 +314,54 @@ fn render_environments(f: &mut Frame, app: &App, area: Rect) {
         Style::default().fg(Color::Gray)
     };
 
-    let environments: Vec<ListItem> = app
-        .environments
-        .iter()
-        .map(|env| {
-            let env_type = match &env.env_type[..] {
-                "venv" => "venv",
-                "conda" => "conda",
-                "pyenv" => "pyenv",
-                "system" => "system",
-                _ => "unknown",
-            };
-            
-            ListItem::new(format!("{} ({}) [{}]", env.name, env.python_version, env_type))
-        })
-        .collect();
+    let mut environments: Vec<ListItem> = Vec::new();
+    let mut selected_row = None;
+    let mut group = None;
+    for &idx in &visible {
+        let env = &app.environments[idx];
+        let env_type = env_group(&env.env_type);
+        // Header rows are never selected, so navigation passes straight over them
+        if app.group_environments && group != Some(env_type) {
+            group = Some(env_type);
+            environments.push(
+                ListItem::new(format!("── {} ──", env_type))
+                    .style(Style::default().fg(Color::DarkGray).add_modifier(Modifier::BOLD)),
+            );
+        }
+        if app.selected_environment == Some(idx) {
+            selected_row = Some(environments.len());
+        }
+        let active = app.active_conda.as_ref() == Some(&env.path);
+        let pin = if app.is_pinned(&env.path) { "* " } else { "" };
+        let mut label = format!("{}{} ({}) [{}]", pin, env.name, env.python_version, env_type);
+        if let Some(count) = app.package_counts.get(&env.path) {
+            label.push_str(&format!(" · {} pkgs", count));
+        }
+        // Only known once the kernel view (J) has been opened
+        if app.kernels.as_deref().is_some_and(|kernels| jupyter::kernel_for(kernels, env).is_some()) {
+            label.push_str(" · kernel");
+        }
+        if app.is_pyenv_global(env) {
+            label.push_str(" · global");
+        }
+        let pipless = app.pipless.contains(&env.path);
+        if pipless {
+            label.push_str(" · no pip");
+        }
+        
+        let item = if active {
+            ListItem::new(format!("{} (active)", label))
+                .style(Style::default().fg(Color::Green).add_modifier(Modifier::BOLD))
+        } else if env.python_version == PENDING_VERSION {
+            // Still being probed; it may be on a slow filesystem
+            ListItem::new(label).style(Style::default().fg(Color::DarkGray))
+        } else if pipless {
+            ListItem::new(label).style(Style::default().fg(Color::Yellow))
+        } else {
+            ListItem::new(label)
+        };
+        environments.push(item);
+    }
 
     let environments_list = List::new(environments)
         .block(Block::default().title(title).borders(Borders::ALL).border_style(border_style))
@@ -117,30 +374,38 @@ fn render_environments(f: &mut Frame, app: &App, area: Rect) {
         .highlight_symbol("> ");
 
     let mut state = ratatui::widgets::ListState::default();
-    state.select(app.selected_environment);
+    state.select(selected_row);
 
     f.render_stateful_widget(environments_list, area, &mut state);
 }
 
-fn render_packages(f: &mut Frame, app: &App, area: Rect) {
-    // Split the right panel into two parts: packages list and details
+fn render_packages(f: &mut Frame, app: &mut App, area: Rect) {
+    // Split the right panel into two parts: packages list and details,
+    // unless the details are hidden to give the list the whole pane
+    let constraints = if app.show_details {
+        [Constraint::Percentage(70), Constraint::Percentage(30)]
+    } else {
+        [Constraint::Percentage(100), Constraint::Length(0)]
+    };
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Percentage(70),
-            Constraint::Percentage(30),
-        ])
+        .constraints(constraints)
         .split(area);
 
     // Determine title based on global package view
     let title = if app.show_global_packages {
-        "Global Packages"
+        "Global Packages".to_string()
+    } else if let Some(env) = app.current_environment() {
+        format!("Packages in {}", env.name)
     } else {
-        &if let Some(idx) = app.selected_environment {
-            format!("Packages in {}", app.environments[idx].name)
-        } else {
-            "Packages".to_string()
-        }
+        "Packages".to_string()
+    };
+    
+    let visible = app.visible_packages();
+    let title = if app.package_filter.is_empty() {
+        title
+    } else {
+        format!("{} [filter: '{}' - {} of {}]", title, app.package_filter, visible.len(), app.packages.len())
     };
 
     // Set border color based on focus
@@ -151,13 +416,87 @@ fn render_packages(f: &mut Frame, app: &App, area: Rect) {
     };
 
     // Render packages list
-    let packages: Vec<ListItem> = app
-        .packages
-        .iter()
-        .map(|pkg| {
-            ListItem::new(format!("{} ({})", pkg.name, pkg.version))
-        })
-        .collect();
+    let packages: Vec<ListItem> = if app.loading_packages {
+        vec![ListItem::new("Loading packages…").style(Style::default().fg(Color::DarkGray))]
+    } else {
+        // Only conda environments mix managers, so only they get markers
+        let mixed = app.packages.iter().any(|pkg| pkg.manager == PackageManager::Conda);
+        let installing = app.installing
+            .as_ref()
+            .filter(|(env_path, _)| !app.show_global_packages && app.current_environment().is_some_and(|env| env.path == *env_path))
+            .map(|(_, names)| names);
+        let rows: Vec<(String, Style, &str)> = visible
+            .iter()
+            .map(|&idx| {
+                let pkg = &app.packages[idx];
+                // The details panel always shows the full version
+                let display_version = |version: &str| if app.short_versions {
+                    short_version(version)
+                } else {
+                    version.to_string()
+                };
+                let version = display_version(&pkg.version);
+                let (label, style) = match &app.outdated {
+                    None => (format!("{} ({})", pkg.name, version), Style::default()),
+                    Some(outdated) => match outdated.get(&normalize_package_name(&pkg.name)) {
+                        Some(latest) => {
+                            let color = match staleness(&pkg.version, latest) {
+                                Staleness::Major => Color::Red,
+                                Staleness::Minor => Color::Yellow,
+                                Staleness::Current => Color::Green,
+                            };
+                            (format!("{} ({} -> {})", pkg.name, version, display_version(latest)), Style::default().fg(color))
+                        },
+                        None => (format!("{} ({})", pkg.name, version), Style::default().fg(Color::Green)),
+                    },
+                };
+                
+                // In the leaf view, bullet the packages nothing depends on and dim the rest
+                let (label, style) = match &app.leaves {
+                    None => (label, style),
+                    Some(leaves) if leaves.contains(&normalize_package_name(&pkg.name)) => {
+                        (format!("• {}", label), style.add_modifier(Modifier::BOLD))
+                    },
+                    Some(_) => (format!("  {}", label), style.add_modifier(Modifier::DIM)),
+                };
+                let label = if mixed { format!("{} [{}]", label, pkg.manager) } else { label };
+                let (label, style) = if installing.is_some_and(|names| names.contains(&normalize_package_name(&pkg.name))) {
+                    (format!("{} · installing…", label), Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD))
+                } else {
+                    (label, style)
+                };
+                (label, style, pkg.summary.as_str())
+            })
+            .collect();
+        
+        if app.show_summaries {
+            // Borders and the highlight symbol take four cells; summaries line up
+            // after the widest label, which gets at most half the row
+            let inner_width = (chunks[0].width as usize).saturating_sub(4);
+            let label_width = rows.iter()
+                .map(|(label, _, _)| label.width())
+                .max()
+                .unwrap_or(0)
+                .min(inner_width / 2);
+            let summary_width = inner_width.saturating_sub(label_width + 2);
+            
+            rows.into_iter()
+                .map(|(label, style, summary)| {
+                    let label = truncate_to_width(&label, label_width);
+                    let padding = " ".repeat(label_width - label.width() + 2);
+                    ListItem::new(Line::from(vec![
+                        Span::styled(label, style),
+                        Span::raw(padding),
+                        Span::styled(truncate_to_width(summary, summary_width), Style::default().fg(Color::DarkGray)),
+                    ]))
+                })
+                .collect()
+        } else {
+            rows.into_iter()
+                .map(|(label, style, _)| ListItem::new(label).style(style))
+                .collect()
+        }
+    };
 
     let packages_list = List::new(packages)
         .block(Block::default().title(title).borders(Borders::ALL).border_style(border_style))
@@ -170,31 +509,79 @@ fn render_packages(f: &mut Frame, app: &App, area: Rect) {
         .highlight_symbol("> ");
 
     let mut state = ratatui::widgets::ListState::default();
-    state.select(app.selected_package);
+    if !app.loading_packages {
+        state.select(app.selected_package.and_then(|idx| visible.iter().position(|&i| i == idx)));
+    }
 
     f.render_stateful_widget(packages_list, chunks[0], &mut state);
 
-    // Render package details
-    let details = if let Some(idx) = app.selected_package {
-        if idx < app.packages.len() {
-            let pkg = &app.packages[idx];
-            format!(
-                "Name: {}
-Version: {}
-Summary: {}",
-                pkg.name, pkg.version, pkg.summary
-            )
+    if app.show_details {
+        // Render package details
+        // Borders take one cell on each side
+        let details_width = chunks[1].width.saturating_sub(2) as usize;
+        let details_height = chunks[1].height.saturating_sub(2) as usize;
+        let mut lines = match app.selected_package.and_then(|idx| app.packages.get(idx)) {
+            Some(pkg) => {
+                let mut lines = vec![
+                    format!("Name: {}", pkg.name),
+                    format!("Version: {}", pkg.version),
+                    format!("Summary: {}", pkg.summary),
+                ];
+                if pkg.manager == PackageManager::Conda {
+                    lines.push("Installed by: conda (removed with conda remove)".to_string());
+                }
+                if let Some(license) = app.package_license(&pkg.name) {
+                    lines.push(format!("License: {}", license));
+                }
+                lines
+            },
+            None => vec!["No package selected".to_string()],
+        };
+
+        // Which pip handles operations here, since the fallback chain can pick an unexpected one
+        if !app.show_global_packages
+            && let Some(env) = app.current_environment()
+            && let Some(info) = app.pip_info.get(&env.path) {
+            lines.push(match info {
+                Some(info) => format!("Pip: {} ({})", info.executable.display(), info.version),
+                None => "Pip: not found".to_string(),
+            });
+        }
+        if app.show_dependency_counts
+            && !app.show_global_packages
+            && let Some(env) = app.current_environment() {
+            lines.push(match app.dependency_counts.get(&env.path) {
+                Some(Some((total, explicit))) => format!(
+                    "Environment: {} packages ({} explicit, {} dependencies)",
+                    total, explicit, total.saturating_sub(*explicit),
+                ),
+                Some(None) => "Environment: package counts unavailable".to_string(),
+                None => "Environment: counting packages…".to_string(),
+            });
+        }
+        
+        let rows = if app.wrap_details {
+            lines.iter().map(|line| wrapped_height(line, details_width)).sum()
         } else {
-            "No package selected".to_string()
+            lines = lines.iter().map(|line| truncate_to_width(line, details_width)).collect();
+            lines.len()
+        };
+        // Don't let the content scroll out of the panel
+        app.details_scroll = app.details_scroll.min(rows.saturating_sub(details_height) as u16);
+        
+        let mut title = "Package Details".to_string();
+        if rows > details_height {
+            title.push_str(" (Shift+↑/↓: scroll)");
+        }
+        let mut details_widget = Paragraph::new(lines.join("\n"))
+            .block(Block::default().title(title).borders(Borders::ALL))
+            .scroll((app.details_scroll, 0));
+        if app.wrap_details {
+            details_widget = details_widget.wrap(Wrap { trim: true });
         }
-    } else {
-        "No package selected".to_string()
-    };
-
-    let details_widget = Paragraph::new(details)
-        .block(Block::default().title("Package Details").borders(Borders::ALL));
 
-    f.render_widget(details_widget, chunks[1]);
+        f.render_widget(details_widget, chunks[1]);
+    }
 
     // Render help text at the bottom
     let help_text = match app.state {
@@ -202,10 +589,9 @@ Summary: {}",
             if app.focus == Focus::Environments {
                 "Press 'x' for help | Tab: Switch focus | Enter: View packages"
             } else {
-                "Press 'x' for help | Tab: Switch focus"
+                "Press 'x' for help | Tab: Switch focus | Enter: Package details"
             }
         },
-        AppState::PackageView => "Press 'x' for help | Tab: Switch focus | Esc: Back",
         _ => "",
     };
 
@@ -222,7 +608,7 @@ Summary: {}",
     f.render_widget(help_widget, help_area);
 }
 
-fn render_help_menu(f: &mut Frame) {
+fn render_help_menu(f: &mut Frame, app: &App) {
     let area = centered_rect(70, 70, f.size());
     
     // Clear the area
@@ -230,7 +616,7 @@ fn render_help_menu(f: &mut Frame) {
     
     // Create a block for the help menu
     let help_block = Block::default()
-        .title("LazyEnv Help")
+        .title(if app.read_only { "LazyEnv Help [read-only]" } else { "LazyEnv Help" })
         .borders(Borders::ALL)
         .style(Style::default().bg(Color::DarkGray));
     
@@ -248,27 +634,91 @@ fn render_help_menu(f: &mut Frame) {
     let help_content = "
 NAVIGATION
 ↑/↓: Navigate through list
+<number>G / <number>Enter: Jump to item; G alone jumps to the end
 Tab: Switch focus between environments and packages
 Enter: View packages for selected environment
+Enter (on a package): Show full package details from pip show
+f (in package details): List the files the package installed
 
 ENVIRONMENT MANAGEMENT
 n: Create new environment
 d: Delete selected environment
-s: Search environments
-g: Toggle between environment packages and global packages
+s: Search environments (fuzzy: 'myprj' finds 'my-project-env')
+n/N: Next/previous search match while a search is active (Esc ends it)
+g: Toggle between environment packages and global packages (clears the filter)
 R: Refresh environment list
+< / >: Shrink / grow the environments sidebar
 
 PACKAGE MANAGEMENT
-i: Install package in selected environment
+i: Install package in selected environment (Tab in the dialog previews with a dry run)
+I: Install the package name or requirement on the clipboard
+/: Filter packages by name, fuzzily, best matches first (works in both environment and global views)
+o: Check for outdated packages (green: current, yellow: minor update, red: major update)
+U: Upgrade every outdated package (after o)
+V: Pick a version of the selected package to upgrade or downgrade to
 r: Remove selected package
+f: Force-reinstall selected package (dependencies untouched)
 
 OTHER
+!: Run a raw pip command in the selected environment
+=: Compare the selected environment with a requirements file
+l: Show/hide the log of command output and detection warnings
+y: Copy pip freeze of the selected environment to the clipboard
+Y: Copy the listed packages as a markdown table
+F: Write requirements with hashes (for pip --require-hashes) to the current directory
+X: Export a conda environment to environment-<name>.yml (full or --from-history)
+E: Show the variables in the .env file of the selected environment
+P: Show the sys.path of the selected environment (site-packages highlighted)
+J: Jupyter kernels; register the selected environment or remove kernels (not in read-only mode)
+c: Copy the pip install command for the selected package
+S: Cycle environment sort order (detected, age, recent, frequent)
+C: Clean up environments with a missing or broken interpreter
+p: Pin/unpin the selected environment at the top of the list
+m: Show/hide package summaries in the package list
+z: Show/hide the package details panel
+W: Wrap/cut off long lines in the details panel
+Shift+Up/Down: Scroll the details panel (with the package list focused)
+a: Open a shell with the selected environment activated (exit to return)
+v: Show short (major.minor.patch) or full versions in the package list
+h: Hide/show system and pyenv interpreters
+T: Group the environment list by type under headers
+M: Make the selected pyenv version the pyenv global default
+H: Show the history of changes made through LazyEnv
+D: Show how much disk space each virtual environment uses, largest first
+L: Mark packages nothing depends on (likely installed explicitly); again to turn off
+K: Fetch package licenses in the background (then filter with /license:gpl)
+t: Open the selected environment in a new tab
+w: Close the current tab
+]/[ or Ctrl+Tab: Next/previous tab (Alt+1-9: go to tab)
+Shift+Up/Down: Reorder pinned environments
+Alt+letters: Jump to the next item starting with them (same letter again: next match)
 x: Show/hide this help menu
 q: Quit application
-Esc: Go back / Cancel current operation
+Esc: Go back to the previous view / Cancel current operation / Dismiss the status message
+Ctrl+X: Kill a stuck background operation (install, create, hash export, listing)
+Ctrl+O: Show the command line LazyEnv is running (or ran last) and add it to the log
 ";
     
-    let help_widget = Paragraph::new(help_content)
+    // Grey out what read-only mode disables
+    let lines: Vec<Line> = help_content
+        .lines()
+        // Switching the pyenv global version needs pyenv
+        .filter(|line| app.pyenv_global.is_some() || !line.starts_with("M: "))
+        .map(|line| {
+            let disabled = app.read_only
+                && line.split_once(": ").is_some_and(|(key, _)| {
+                    let mut chars = key.chars();
+                    chars.next().is_some_and(|c| chars.next().is_none() && READ_ONLY_KEYS.contains(&c))
+                });
+            if disabled {
+                Line::styled(format!("{} (disabled: read-only)", line), Style::default().fg(Color::Gray))
+            } else {
+                Line::from(line)
+            }
+        })
+        .collect();
+    
+    let help_widget = Paragraph::new(lines)
         .style(Style::default().fg(Color::White));
     
     f.render_widget(help_widget, inner_area);
@@ -287,33 +737,847 @@ Esc: Go back / Cancel current operation
     f.render_widget(footer_widget, footer_area);
 }
 
+fn render_create_dialog(f: &mut Frame, app: &App) {
+    let area = centered_rect_rows(60, 8, f.size());
+    
+    // Clear the area
+    f.render_widget(Clear, area);
+    
+    let dialog = Block::default()
+        .title("Create New Environment")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(Color::DarkGray));
+    
+    f.render_widget(dialog, area);
+    
+    // Create the inner area for content
+    let inner_area = Rect {
+        x: area.x + 2,
+        y: area.y + 1,
+        width: area.width.saturating_sub(4),
+        height: area.height.saturating_sub(2),
+    };
+    
+    let row = |offset: u16| Rect {
+        x: inner_area.x,
+        y: inner_area.y + offset,
+        width: inner_area.width,
+        height: 1,
+    };
+    
+    // Room left for input after the "> " prompt
+    let field_width = (inner_area.width as usize).saturating_sub(2);
+    
+    let field_style = |active: bool| {
+        if active {
+            Style::default().fg(Color::White).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(Color::Gray)
+        }
+    };
+    
+    f.render_widget(Paragraph::new("Enter environment name (comma-separated to create several):"), row(0));
+    f.render_widget(
+        Paragraph::new(if app.editing_python {
+            Line::from(format!("> {}", tail_to_width(&app.input_text, field_width)))
+        } else {
+            input_line(&app.input_text, app.input_cursor, field_width)
+        })
+        .style(field_style(!app.editing_python)),
+        row(1),
+    );
+    
+    f.render_widget(Paragraph::new("Python (path, command or version; Up/Down to edit):"), row(2));
+    let python_text = if !app.python_override.is_empty() || app.editing_python {
+        format!("> {}", tail_to_width(&app.python_override, field_width))
+    } else {
+        match &app.default_python {
+            Some(python) => format!("  {} (from config)", python),
+            None => "  python on PATH (default)".to_string(),
+        }
+    };
+    f.render_widget(Paragraph::new(python_text).style(field_style(app.editing_python)), row(3));
+    
+    let help_widget = Paragraph::new("Enter: Create | Esc: Cancel")
+        .style(Style::default().fg(Color::Gray));
+    f.render_widget(help_widget, row(inner_area.height.saturating_sub(1)));
+}
+
+fn render_upgrade_all_dialog(f: &mut Frame, app: &App) {
+    let area = centered_rect(70, 60, f.size());
+    
+    // Clear the area
+    f.render_widget(Clear, area);
+    
+    let dialog = Block::default()
+        .title("Upgrade All Outdated Packages")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(Color::DarkGray));
+    
+    f.render_widget(dialog, area);
+    
+    // Create the inner area for content
+    let inner_area = Rect {
+        x: area.x + 2,
+        y: area.y + 1,
+        width: area.width.saturating_sub(4),
+        height: area.height.saturating_sub(2),
+    };
+    
+    let outdated = app.upgradable_packages();
+    let mut lines = vec![format!("Upgrade {} package(s)?", outdated.len())];
+    lines.extend(outdated.iter().map(|(pkg, latest)| format!("  {} {} -> {}", pkg.name, pkg.version, latest)));
+    let conda_managed = app.outdated_packages().len() - outdated.len();
+    if conda_managed > 0 {
+        lines.push(format!("Skipping {} conda-managed package(s); upgrade them with conda update", conda_managed));
+    }
+    
+    let content_area = Rect {
+        height: inner_area.height.saturating_sub(2),
+        ..inner_area
+    };
+    f.render_widget(Paragraph::new(lines.join("\n")), content_area);
+    
+    // Render help text on the last line
+    let help_area = Rect {
+        x: inner_area.x,
+        y: inner_area.y + inner_area.height.saturating_sub(1),
+        width: inner_area.width,
+        height: 1,
+    };
+    
+    let help_widget = Paragraph::new("y: Upgrade | n/Esc: Cancel")
+        .style(Style::default().fg(Color::Gray));
+    f.render_widget(help_widget, help_area);
+}
+
+fn render_template_picker(f: &mut Frame, app: &App) {
+    let area = centered_rect(60, 50, f.size());
+    
+    // Clear the area
+    f.render_widget(Clear, area);
+    
+    let mut items = vec![ListItem::new("No template")];
+    items.extend(app.templates.iter().map(|template| {
+        ListItem::new(format!("{} — {}", template.name, template.packages.join(", ")))
+    }));
+    
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!("Template for '{}' (Enter: create | Esc: back)", app.input_text))
+                .borders(Borders::ALL)
+                .style(Style::default().bg(Color::DarkGray)),
+        )
+        .highlight_style(Style::default().fg(Color::White).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+    
+    let mut state = ratatui::widgets::ListState::default();
+    state.select(Some(app.selected_template));
+    
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_version_picker(f: &mut Frame, app: &App) {
+    let area = centered_rect(40, 60, f.size());
+    
+    // Clear the area
+    f.render_widget(Clear, area);
+    
+    let installed = app.selected_package.and_then(|idx| app.packages.get(idx));
+    let items: Vec<ListItem> = app.available_versions
+        .iter()
+        .map(|version| {
+            if installed.is_some_and(|pkg| pkg.version == *version) {
+                ListItem::new(format!("{} (installed)", version)).style(Style::default().fg(Color::Green))
+            } else {
+                ListItem::new(version.as_str())
+            }
+        })
+        .collect();
+    
+    let name = installed.map_or("package", |pkg| pkg.name.as_str());
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(if app.versions_offline {
+                    format!("Cached versions of {}, index unreachable (Enter: install | Esc: cancel)", name)
+                } else {
+                    format!("Versions of {} (Enter: install | Esc: cancel)", name)
+                })
+                .borders(Borders::ALL)
+                .style(Style::default().bg(Color::DarkGray)),
+        )
+        .highlight_style(Style::default().fg(Color::White).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+    
+    let mut state = ratatui::widgets::ListState::default();
+    state.select(Some(app.selected_version));
+    
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_install_dialog(f: &mut Frame, app: &App) {
+    let area = centered_rect_rows(60, 9, f.size());
+    
+    // Clear the area
+    f.render_widget(Clear, area);
+    
+    let dialog = Block::default()
+        .title("Install Package")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(Color::DarkGray));
+    
+    f.render_widget(dialog, area);
+    
+    // Create the inner area for content
+    let inner_area = Rect {
+        x: area.x + 2,
+        y: area.y + 1,
+        width: area.width.saturating_sub(4),
+        height: area.height.saturating_sub(2),
+    };
+    
+    let row = |offset: u16| Rect {
+        x: inner_area.x,
+        y: inner_area.y + offset,
+        width: inner_area.width,
+        height: 1,
+    };
+    
+    // Room left for input after the "> " prompt
+    let field_width = (inner_area.width as usize).saturating_sub(2);
+    
+    let field_style = |active: bool| {
+        if active {
+            Style::default().fg(Color::White).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(Color::Gray)
+        }
+    };
+    
+    f.render_widget(Paragraph::new("Enter package name:"), row(0));
+    f.render_widget(
+        Paragraph::new(if app.editing_index {
+            Line::from(format!("> {}", tail_to_width(&app.input_text, field_width)))
+        } else {
+            input_line(&app.input_text, app.input_cursor, field_width)
+        })
+        .style(field_style(!app.editing_index)),
+        row(1),
+    );
+    
+    // Show which index the install will hit so nobody is surprised
+    f.render_widget(Paragraph::new("Index URL (Up/Down to edit):"), row(2));
+    let index_text = if !app.index_override.is_empty() || app.editing_index {
+        format!("> {}", tail_to_width(&app.index_override, field_width))
+    } else {
+        match &app.install_options.index_url {
+            Some(url) => format!("  {} (from config)", url),
+            None => "  PyPI (default)".to_string(),
+        }
+    };
+    f.render_widget(Paragraph::new(index_text).style(field_style(app.editing_index)), row(3));
+    
+    // Pre-releases are opt-in per install, so make it obvious when they're on
+    let pre = if app.install_pre {
+        Paragraph::new("Pre-releases: ON (--pre)").style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+    } else {
+        Paragraph::new("Pre-releases: off").style(Style::default().fg(Color::Gray))
+    };
+    f.render_widget(pre, row(4));
+    
+    let suggestions = app.install_suggestions();
+    if !suggestions.is_empty() {
+        let more = if suggestions.len() == MAX_SUGGESTIONS { " …" } else { "" };
+        let text = format!("Tab: {}{}", suggestions.join("  "), more);
+        f.render_widget(
+            Paragraph::new(truncate_to_width(&text, inner_area.width as usize)).style(Style::default().fg(Color::Cyan)),
+            row(5),
+        );
+    }
+    
+    let help_widget = Paragraph::new("Enter: Install | Tab: Complete/Preview | Ctrl+P: Pre-releases | Esc: Cancel")
+        .style(Style::default().fg(Color::Gray));
+    f.render_widget(help_widget, row(inner_area.height.saturating_sub(1)));
+}
+
+fn render_install_preview(f: &mut Frame, app: &App) {
+    let area = centered_rect(60, 60, f.size());
+    
+    // Clear the area
+    f.render_widget(Clear, area);
+    
+    let title = format!("Install '{}' - dry run", app.input_text);
+    let dialog = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .style(Style::default().bg(Color::DarkGray));
+    
+    f.render_widget(dialog, area);
+    
+    // Create the inner area for content
+    let inner_area = Rect {
+        x: area.x + 2,
+        y: area.y + 1,
+        width: area.width.saturating_sub(4),
+        height: area.height.saturating_sub(2),
+    };
+    
+    let content = if app.install_preview.is_empty() {
+        "Nothing would be installed; the requirement is already satisfied.".to_string()
+    } else {
+        let lines: Vec<String> = app
+            .install_preview
+            .iter()
+            .map(|pkg| format!("  {} {}", pkg.name, pkg.version))
+            .collect();
+        format!("pip would install {} package(s):\n{}", app.install_preview.len(), lines.join("\n"))
+    };
+    
+    let content_area = Rect {
+        height: inner_area.height.saturating_sub(2),
+        ..inner_area
+    };
+    f.render_widget(Paragraph::new(content), content_area);
+    
+    // Render help text on the last line
+    let help_area = Rect {
+        x: inner_area.x,
+        y: inner_area.y + inner_area.height.saturating_sub(1),
+        width: inner_area.width,
+        height: 1,
+    };
+    
+    let help_widget = Paragraph::new("y: Install | n/Esc: Back")
+        .style(Style::default().fg(Color::Gray));
+    
+    f.render_widget(help_widget, help_area);
+}
+
+fn render_cleanup_dialog(f: &mut Frame, app: &App) {
+    let area = centered_rect(70, 60, f.size());
+    
+    // Clear the area
+    f.render_widget(Clear, area);
+    
+    let dialog = Block::default()
+        .title("Clean Up Broken Environments")
+        .borders(Borders::ALL)
+        .style(Style::default().bg(Color::DarkGray));
+    
+    f.render_widget(dialog, area);
+    
+    // Create the inner area for content
+    let inner_area = Rect {
+        x: area.x + 2,
+        y: area.y + 1,
+        width: area.width.saturating_sub(4),
+        height: area.height.saturating_sub(2),
+    };
+    
+    let mut lines = Vec::new();
+    if !app.cleanup_candidates.is_empty() {
+        lines.push(format!("Delete {} environment(s) with a missing or broken interpreter:", app.cleanup_candidates.len()));
+        lines.extend(app.cleanup_candidates.iter().map(|path| format!("  {}", path.display())));
+    }
+    if !app.cleanup_reported.is_empty() {
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines.push("Broken, but not managed by LazyEnv (left alone):".to_string());
+        lines.extend(app.cleanup_reported.iter().map(|name| format!("  {}", name)));
+    }
+    
+    let content_area = Rect {
+        height: inner_area.height.saturating_sub(2),
+        ..inner_area
+    };
+    f.render_widget(Paragraph::new(lines.join("\n")), content_area);
+    
+    // Render help text on the last line
+    let help_area = Rect {
+        x: inner_area.x,
+        y: inner_area.y + inner_area.height.saturating_sub(1),
+        width: inner_area.width,
+        height: 1,
+    };
+    
+    let help = if app.cleanup_candidates.is_empty() {
+        "Esc: Close"
+    } else {
+        "y: Delete | r: Repair in place (python -m venv --upgrade) | n/Esc: Cancel"
+    };
+    let help_widget = Paragraph::new(help)
+        .style(Style::default().fg(Color::Gray));
+    
+    f.render_widget(help_widget, help_area);
+}
+
+fn render_log_view(f: &mut Frame, app: &App) {
+    let area = centered_rect(80, 80, f.size());
+    
+    // Clear the area
+    f.render_widget(Clear, area);
+    
+    let content = if app.log.is_empty() {
+        "Nothing logged yet.".to_string()
+    } else {
+        app.log.join("\n")
+    };
+    
+    let log_widget = Paragraph::new(content)
+        .block(
+            Block::default()
+                .title("Log (↑/↓/PgUp/PgDn: scroll | l/Esc: close)")
+                .borders(Borders::ALL)
+                .style(Style::default().bg(Color::DarkGray)),
+        )
+        .scroll((app.log_scroll, 0));
+    
+    f.render_widget(log_widget, area);
+}
+
+fn render_package_details(f: &mut Frame, app: &App) {
+    let Some(metadata) = &app.package_metadata else {
+        return;
+    };
+    let area = centered_rect(80, 80, f.size());
+    
+    // Clear the area
+    f.render_widget(Clear, area);
+    
+    let or_none = |value: &str| if value.is_empty() { "-".to_string() } else { value.to_string() };
+    let list = |names: &[String]| if names.is_empty() { "-".to_string() } else { names.join(", ") };
+    let lines = [
+        format!("Name:        {}", metadata.name),
+        format!("Version:     {}", metadata.version),
+        format!("Summary:     {}", or_none(&metadata.summary)),
+        format!("Author:      {}", or_none(&metadata.author)),
+        format!("License:     {}", or_none(&metadata.license)),
+        format!("Homepage:    {}", or_none(&metadata.home_page)),
+        format!("Location:    {}", or_none(&metadata.location)),
+        format!("Requires:    {}", list(&metadata.requires)),
+        format!("Required by: {}", list(&metadata.required_by)),
+        format!("Files:       {}", metadata.files.as_ref().map_or_else(|| "unknown".to_string(), |files| files.len().to_string())),
+        format!("Editable:    {}", or_none(&metadata.editable_location)),
+    ];
+    
+    let details = Paragraph::new(lines.join("\n"))
+        .block(
+            Block::default()
+                .title(format!("{} (↑/↓/PgUp/PgDn: scroll | f: files | Enter/Esc: close)", metadata.name))
+                .borders(Borders::ALL)
+                .style(Style::default().bg(Color::DarkGray)),
+        )
+        .wrap(Wrap { trim: false })
+        .scroll((app.metadata_scroll, 0));
+    
+    f.render_widget(details, area);
+}
+
+fn render_package_files(f: &mut Frame, app: &App) {
+    let Some(metadata) = &app.package_metadata else {
+        return;
+    };
+    let area = centered_rect(80, 80, f.size());
+    
+    // Clear the area
+    f.render_widget(Clear, area);
+    
+    let files = metadata.files.as_deref().unwrap_or_default();
+    let mut lines = Vec::new();
+    // An editable install only lists its metadata; the code is in the project
+    if !metadata.editable_location.is_empty() {
+        lines.push(Line::styled(
+            format!("Editable install: the code lives in {}", metadata.editable_location),
+            Style::default().fg(Color::Yellow),
+        ));
+        lines.push(Line::from(""));
+    }
+    if metadata.files.is_none() {
+        lines.push(Line::from("pip didn't list any files for this package (no RECORD file)."));
+    }
+    lines.extend(files.iter().map(|file| Line::from(file.as_str())));
+    
+    let title = format!(
+        "{} files of {} under {} (↑/↓/PgUp/PgDn: scroll | f/Esc: back)",
+        files.len(), metadata.name, metadata.location,
+    );
+    let files_widget = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .style(Style::default().bg(Color::DarkGray)),
+        )
+        .scroll((app.files_scroll, 0));
+    
+    f.render_widget(files_widget, area);
+}
+
+fn render_requirements_diff(f: &mut Frame, app: &App) {
+    let area = centered_rect(80, 80, f.size());
+    
+    // Clear the area
+    f.render_widget(Clear, area);
+    
+    let Some((path, diff)) = &app.requirements_diff else {
+        return;
+    };
+    let title = format!("{} vs installed (↑/↓/PgUp/PgDn: scroll | =/Esc: close)", path.display());
+    
+    let heading = |text: String, color: Color| {
+        Line::styled(text, Style::default().fg(color).add_modifier(Modifier::BOLD))
+    };
+    let mut lines = Vec::new();
+    if diff.missing.is_empty() && diff.mismatched.is_empty() {
+        lines.push(heading(format!("All {} requirements are satisfied", diff.matched), Color::Green));
+    } else {
+        lines.push(Line::from(format!("{} requirements satisfied", diff.matched)));
+    }
+    
+    if !diff.missing.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(heading(format!("Missing ({})", diff.missing.len()), Color::Red));
+        lines.extend(diff.missing.iter().map(|requirement| Line::from(format!("  {}", requirement.text))));
+    }
+    if !diff.mismatched.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(heading(format!("Version mismatch ({})", diff.mismatched.len()), Color::Yellow));
+        lines.extend(diff.mismatched.iter().map(|(requirement, installed, certain)| {
+            let note = if *certain { "" } else { " (can't compare these versions)" };
+            Line::from(vec![
+                Span::raw(format!("  {}", requirement.text)),
+                Span::styled(format!("  installed: {}{}", installed, note), Style::default().fg(Color::Yellow)),
+            ])
+        }));
+    }
+    if !diff.extra.is_empty() {
+        lines.push(Line::from(""));
+        // Dependencies of listed packages show up here unless the file is a full freeze
+        lines.push(heading(format!("Not in the file ({})", diff.extra.len()), Color::Cyan));
+        lines.extend(diff.extra.iter().map(|pkg| Line::from(format!("  {}=={}", pkg.name, pkg.version))));
+    }
+    
+    let diff_widget = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .style(Style::default().bg(Color::DarkGray)),
+        )
+        .scroll((app.requirements_diff_scroll, 0));
+    
+    f.render_widget(diff_widget, area);
+}
+
+fn render_history_view(f: &mut Frame, app: &App) {
+    let area = centered_rect(80, 80, f.size());
+    
+    // Clear the area
+    f.render_widget(Clear, area);
+    
+    let lines: Vec<Line> = if app.history.is_empty() {
+        vec![Line::from("No changes recorded yet.")]
+    } else {
+        app.history
+            .iter()
+            .map(|entry| {
+                let when = relative_time(UNIX_EPOCH + Duration::from_secs(entry.timestamp));
+                let mut target = match (&entry.package, &entry.version) {
+                    (Some(name), Some(version)) => format!("{} {} in {}", name, version, entry.environment),
+                    (Some(name), None) => format!("{} in {}", name, entry.environment),
+                    _ => entry.environment.clone(),
+                };
+                let (mark, color) = match &entry.error {
+                    None => ("✓", Color::Green),
+                    Some(error) => {
+                        target.push_str(&format!(" ({})", error));
+                        ("✗", Color::Red)
+                    }
+                };
+                Line::from(vec![
+                    Span::styled(format!("{} ", mark), Style::default().fg(color)),
+                    Span::raw(format!("{:<10} {:<10} {}", when, entry.operation.label(), target)),
+                ])
+            })
+            .collect()
+    };
+    
+    let history_widget = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title("History, newest first (↑/↓/PgUp/PgDn: scroll | H/Esc: close)")
+                .borders(Borders::ALL)
+                .style(Style::default().bg(Color::DarkGray)),
+        )
+        .scroll((app.history_scroll, 0));
+    
+    f.render_widget(history_widget, area);
+}
+
+fn render_disk_usage(f: &mut Frame, app: &App) {
+    let area = centered_rect(80, 80, f.size());
+    
+    // Clear the area
+    f.render_widget(Clear, area);
+    
+    let total: u64 = app.disk_usage.iter().map(|&(_, _, size)| size).sum();
+    let mut title = format!("{} virtual environments using {}", app.disk_usage.len(), format_size(total));
+    if app.disk_usage_pending > 0 {
+        title.push_str(&format!(" (measuring, {} to go…)", app.disk_usage_pending));
+    }
+    title.push_str(" (↑/↓/PgUp/PgDn: scroll | D/Esc: close)");
+    
+    let lines: Vec<Line> = if app.disk_usage.is_empty() && app.disk_usage_pending == 0 {
+        vec![Line::from("No virtual environments found.")]
+    } else {
+        app.disk_usage
+            .iter()
+            .map(|(name, path, size)| {
+                Line::from(vec![
+                    Span::styled(format!("{:>9}  ", format_size(*size)), Style::default().fg(Color::Yellow)),
+                    Span::raw(format!("{}  ", name)),
+                    Span::styled(path.display().to_string(), Style::default().fg(Color::Gray)),
+                ])
+            })
+            .collect()
+    };
+    
+    let usage_widget = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .style(Style::default().bg(Color::DarkGray)),
+        )
+        .scroll((app.disk_usage_scroll, 0));
+    
+    f.render_widget(usage_widget, area);
+}
+
+fn render_dotenv(f: &mut Frame, app: &App) {
+    let area = centered_rect(80, 80, f.size());
+    
+    // Clear the area
+    f.render_widget(Clear, area);
+    
+    let path = app.dotenv_path.as_ref().map_or_else(String::new, |path| path.display().to_string());
+    let title = format!("{} ({} variables) (↑/↓/PgUp/PgDn: scroll | E/Esc: close)", path, app.dotenv_vars.len());
+    
+    let lines: Vec<Line> = if app.dotenv_vars.is_empty() {
+        vec![Line::from("No variables defined.")]
+    } else {
+        app.dotenv_vars
+            .iter()
+            .map(|(key, value)| {
+                let value_style = if value == dotenv::MASK {
+                    Style::default().fg(Color::Gray)
+                } else {
+                    Style::default()
+                };
+                Line::from(vec![
+                    Span::styled(key.clone(), Style::default().fg(Color::Yellow)),
+                    Span::raw(" = "),
+                    // Multi-line values would break the one-variable-per-line layout
+                    Span::styled(value.replace('\n', "\\n"), value_style),
+                ])
+            })
+            .collect()
+    };
+    
+    let dotenv_widget = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .style(Style::default().bg(Color::DarkGray)),
+        )
+        .scroll((app.dotenv_scroll, 0));
+    
+    f.render_widget(dotenv_widget, area);
+}
+
+fn render_sys_path(f: &mut Frame, app: &App) {
+    let area = centered_rect(80, 80, f.size());
+    
+    // Clear the area
+    f.render_widget(Clear, area);
+    
+    let name = app.current_environment().map_or("environment", |env| env.name.as_str());
+    let title = format!("sys.path of {} (↑/↓/PgUp/PgDn: scroll | P/Esc: close)", name);
+    
+    let lines: Vec<Line> = app.sys_path
+        .iter()
+        .enumerate()
+        .map(|(idx, entry)| {
+            let number = Span::styled(format!("{:>3}  ", idx + 1), Style::default().fg(Color::Gray));
+            // Where installed packages are found; Debian's system Python calls it dist-packages
+            let line = if entry.is_empty() {
+                vec![number, Span::styled("(current directory)", Style::default().fg(Color::Gray))]
+            } else if entry.ends_with("site-packages") || entry.ends_with("dist-packages") {
+                vec![number, Span::styled(entry.clone(), Style::default().fg(Color::Green).add_modifier(Modifier::BOLD))]
+            } else if !std::path::Path::new(entry).exists() {
+                vec![number, Span::raw(entry.clone()), Span::styled("  (missing)", Style::default().fg(Color::Gray))]
+            } else {
+                vec![number, Span::raw(entry.clone())]
+            };
+            Line::from(line)
+        })
+        .collect();
+    
+    let sys_path_widget = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .style(Style::default().bg(Color::DarkGray)),
+        )
+        .scroll((app.sys_path_scroll, 0));
+    
+    f.render_widget(sys_path_widget, area);
+}
+
+fn render_kernels(f: &mut Frame, app: &App) {
+    let area = centered_rect(80, 80, f.size());
+    
+    // Clear the area
+    f.render_widget(Clear, area);
+    
+    let kernels = app.kernels.as_deref().unwrap_or_default();
+    let items: Vec<ListItem> = if kernels.is_empty() {
+        vec![ListItem::new("No kernels registered.")]
+    } else {
+        kernels
+            .iter()
+            .map(|kernel| {
+                // Which environment the kernel runs in, if LazyEnv knows it
+                let target = match app.environments.iter().find(|env| kernel.launches(env)) {
+                    Some(env) => Span::styled(format!("→ {}", env.name), Style::default().fg(Color::Green)),
+                    None => Span::styled(
+                        kernel.python.as_ref().map_or_else(|| "no interpreter path".to_string(), |python| python.display().to_string()),
+                        Style::default().fg(Color::Gray),
+                    ),
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{}  ", kernel.name), Style::default().fg(Color::Yellow)),
+                    Span::raw(format!("{}  ", kernel.display_name)),
+                    target,
+                ]))
+            })
+            .collect()
+    };
+    
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("Jupyter Kernels (a: register selected environment | d: remove | r: refresh | J/Esc: close)")
+                .borders(Borders::ALL)
+                .style(Style::default().bg(Color::DarkGray)),
+        )
+        .highlight_style(Style::default().fg(Color::White).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+    
+    let mut state = ratatui::widgets::ListState::default();
+    if !kernels.is_empty() {
+        state.select(Some(app.selected_kernel));
+    }
+    
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+/// A size in bytes for people, e.g. "3.4 GB".
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Frames of the spinner shown while work runs in the background
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
 fn render_status_bar(f: &mut Frame, app: &App, area: Rect) {
     let status_text = match &app.status_message {
+        _ if app.state == AppState::FilterPackages => {
+            format!("/{}  (Enter: keep filter | Esc: clear)", app.package_filter)
+        },
         Some(msg) => msg.clone(),
+        None if let Some(label) = app.creating.as_ref().or(app.working.as_ref()) => {
+            format!("{} {}… (Ctrl+X: cancel)", SPINNER[app.spinner_frame % SPINNER.len()], label)
+        },
+        None if !app.count_buffer.is_empty() => format!("Jump to: {}", app.count_buffer),
+        None if !app.search_matches.is_empty() => format!(
+            "Match {} of {} for '{}' (n/N: next/previous | Esc: end search)",
+            app.search_match + 1, app.search_matches.len(), app.search_query,
+        ),
+        None if !app.type_ahead.is_empty() => format!("Find: {}", app.type_ahead),
+        // With the details panel hidden, the selected package's summary goes here
+        None if !app.show_details
+            && app.focus == Focus::Packages
+            && let Some(pkg) = app.selected_package.and_then(|idx| app.packages.get(idx)) => {
+            format!("{} {}: {}", pkg.name, pkg.version, pkg.summary)
+        },
         None => {
-            if let Some(idx) = app.selected_environment {
-                format!("Environment: {} | Path: {}", 
-                    app.environments[idx].name,
-                    app.environments[idx].path.display())
+            if let Some(env) = app.current_environment() {
+                let location = match activation_command(env) {
+                    Some(command) if app.show_activation_command => format!("Activate: {}", command),
+                    _ => format!("Path: {}", env.path.display()),
+                };
+                let mut status = format!("Environment: {} | {}", env.name, location);
+                if let Some(created) = environment_created(&env.path) {
+                    status.push_str(&format!(" | Created: {}", relative_time(created)));
+                }
+                if let Some(url) = &app.install_options.index_url {
+                    status.push_str(&format!(" | Index: {}", url));
+                }
+                status
             } else {
                 "No environment selected".to_string()
             }
         }
     };
 
-    let status_style = if app.status_message.is_some() {
+    let status_style = if app.status_message.is_some() || app.creating.is_some() {
         Style::default().fg(Color::Yellow)
     } else {
         Style::default().fg(Color::Gray)
     };
 
-    let status_widget = Paragraph::new(status_text)
+    let status_widget = Paragraph::new(truncate_to_width(&status_text, area.width as usize))
         .style(status_style);
 
     f.render_widget(status_widget, area);
 }
 
-fn render_input_dialog(f: &mut Frame, title: &str, prompt: &str, input: &str) {
+/// A rough "3 months ago" description of how long ago `time` was.
+fn relative_time(time: SystemTime) -> String {
+    let Ok(elapsed) = SystemTime::now().duration_since(time) else {
+        return "just now".to_string();
+    };
+    
+    let secs = elapsed.as_secs();
+    let (count, unit) = match secs {
+        0..=59 => return "just now".to_string(),
+        60..=3599 => (secs / 60, "minute"),
+        3600..=86_399 => (secs / 3600, "hour"),
+        86_400..=2_591_999 => (secs / 86_400, "day"),
+        2_592_000..=31_535_999 => (secs / 2_592_000, "month"),
+        _ => (secs / 31_536_000, "year"),
+    };
+    
+    format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" })
+}
+
+fn render_input_dialog(f: &mut Frame, title: &str, prompt: &str, input: &str, cursor: usize) {
     let area = centered_rect(60, 6, f.size());
     
     // Clear the area
@@ -354,7 +1618,7 @@ fn render_input_dialog(f: &mut Frame, title: &str, prompt: &str, input: &str) {
         height: 1,
     };
     
-    let input_text = format!("> {}", input);
+    let input_text = input_line(input, cursor, (input_area.width as usize).saturating_sub(2));
     let input_widget = Paragraph::new(input_text)
         .style(Style::default().fg(Color::White));
     
@@ -374,8 +1638,9 @@ fn render_input_dialog(f: &mut Frame, title: &str, prompt: &str, input: &str) {
     f.render_widget(help_widget, help_area);
 }
 
-fn render_confirm_dialog(f: &mut Frame, title: &str, message: &str) {
-    let area = centered_rect(60, 6, f.size());
+fn render_confirm_dialog(f: &mut Frame, title: &str, message: &str, warning: Option<&str>) {
+    let height = if warning.is_some() { 8 } else { 6 };
+    let area = centered_rect_rows(60, height, f.size());
     
     // Clear the area
     f.render_widget(Clear, area);
@@ -404,13 +1669,29 @@ fn render_confirm_dialog(f: &mut Frame, title: &str, message: &str) {
         height: 2,
     };
     
-    let message_widget = Paragraph::new(message);
+    let message_widget = Paragraph::new(message)
+        .wrap(Wrap { trim: true });
     f.render_widget(message_widget, message_area);
     
-    // Render help text
+    // Render warning below the message
+    if let Some(warning) = warning {
+        let warning_area = Rect {
+            x: inner_area.x,
+            y: inner_area.y + 2,
+            width: inner_area.width,
+            height: 2,
+        };
+        
+        let warning_widget = Paragraph::new(warning)
+            .style(Style::default().fg(Color::Yellow))
+            .wrap(Wrap { trim: true });
+        f.render_widget(warning_widget, warning_area);
+    }
+    
+    // Render help text on the last line
     let help_area = Rect {
         x: inner_area.x,
-        y: inner_area.y + 3,
+        y: inner_area.y + inner_area.height.saturating_sub(1),
         width: inner_area.width,
         height: 1,
     };
@@ -421,6 +1702,113 @@ fn render_confirm_dialog(f: &mut Frame, title: &str, message: &str) {
     f.render_widget(help_widget, help_area);
 }
 
+/// Render an input field as "> text" with the character under the cursor
+/// highlighted. Scrolls so the cursor always stays within `width` cells.
+fn input_line(text: &str, cursor: usize, width: usize) -> Line<'_> {
+    let cursor_style = Style::default().add_modifier(Modifier::REVERSED);
+    
+    // Leave one cell for the cursor itself
+    let before = tail_to_width(&text[..cursor], width.saturating_sub(1));
+    let (under, after) = match text[cursor..].chars().next() {
+        Some(c) => text[cursor..].split_at(c.len_utf8()),
+        None => (" ", ""),
+    };
+    
+    Line::from(vec![
+        Span::raw("> "),
+        Span::raw(before),
+        Span::styled(under, cursor_style),
+        Span::raw(after),
+    ])
+}
+
+/// Rows `text` takes when word-wrapped to `width` cells, matching how
+/// `Paragraph` wraps with `trim: true` closely enough to bound scrolling.
+fn wrapped_height(text: &str, width: usize) -> usize {
+    if width == 0 {
+        return 1;
+    }
+    
+    let mut rows = 1;
+    let mut row_width = 0;
+    for word in text.split_whitespace() {
+        let word_width = word.width();
+        if row_width > 0 && row_width + 1 + word_width <= width {
+            row_width += 1 + word_width;
+            continue;
+        }
+        if row_width > 0 {
+            rows += 1;
+        }
+        // Words longer than a row are broken across rows
+        rows += word_width.saturating_sub(1) / width;
+        row_width = match word_width % width {
+            0 if word_width > 0 => width,
+            rest => rest,
+        };
+    }
+    rows
+}
+
+/// Cut `text` to at most `max_width` terminal cells, marking the cut with an ellipsis.
+/// Wide characters (CJK, emoji) count as two cells.
+fn truncate_to_width(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    if max_width == 0 {
+        return String::new();
+    }
+    
+    let mut result = String::new();
+    let mut width = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        // Leave one cell for the ellipsis
+        if width + char_width > max_width - 1 {
+            break;
+        }
+        result.push(c);
+        width += char_width;
+    }
+    result.push('…');
+    result
+}
+
+/// The longest suffix of `text` that fits in `max_width` terminal cells.
+fn tail_to_width(text: &str, max_width: usize) -> &str {
+    let mut width = 0;
+    let mut start = text.len();
+    for (idx, c) in text.char_indices().rev() {
+        width += c.width().unwrap_or(0);
+        if width > max_width {
+            break;
+        }
+        start = idx;
+    }
+    &text[start..]
+}
+
+/// Like `centered_rect`, but with a fixed height in rows (clamped to the available area)
+fn centered_rect_rows(percent_x: u16, height: u16, r: Rect) -> Rect {
+    let height = height.min(r.height);
+    let popup_area = Rect {
+        x: r.x,
+        y: r.y + (r.height - height) / 2,
+        width: r.width,
+        height,
+    };
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(popup_area)[1]
+}
+
 /// Helper function to create a centered rect using up certain percentage of the available rect
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
@@ -0,0 +1,62 @@
+//! How often and how recently each environment was used, persisted in the
+//! state directory so the "recent" and "frequent" sort orders survive restarts.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::paths;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct UsageEntry {
+    /// Number of times the environment's packages were opened
+    pub count: u64,
+    /// Seconds since the Unix epoch of the last use
+    pub last_used: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Usage {
+    entries: HashMap<PathBuf, UsageEntry>,
+}
+
+impl Usage {
+    pub fn path() -> io::Result<PathBuf> {
+        Ok(paths::state_dir()?.join("usage.json"))
+    }
+
+    /// Load usage data, starting fresh if the file is missing or unreadable.
+    pub fn load() -> Self {
+        Self::path()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path()?;
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(path, content)
+    }
+
+    /// Count a use of the environment at `path`.
+    pub fn record(&mut self, path: &Path) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+
+        let entry = self.entries.entry(path.to_path_buf()).or_default();
+        entry.count += 1;
+        entry.last_used = now;
+    }
+
+    /// Usage of the environment at `path`; zero if it was never used.
+    pub fn get(&self, path: &Path) -> UsageEntry {
+        self.entries.get(path).copied().unwrap_or_default()
+    }
+}
@@ -0,0 +1,105 @@
+//! Lightweight parsing of Python package versions.
+//!
+//! Only the leading numeric release segment (`1.2.3` in `1.2.3rc1+local`) is
+//! interpreted; anything we can't parse is treated as unknown rather than guessed.
+
+/// How far an installed version lags behind the latest release.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Staleness {
+    Current,
+    Minor,
+    Major,
+}
+
+/// The numeric release components of a version, e.g. `[2, 0, 1]` for `2.0.1.dev3`.
+/// Returns `None` if the version doesn't start with a number.
+pub fn release_components(version: &str) -> Option<Vec<u64>> {
+    let version = version.trim().trim_start_matches(['v', 'V']);
+    // Drop an epoch like "1!" - it isn't part of the release segment
+    let version = version.split_once('!').map_or(version, |(_, rest)| rest);
+
+    let mut components = Vec::new();
+    for part in version.split('.') {
+        let digits: String = part.chars().take_while(|c| c.is_ascii_digit()).collect();
+        if digits.is_empty() {
+            break;
+        }
+        components.push(digits.parse().ok()?);
+        // A suffix like "0rc1" ends the release segment
+        if digits.len() != part.len() {
+            break;
+        }
+    }
+
+    if components.is_empty() {
+        None
+    } else {
+        Some(components)
+    }
+}
+
+/// `version` cut to its first three release components, with an ellipsis
+/// marking anything dropped: `2.0.0…` for `2.0.0.dev20240101+cu121`. Versions
+/// that don't start with a number, or carry an epoch, come back unchanged.
+pub fn short_version(version: &str) -> String {
+    if version.contains('!') {
+        return version.to_string();
+    }
+
+    let mut end = 0;
+    for (idx, part) in version.split('.').take(3).enumerate() {
+        let digits = part.chars().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 {
+            break;
+        }
+        // Count the dot before every component but the first
+        end += digits + usize::from(idx > 0);
+        if digits != part.len() {
+            break;
+        }
+    }
+
+    if end == 0 || end == version.len() {
+        version.to_string()
+    } else {
+        format!("{}…", &version[..end])
+    }
+}
+
+/// Compare an installed version with the latest available one.
+pub fn staleness(installed: &str, latest: &str) -> Staleness {
+    if installed == latest {
+        return Staleness::Current;
+    }
+
+    match (release_components(installed), release_components(latest)) {
+        (Some(installed), Some(latest)) if latest.first() > installed.first() => Staleness::Major,
+        (Some(installed), Some(latest)) if latest <= installed => Staleness::Current,
+        _ => Staleness::Minor,
+    }
+}
+
+/// Order two versions by their release segments, padding the shorter one
+/// with zeros so `1.2` equals `1.2.0`. Versions whose release segments are
+/// equal but that differ otherwise (`1.2rc1` vs `1.2`) can't be ordered
+/// without a full PEP 440 parser, so they're `None` like unparseable ones.
+pub fn compare(a: &str, b: &str) -> Option<std::cmp::Ordering> {
+    if a.trim() == b.trim() {
+        return Some(std::cmp::Ordering::Equal);
+    }
+
+    let (mut a_components, mut b_components) = (release_components(a)?, release_components(b)?);
+    let len = a_components.len().max(b_components.len());
+    a_components.resize(len, 0);
+    b_components.resize(len, 0);
+    match a_components.cmp(&b_components) {
+        std::cmp::Ordering::Equal if has_suffix(a) || has_suffix(b) => None,
+        ordering => Some(ordering),
+    }
+}
+
+/// Whether anything follows the release segment, e.g. `rc1` or `.post2`.
+fn has_suffix(version: &str) -> bool {
+    let version = version.trim().trim_start_matches(['v', 'V']);
+    version.contains(|c: char| !(c.is_ascii_digit() || c == '.'))
+}